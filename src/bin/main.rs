@@ -1,6 +1,95 @@
-use system::{run, Result};
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+use system::{
+    backfill, regenerate, run_with_options, BackfillLimit, BlockNumber, Context, Network,
+    ReportModule, Result, RunOptions, ScrapingModule,
+};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Path to the configuration file.
+    #[arg(long, env = "MONITOR_CONFIG", default_value = "config/config.yml")]
+    config: String,
+    /// Runs a single collection and report cycle, then exits.
+    #[arg(long)]
+    once: bool,
+    /// Only monitors the accounts of this network, overriding the config.
+    #[arg(long)]
+    network: Option<Network>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fetches the history of a single account once, then exits.
+    Backfill {
+        /// The address of the account.
+        #[arg(long)]
+        address: String,
+        /// The network of the account, e.g. `polkadot`.
+        #[arg(long)]
+        network: Network,
+        /// The scraping module to backfill, e.g. `transfer`.
+        #[arg(long)]
+        module: ScrapingModule,
+        /// The maximum amount of pages to fetch.
+        #[arg(long)]
+        max_pages: Option<usize>,
+        /// Stops once the history reaches back to this block.
+        #[arg(long)]
+        oldest_block: Option<u64>,
+    },
+    /// Generates the reports of a module from the stored data once, then exits.
+    Regenerate {
+        /// The report module to generate, e.g. `transfers`.
+        #[arg(long)]
+        module: ReportModule,
+        /// The first day of the reports, e.g. `2021-06-01`.
+        #[arg(long)]
+        from: NaiveDate,
+        /// The day after the last day of the reports (exclusive).
+        #[arg(long)]
+        to: NaiveDate,
+    },
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    run().await
+    let cli = Cli::parse();
+
+    match cli.command {
+        None => {
+            let options = RunOptions {
+                once: cli.once,
+                network: cli.network,
+            };
+
+            run_with_options(&cli.config, options).await
+        }
+        Some(Command::Backfill {
+            address,
+            network,
+            module,
+            max_pages,
+            oldest_block,
+        }) => {
+            let context = Context {
+                stash: address,
+                network,
+                description: String::new(),
+                labels: Default::default(),
+            };
+            let limit = BackfillLimit {
+                max_pages,
+                oldest_block: oldest_block.map(BlockNumber::from),
+            };
+
+            backfill(&cli.config, context, module, limit).await
+        }
+        Some(Command::Regenerate { module, from, to }) => {
+            regenerate(&cli.config, module, from, to, cli.network).await
+        }
+    }
 }
@@ -1,117 +1,413 @@
-use crate::{BlockNumber, Context, Result, Timestamp};
-use reqwest::header::{CONTENT_TYPE, USER_AGENT};
-use reqwest::Client;
-use serde::{de::DeserializeOwned, Serialize};
+use crate::database::RawResponse;
+use crate::rate_limit::RateLimiter;
+use crate::{BlockNumber, Context, Database, Network, Result, Timestamp};
+use reqwest::header::{CONTENT_TYPE, RETRY_AFTER, USER_AGENT};
+use reqwest::{Client, StatusCode};
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use tokio::time::{sleep, Duration};
+use std::sync::{Mutex, OnceLock};
+use tokio::time::{sleep_until, Duration, Instant};
 
 const REQUEST_TIMEOUT: u64 = 10;
+/// How long a single request to Subscan may take, until the response body is
+/// received. Unrelated to the spacing of requests of `REQUEST_TIMEOUT`.
+const HTTP_TIMEOUT: u64 = 30;
+/// How long an API key is skipped after Subscan rejected it with a `429 Too
+/// Many Requests`, unless the response specifies a `Retry-After`.
+const KEY_BENCH_DURATION: u64 = 60;
+/// The `{network}` placeholder is replaced with the network of the requested
+/// account.
+const DEFAULT_BASE_URL: &str = "https://{network}.api.subscan.io";
+/// Identifies the requests of this crate, e.g. in the logs of Subscan.
+const DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// The version of the Subscan API. Subscan is migrating its endpoints to
+/// `/api/v2/scan/...` and deprecating the v1 endpoints, so v1 is only kept for
+/// the transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiVersion {
+    V1,
+    #[default]
+    V2,
+}
 
 pub struct ChainApi {
     client: Client,
-    guard_lock: Arc<Mutex<()>>,
+    base_url: String,
+    user_agent: String,
+    api_version: ApiVersion,
+    http_timeout: Duration,
+    /// Requests are sent without a key if none is configured.
+    keys: Vec<ApiKey>,
+    key_bench_duration: Duration,
+    pool: Mutex<KeyPoolState>,
+    /// See `with_raw_responses`.
+    raw_responses: Option<RawResponses>,
+}
+
+/// Where the raw responses are stored, and how many of them are kept.
+struct RawResponses {
+    db: Database,
+    max_entries: u64,
+}
+
+struct ApiKey {
+    key: Option<String>,
+    /// Shared by all fetchers using this key.
+    rate_limiter: RateLimiter,
+}
+
+struct KeyPoolState {
+    /// The index of the key to try first for the next request.
+    next: usize,
+    /// Until when each key is skipped after being rate limited.
+    benched_until: Vec<Option<Instant>>,
+}
+
+impl KeyPoolState {
+    fn new(keys: usize) -> Self {
+        KeyPoolState {
+            next: 0,
+            benched_until: vec![None; keys],
+        }
+    }
+    /// Selects the keys round-robin, skipping benched ones. If all keys are
+    /// benched, the one available again first is selected together with the
+    /// time to wait for.
+    fn select(&mut self, now: Instant) -> (usize, Option<Instant>) {
+        let len = self.benched_until.len();
+        let (idx, wait) = (0..len)
+            .map(|offset| (self.next + offset) % len)
+            .find(|idx| self.benched_until[*idx].is_none_or(|until| until <= now))
+            .map(|idx| (idx, None))
+            .unwrap_or_else(|| {
+                let idx = (0..len)
+                    .min_by_key(|idx| self.benched_until[*idx])
+                    .unwrap_or_default();
+                (idx, self.benched_until[idx])
+            });
+
+        self.benched_until[idx] = None;
+        self.next = (idx + 1) % len;
+        (idx, wait)
+    }
+    fn bench(&mut self, idx: usize, until: Instant) {
+        self.benched_until[idx] = Some(until);
+    }
 }
 
 impl ChainApi {
     pub fn new() -> Self {
+        Self::with_base_url(DEFAULT_BASE_URL)
+    }
+    /// Points the API at a different Subscan compatible endpoint, such as a
+    /// proxy or a mock server. May contain a `{network}` placeholder.
+    pub fn with_base_url(base_url: &str) -> Self {
         ChainApi {
             client: Client::new(),
-            guard_lock: Arc::new(Mutex::new(())),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            api_version: ApiVersion::default(),
+            http_timeout: Duration::from_secs(HTTP_TIMEOUT),
+            keys: vec![ApiKey {
+                key: None,
+                rate_limiter: RateLimiter::every(Duration::from_secs(REQUEST_TIMEOUT)),
+            }],
+            key_bench_duration: Duration::from_secs(KEY_BENCH_DURATION),
+            pool: Mutex::new(KeyPoolState::new(1)),
+            raw_responses: None,
         }
     }
-    async fn time_guard(&self) {
-        let mutex = Arc::clone(&self.guard_lock);
-        let guard = mutex.lock_owned().await;
+    /// Overrides the `User-Agent` header, which defaults to the name and
+    /// version of this crate.
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+    /// Selects the version of the Subscan endpoints. Endpoints without a v2
+    /// equivalent always use v1.
+    pub fn with_api_version(mut self, api_version: ApiVersion) -> Self {
+        self.api_version = api_version;
+        self
+    }
+    /// Fails requests which take longer than the given duration, e.g. because
+    /// of a hung connection, `HTTP_TIMEOUT` seconds by default.
+    pub fn with_http_timeout(mut self, timeout: Duration) -> Self {
+        self.http_timeout = timeout;
+        self
+    }
+    /// Limits the rate of requests per API key, one every `REQUEST_TIMEOUT`
+    /// seconds by default.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        for key in &mut self.keys {
+            key.rate_limiter = rate_limiter.same_rate();
+        }
+        self
+    }
+    /// Authenticates requests with a pool of API keys, which are used
+    /// round-robin. Each key is rate limited on its own, so the throughput
+    /// scales with the amount of keys.
+    pub fn with_api_keys(mut self, keys: Vec<String>) -> Self {
+        if keys.is_empty() {
+            return self;
+        }
+
+        let rate_limiter = &self.keys[0].rate_limiter;
+        self.keys = keys
+            .into_iter()
+            .map(|key| ApiKey {
+                key: Some(key),
+                rate_limiter: rate_limiter.same_rate(),
+            })
+            .collect();
+        self.pool = Mutex::new(KeyPoolState::new(self.keys.len()));
+        self
+    }
+    /// How long a key is skipped after it was rate limited, unless Subscan
+    /// specifies a `Retry-After`. `KEY_BENCH_DURATION` seconds by default.
+    #[cfg(test)]
+    pub fn with_key_bench_duration(mut self, duration: Duration) -> Self {
+        self.key_bench_duration = duration;
+        self
+    }
+    /// Stores the body of each response as received (gzipped) in the
+    /// `raw_responses` collection, keyed by the URL and the body of the
+    /// request, e.g. to tell changes of Subscan from parsing bugs. Only the
+    /// most recent `max_entries` responses are kept. Disabled by default.
+    pub fn with_raw_responses(mut self, db: Database, max_entries: u64) -> Self {
+        self.raw_responses = Some(RawResponses { db, max_entries });
+        self
+    }
+    /// Stores the raw response, if enabled. Failures are only logged, so
+    /// debugging never interrupts the collection.
+    async fn store_raw_response<T: Serialize>(&self, url: &str, param: &T, body: &[u8]) {
+        let raw = match &self.raw_responses {
+            Some(raw) => raw,
+            None => return,
+        };
 
-        tokio::spawn(async move {
-            // Capture guard, drops after sleeping period;
-            let _ = guard;
-            sleep(Duration::from_secs(REQUEST_TIMEOUT)).await;
-        });
+        let res = async {
+            let response = RawResponse::new(url, &serde_json::to_string(param)?, body)?;
+            raw.db.store_raw_response(&response, raw.max_entries).await
+        }
+        .await;
+
+        if let Err(err) = res {
+            warn!("Failed to store the raw response of {}: {:?}", url, err);
+        }
+    }
+    fn request_error(&self, url: &str, err: reqwest::Error) -> anyhow::Error {
+        if err.is_timeout() {
+            anyhow!("request to {} timed out after {:?}", url, self.http_timeout)
+        } else {
+            err.into()
+        }
+    }
+    fn url(&self, context: &Context, path: &str) -> String {
+        format!(
+            "{}{}",
+            self.base_url.replace("{network}", context.network.as_str()),
+            path
+        )
     }
     async fn post<T, R>(&self, url: &str, param: &T) -> Result<R>
     where
         T: Serialize,
         R: DeserializeOwned,
     {
-        let headers = [
-            ("X-API-Key".parse()?, "YOUR_KEY".parse()?),
-            (CONTENT_TYPE, "application/json".parse()?),
-            (USER_AGENT, "curl/7.68.0".parse()?),
-        ]
-        .iter()
-        .cloned()
-        .collect();
-
-        self.time_guard().await;
-
-        self.client
-            .post(url)
-            .headers(headers)
-            .json(param)
-            .send()
-            .await?
-            .json()
-            .await
-            .map_err(|err| err.into())
+        // Every key is tried at most once, unless it's available again.
+        for _ in 0..self.keys.len() {
+            let (idx, wait) = self.pool.lock().unwrap().select(Instant::now());
+            if let Some(until) = wait {
+                sleep_until(until).await;
+            }
+
+            let key = &self.keys[idx];
+            let mut headers: reqwest::header::HeaderMap = [
+                (CONTENT_TYPE, "application/json".parse()?),
+                (USER_AGENT, self.user_agent.parse()?),
+            ]
+            .iter()
+            .cloned()
+            .collect();
+            if let Some(key) = &key.key {
+                headers.insert("X-API-Key", key.parse()?);
+            }
+
+            key.rate_limiter.until_ready().await;
+
+            let resp = self
+                .client
+                .post(url)
+                .headers(headers)
+                .json(param)
+                .timeout(self.http_timeout)
+                .send()
+                .await
+                .map_err(|err| self.request_error(url, err))?;
+
+            if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+                let bench = resp
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok()?.parse().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or(self.key_bench_duration);
+
+                warn!(
+                    "API key #{} was rate limited by Subscan, skipping it for {:?}",
+                    idx, bench
+                );
+                self.pool.lock().unwrap().bench(idx, Instant::now() + bench);
+                continue;
+            }
+
+            let body = resp
+                .bytes()
+                .await
+                .map_err(|err| self.request_error(url, err))?;
+            self.store_raw_response(url, param, &body).await;
+
+            return Ok(serde_json::from_slice(&body)?);
+        }
+
+        Err(anyhow!("all API keys are rate limited by Subscan"))
     }
-    pub async fn request_transfer(
+}
+
+/// A source of on-chain account data. The Subscan API, as implemented by
+/// `ChainApi`, is the default backend, but nodes can also be queried directly
+/// via `RpcBackend`.
+#[async_trait]
+pub trait ChainBackend: Send + Sync {
+    async fn request_transfer(
+        &self,
+        context: &Context,
+        row: usize,
+        page: usize,
+    ) -> Result<Response<TransfersPage>>;
+    async fn request_reward_slash(
+        &self,
+        context: &Context,
+        row: usize,
+        page: usize,
+    ) -> Result<Response<RewardsSlashesPage>>;
+    async fn request_staking_actions(
+        &self,
+        context: &Context,
+        row: usize,
+        page: usize,
+    ) -> Result<Response<StakingActionsPage>>;
+    async fn request_nominations(&self, context: &Context) -> Result<Response<NominationsPage>>;
+    /// Checks whether the backend is reachable and accepts requests for the
+    /// network of the given account, e.g. at startup. Succeeds by default.
+    async fn check_connection(&self, _context: &Context) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChainBackend for ChainApi {
+    async fn request_transfer(
         &self,
         context: &Context,
         row: usize,
         page: usize,
     ) -> Result<Response<TransfersPage>> {
-        Ok(self
-            .post(
-                &format!(
-                    "https://{}.api.subscan.io/api/scan/transfers",
-                    context.network.as_str()
-                ),
-                &PageBody {
-                    address: &context.stash,
-                    row: row,
-                    page: page,
-                },
-            )
-            .await?)
+        let path = match self.api_version {
+            ApiVersion::V1 => "/api/scan/transfers",
+            ApiVersion::V2 => "/api/v2/scan/transfers",
+        };
+
+        self.post(
+            &self.url(context, path),
+            &PageBody {
+                address: &context.stash,
+                row,
+                page,
+            },
+        )
+        .await
     }
-    pub async fn request_reward_slash(
+    async fn request_reward_slash(
         &self,
         context: &Context,
         row: usize,
         page: usize,
     ) -> Result<Response<RewardsSlashesPage>> {
-        Ok(self
+        let path = match self.api_version {
+            ApiVersion::V1 => "/api/scan/account/reward_slash",
+            ApiVersion::V2 => "/api/v2/scan/account/reward_slash",
+        };
+
+        let mut resp: Response<RewardsSlashesPage> = self
             .post(
-                &format!(
-                    "https://{}.api.subscan.io/api/scan/account/reward_slash",
-                    context.network.as_str()
-                ),
+                &self.url(context, path),
                 &PageBody {
                     address: &context.stash,
-                    row: row,
-                    page: page,
+                    row,
+                    page,
                 },
             )
-            .await?)
+            .await?;
+
+        if self.api_version == ApiVersion::V2 {
+            for entry in resp.data.list.iter_mut().flatten() {
+                entry.fill_from_event_index();
+            }
+        }
+
+        Ok(resp)
     }
-    pub async fn request_nominations(
+    async fn request_staking_actions(
         &self,
         context: &Context,
-    ) -> Result<Response<NominationsPage>> {
-        Ok(self
-            .post(
-                &format!(
-                    "https://{}.api.subscan.io/api/scan/staking/voted",
-                    context.network.as_str()
-                ),
-                &Address {
-                    address: &context.stash,
-                },
+        row: usize,
+        page: usize,
+    ) -> Result<Response<StakingActionsPage>> {
+        // Not (yet) available as a v2 endpoint.
+        self.post(
+            &self.url(context, "/api/scan/staking_history"),
+            &PageBody {
+                address: &context.stash,
+                row,
+                page,
+            },
+        )
+        .await
+    }
+    async fn request_nominations(&self, context: &Context) -> Result<Response<NominationsPage>> {
+        // Not (yet) available as a v2 endpoint.
+        self.post(
+            &self.url(context, "/api/scan/staking/voted"),
+            &Address {
+                address: &context.stash,
+            },
+        )
+        .await
+    }
+    /// Requests a single transfer of the account. Fails if Subscan is
+    /// unreachable or returns an error code, e.g. for an invalid API key.
+    async fn check_connection(&self, context: &Context) -> Result<()> {
+        let resp = self.request_transfer(context, 1, 0).await.map_err(|err| {
+            anyhow!(
+                "Subscan is unreachable for {}: {}",
+                context.network.as_str(),
+                err
             )
-            .await?)
+        })?;
+
+        match resp.code {
+            Some(0) | None => Ok(()),
+            Some(code) => Err(anyhow!(
+                "Subscan rejected requests for {} with code {}: {}",
+                context.network.as_str(),
+                code,
+                resp.message
+            )),
+        }
     }
 }
 
@@ -130,24 +426,69 @@ struct Address<'a> {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Response<T> {
     pub code: Option<usize>,
+    /// Subscan returns `null` (or omits the field) for accounts without any
+    /// entries, which results in an empty page.
+    #[serde(
+        default,
+        deserialize_with = "null_as_default",
+        bound(deserialize = "T: Deserialize<'de> + Default")
+    )]
     pub data: T,
     pub message: String,
     pub ttl: Option<usize>,
 }
 
+fn null_as_default<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TransfersPage {
     pub count: i64,
     pub transfers: Option<Vec<Transfer>>,
 }
 
+impl TransfersPage {
+    /// Re-encodes the addresses of the transfers and of their parent accounts
+    /// with the prefix of the network, see `Network::normalize_address`, so
+    /// they can be compared with the stashes of the monitored accounts.
+    /// Addresses which can not be decoded are kept as they are.
+    pub fn normalize_addresses(&mut self, network: Network) {
+        let normalize = |address: &mut String| {
+            if let Ok(normalized) = network.normalize_address(address) {
+                *address = normalized;
+            }
+        };
+
+        for transfer in self.transfers.iter_mut().flatten() {
+            normalize(&mut transfer.from);
+            normalize(&mut transfer.to);
+            normalize(&mut transfer.from_account_display.address);
+            normalize(&mut transfer.to_account_display.address);
+            for parent in transfer
+                .from_account_display
+                .parent
+                .iter_mut()
+                .chain(transfer.to_account_display.parent.iter_mut())
+            {
+                normalize(&mut parent.address);
+            }
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Transfer {
-    pub amount: String,
+    pub amount: Balance,
     pub block_num: BlockNumber,
     pub block_timestamp: Timestamp,
     pub extrinsic_index: ExtrinsicIndex,
-    pub fee: String,
+    pub fee: Balance,
     pub from: String,
     pub from_account_display: FromAccountDisplay,
     pub hash: String,
@@ -156,9 +497,88 @@ pub struct Transfer {
     pub success: bool,
     pub to: String,
     pub to_account_display: ToAccountDisplay,
+    /// Whether the requested account sent or received the transfer, if
+    /// Subscan reports it. See `Transfer::is_outgoing`.
+    #[serde(
+        default,
+        alias = "transfer_type",
+        deserialize_with = "deserialize_direction",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub direction: Option<TransferDirection>,
+    /// The index of the transfer event within its block. Missing in
+    /// transfers stored before it was collected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_idx: Option<i64>,
+}
+
+impl Transfer {
+    /// Identifies the transfer among the transfers of an account. Transfers
+    /// of the same extrinsic, e.g. of a batch call, share the extrinsic index
+    /// and hash, so they are told apart by the index of their event.
+    pub(crate) fn key(&self) -> String {
+        match self.event_idx {
+            Some(idx) => format!("{}/{}", self.extrinsic_index, idx),
+            None => self.legacy_key(),
+        }
+    }
+    /// The key of transfers without an event index, which relies on the
+    /// sender, recipient and amount instead. Identical transfers of the same
+    /// extrinsic can not be told apart this way.
+    pub(crate) fn legacy_key(&self) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            self.extrinsic_index, self.from, self.to, self.amount
+        )
+    }
+    /// Whether the given account sent the transfer. Relies on the direction
+    /// reported by Subscan if present, which is also correct if the account
+    /// is not `from`, e.g. for proxy calls. Falls back to comparing the
+    /// account with `from` otherwise.
+    pub fn is_outgoing(&self, stash: &str) -> bool {
+        match self.direction {
+            Some(direction) => direction == TransferDirection::Outgoing,
+            None => self.from == stash,
+        }
+    }
 }
 
+/// The direction of a transfer, from the point of view of the requested
+/// account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferDirection {
+    Incoming,
+    Outgoing,
+}
+
+impl TransferDirection {
+    /// Parses the direction as returned by Subscan, e.g. `in` or `sent`.
+    fn parse(val: &str) -> Option<Self> {
+        match val.to_ascii_lowercase().as_str() {
+            "in" | "incoming" | "received" => Some(TransferDirection::Incoming),
+            "out" | "outgoing" | "sent" => Some(TransferDirection::Outgoing),
+            _ => None,
+        }
+    }
+}
+
+/// Unknown directions are treated like missing ones, so new values of
+/// Subscan do not break the parsing of transfers.
+fn deserialize_direction<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<TransferDirection>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?
+        .as_deref()
+        .and_then(TransferDirection::parse))
+}
+
+/// The v2 API omits the identity fields for accounts without an identity.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FromAccountDisplay {
     pub address: String,
     pub display: String,
@@ -169,6 +589,7 @@ pub struct FromAccountDisplay {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ToAccountDisplay {
     pub address: String,
     pub display: String,
@@ -186,7 +607,12 @@ pub struct Parent {
     pub identity: bool,
 }
 
+/// The response of `/api/scan/staking/voted`. Unlike the other endpoints, it
+/// is not paginated and has no `count`: it returns all validators nominated
+/// by the account, which are limited by `MaxNominations` (16 on Polkadot, 24
+/// on Kusama).
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NominationsPage {
     pub list: Option<Vec<Nomination>>,
 }
@@ -194,8 +620,8 @@ pub struct NominationsPage {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Nomination {
     pub rank_validator: Option<i64>,
-    pub bonded_nominators: String,
-    pub bonded_owner: String,
+    pub bonded_nominators: Balance,
+    pub bonded_owner: Balance,
     pub count_nominators: i64,
     pub validator_prefs_value: i64,
     pub latest_mining: i64,
@@ -205,9 +631,9 @@ pub struct Nomination {
     pub controller_account_display: Option<::serde_json::Value>,
     pub node_name: String,
     pub reward_account: String,
-    pub reward_pot_balance: String,
+    pub reward_pot_balance: Balance,
     pub grandpa_vote: i64,
-    pub bonded: String,
+    pub bonded: Balance,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -220,6 +646,152 @@ pub struct StashAccountDisplay {
     pub parent: Option<Parent>,
 }
 
+/// An amount in Planck, which Subscan returns as a string of digits, e.g.
+/// `"10000000000"`, or as an empty string if missing. Parsed once when
+/// deserialized, but serialized as received, so stored entries (and their
+/// checksums) are not changed by a round trip. Malformed values are kept too,
+/// but have no amount.
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Balance {
+    raw: String,
+    planck: Option<u128>,
+}
+
+impl Balance {
+    /// The amount in Planck, `None` if missing or malformed.
+    pub fn planck(&self) -> Option<u128> {
+        self.planck
+    }
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+}
+
+impl From<String> for Balance {
+    fn from(raw: String) -> Self {
+        let planck = raw.parse().ok();
+        Balance { raw, planck }
+    }
+}
+
+impl From<&str> for Balance {
+    fn from(raw: &str) -> Self {
+        Balance::from(raw.to_string())
+    }
+}
+
+impl From<u128> for Balance {
+    fn from(planck: u128) -> Self {
+        Balance {
+            raw: planck.to_string(),
+            planck: Some(planck),
+        }
+    }
+}
+
+impl fmt::Display for Balance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl Serialize for Balance {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+impl<'de> Deserialize<'de> for Balance {
+    /// Also accepts numbers, and `null` as a missing value.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct BalanceVisitor;
+
+        impl<'de> Visitor<'de> for BalanceVisitor {
+            type Value = Balance;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an amount as a string or number")
+            }
+            fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<Balance, E> {
+                Ok(Balance::from(value))
+            }
+            fn visit_string<E: de::Error>(self, value: String) -> std::result::Result<Balance, E> {
+                Ok(Balance::from(value))
+            }
+            fn visit_u64<E: de::Error>(self, value: u64) -> std::result::Result<Balance, E> {
+                Ok(Balance::from(value as u128))
+            }
+            fn visit_u128<E: de::Error>(self, value: u128) -> std::result::Result<Balance, E> {
+                Ok(Balance::from(value))
+            }
+            fn visit_i64<E: de::Error>(self, value: i64) -> std::result::Result<Balance, E> {
+                Ok(Balance::from(value.to_string()))
+            }
+            fn visit_f64<E: de::Error>(self, value: f64) -> std::result::Result<Balance, E> {
+                Ok(Balance::from(value.to_string()))
+            }
+            fn visit_unit<E: de::Error>(self) -> std::result::Result<Balance, E> {
+                Ok(Balance::default())
+            }
+            fn visit_none<E: de::Error>(self) -> std::result::Result<Balance, E> {
+                Ok(Balance::default())
+            }
+        }
+
+        deserializer.deserialize_any(BalanceVisitor)
+    }
+}
+
+/// An amount in whole tokens, e.g. `2.5` DOT, kept as Planck and the token
+/// decimals, so it's exact no matter how large. Displayed as a decimal without
+/// trailing zeros.
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct TokenAmount {
+    planck: u128,
+    decimals: u32,
+}
+
+impl TokenAmount {
+    pub fn new(planck: u128, decimals: u32) -> Self {
+        TokenAmount { planck, decimals }
+    }
+    pub fn is_zero(&self) -> bool {
+        self.planck == 0
+    }
+    /// Adds up the amounts, `None` on overflow or if the decimals differ.
+    pub fn checked_add(self, other: TokenAmount) -> Option<TokenAmount> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+
+        Some(TokenAmount::new(
+            self.planck.checked_add(other.planck)?,
+            self.decimals,
+        ))
+    }
+    /// Whether the amount is at or above the threshold in whole tokens. Only
+    /// the comparison is approximated, the amount itself is not changed.
+    pub fn at_least(&self, threshold: f64) -> bool {
+        self.planck as f64 / 10_f64.powi(self.decimals as i32) >= threshold
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = 10_u128.pow(self.decimals);
+        let (whole, fraction) = (self.planck / unit, self.planck % unit);
+        if fraction == 0 {
+            return write!(f, "{}", whole);
+        }
+
+        let fraction = format!("{:0width$}", fraction, width = self.decimals as usize);
+        write!(f, "{}.{}", whole, fraction.trim_end_matches('0'))
+    }
+}
+
 #[derive(Default, Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct ExtrinsicIndex(String);
 
@@ -232,30 +804,145 @@ impl fmt::Display for ExtrinsicIndex {
 #[derive(Default, Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct ExtrinsicHash(String);
 
+impl fmt::Display for ExtrinsicHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case", default)]
 pub struct RewardsSlashesPage {
     count: i64,
     pub list: Option<Vec<RewardSlash>>,
 }
 
+/// The v2 API only returns the `amount` and the `event_index` (formatted as
+/// `<block_num>-<event_idx>`) of an event, but neither `params` nor the
+/// block number, which is derived from the event index instead.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct RewardSlash {
-    pub amount: String,
+    pub amount: Balance,
     pub event_index: String,
+    #[serde(default)]
     pub block_num: BlockNumber,
+    #[serde(default)]
     pub extrinsic_idx: i64,
     pub module_id: String,
     pub event_id: String,
+    #[serde(default)]
     pub params: String,
+    #[serde(default)]
     pub extrinsic_hash: ExtrinsicHash,
+    #[serde(default)]
     pub event_idx: i64,
+    #[serde(skip)]
+    pub(crate) parsed_params: ParsedParams,
+}
+
+impl RewardSlash {
+    /// Sets the block number and the index of the event from `event_index`,
+    /// unless already present.
+    fn fill_from_event_index(&mut self) {
+        let Some((block, idx)) = self.event_index.split_once('-') else {
+            return;
+        };
+        if self.block_num == BlockNumber::default() {
+            self.block_num = block.parse::<u64>().unwrap_or_default().into();
+        }
+        if self.event_idx == 0 {
+            self.event_idx = idx.parse().unwrap_or_default();
+        }
+    }
+    /// The parameters of the event, decoded from the JSON of `params`. The
+    /// result is cached, so `params` is only parsed once. Returns `None` if
+    /// `params` is malformed.
+    pub fn event_params(&self) -> Option<&[EventParam]> {
+        self.parsed_params
+            .0
+            .get_or_init(|| serde_json::from_str(&self.params).ok())
+            .as_deref()
+    }
+    /// The value of the balance parameter in Planck, i.e. the amount of the
+    /// reward/slash.
+    pub fn balance_param(&self) -> Option<Balance> {
+        let param = self
+            .event_params()?
+            .iter()
+            .find(|param| param.is_balance())?;
+
+        match &param.value {
+            serde_json::Value::String(value) => Some(Balance::from(value.as_str())),
+            serde_json::Value::Number(value) => Some(Balance::from(value.to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// A parameter of an event, e.g. `{"type": "Balance", "value": "1000"}`.
+/// Newer Subscan versions put the primitive type into `type` and the name of
+/// the type into `type_name`, e.g. `{"type": "U128", "type_name": "BalanceOf",
+/// "value": "1000"}`. Values are either strings or numbers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventParam {
+    #[serde(rename = "type")]
+    pub param_type: String,
+    #[serde(default)]
+    pub type_name: Option<String>,
+    pub value: serde_json::Value,
+}
+
+impl EventParam {
+    fn is_balance(&self) -> bool {
+        self.type_name
+            .as_deref()
+            .unwrap_or(&self.param_type)
+            .starts_with("Balance")
+    }
+}
+
+/// The lazily parsed `RewardSlash::params`. Ignored for comparisons, since it
+/// is derived from `params`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ParsedParams(OnceLock<Option<Vec<EventParam>>>);
+
+impl PartialEq for ParsedParams {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default)]
+pub struct StakingActionsPage {
+    pub count: i64,
+    pub list: Option<Vec<StakingAction>>,
+}
+
+/// A staking related extrinsic of an account, such as `bond`, `bond_extra`,
+/// `unbond` or `withdraw_unbonded`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct StakingAction {
+    pub amount: Balance,
+    pub block_num: BlockNumber,
+    pub block_timestamp: Timestamp,
+    pub extrinsic_index: ExtrinsicIndex,
+    pub extrinsic_hash: ExtrinsicHash,
+    pub call_module: String,
+    pub call_module_function: String,
+    pub success: bool,
 }
 
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use super::*;
+    use crate::SqliteStore;
+    use std::sync::Arc;
+    use tokio::time::Instant;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
     impl From<String> for ExtrinsicIndex {
         fn from(val: String) -> Self {
@@ -268,4 +955,646 @@ mod tests {
             ExtrinsicHash(val)
         }
     }
+
+    /// Creates a transfers page as returned by Subscan, with unique extrinsic
+    /// indexes starting at `offset`.
+    pub fn transfers_page(offset: usize, len: usize) -> Response<TransfersPage> {
+        Response {
+            code: Some(0),
+            data: TransfersPage {
+                count: len as i64,
+                transfers: Some(
+                    (offset..offset + len)
+                        .map(|i| Transfer {
+                            amount: "10000000000".into(),
+                            extrinsic_index: format!("{}-1", i).into(),
+                            ..Default::default()
+                        })
+                        .collect(),
+                ),
+            },
+            message: "Success".to_string(),
+            ttl: Some(1),
+        }
+    }
+
+    /// Mounts a mock response for the given page of the transfers endpoint.
+    pub async fn mock_transfers_page(
+        server: &MockServer,
+        page: usize,
+        resp: &Response<TransfersPage>,
+        expected_calls: u64,
+    ) {
+        Mock::given(method("POST"))
+            .and(path("/api/v2/scan/transfers"))
+            .and(body_partial_json(serde_json::json!({ "page": page })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(resp))
+            .expect(expected_calls)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn request_transfer_from_mock_server() {
+        let server = MockServer::start().await;
+        let resp = transfers_page(0, 3);
+        mock_transfers_page(&server, 1, &resp, 1).await;
+
+        let api = ChainApi::with_base_url(&server.uri());
+        let fetched = api
+            .request_transfer(&Context::alice(), 10, 1)
+            .await
+            .unwrap();
+
+        assert_eq!(fetched, resp);
+    }
+
+    #[tokio::test]
+    async fn store_raw_responses() {
+        let server = MockServer::start().await;
+        for page in 1..=3 {
+            mock_transfers_page(&server, page, &transfers_page(page * 10, 1), 1).await;
+        }
+
+        let db = Database::with_store(SqliteStore::new("sqlite::memory:").await.unwrap());
+        let api = ChainApi::with_base_url(&server.uri())
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO))
+            .with_raw_responses(db.clone(), 2);
+
+        let alice = Context::alice();
+        for page in 1..=3 {
+            api.request_transfer(&alice, 10, page).await.unwrap();
+        }
+
+        // Only the two most recent responses are kept.
+        let url = format!("{}/api/v2/scan/transfers", server.uri());
+        let reader = db.reader();
+        let responses = reader.fetch_raw_responses(&url, None, 10).await.unwrap();
+        assert_eq!(responses.len(), 2);
+
+        let body: Response<TransfersPage> =
+            serde_json::from_slice(&responses[0].body().unwrap()).unwrap();
+        assert_eq!(body, transfers_page(30, 1));
+        assert!(responses[0].compressed.len() < responses[0].body().unwrap().len());
+
+        let page2 = reader
+            .fetch_raw_responses(&url, Some(&responses[1].request), 10)
+            .await
+            .unwrap();
+        assert_eq!(page2, vec![responses[1].clone()]);
+        assert!(page2[0].request.contains(r#""page":2"#));
+    }
+
+    #[tokio::test]
+    async fn user_agent_header() {
+        let server = MockServer::start().await;
+        let alice = Context::alice();
+
+        Mock::given(method("POST"))
+            .and(header(
+                "user-agent",
+                format!("polkadot-account-monitoring/{}", env!("CARGO_PKG_VERSION")).as_str(),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(transfers_page(0, 1)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(header("user-agent", "custom-monitor/1.0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(transfers_page(0, 1)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = ChainApi::with_base_url(&server.uri())
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO));
+        api.request_transfer(&alice, 10, 1).await.unwrap();
+
+        let api = api.with_user_agent("custom-monitor/1.0");
+        api.request_transfer(&alice, 10, 1).await.unwrap();
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn space_requests_apart() {
+        let server = MockServer::start().await;
+        let alice = Context::alice();
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(transfers_page(0, 1)))
+            .expect(4)
+            .mount(&server)
+            .await;
+
+        let timeout = Duration::from_millis(200);
+        let api = Arc::new(
+            ChainApi::with_base_url(&server.uri()).with_rate_limiter(RateLimiter::every(timeout)),
+        );
+
+        // The first request is sent right away.
+        let start = Instant::now();
+        api.request_transfer(&alice, 10, 1).await.unwrap();
+        assert!(start.elapsed() < timeout);
+
+        api.request_transfer(&alice, 10, 1).await.unwrap();
+        assert!(start.elapsed() >= timeout);
+
+        // Concurrent requests, e.g. of different fetchers, wait for each other.
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let api = Arc::clone(&api);
+                let alice = alice.clone();
+                tokio::spawn(async move { api.request_transfer(&alice, 10, 1).await })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+        assert!(start.elapsed() >= timeout * 3);
+
+        server.verify().await;
+    }
+
+    #[test]
+    fn select_keys_round_robin() {
+        let mut pool = KeyPoolState::new(3);
+        let now = Instant::now();
+
+        let selected: Vec<_> = (0..4).map(|_| pool.select(now).0).collect();
+        assert_eq!(selected, vec![0, 1, 2, 0]);
+
+        // Benched keys are skipped until they are available again.
+        pool.bench(2, now + Duration::from_secs(10));
+        let selected: Vec<_> = (0..3).map(|_| pool.select(now).0).collect();
+        assert_eq!(selected, vec![1, 0, 1]);
+        let later = now + Duration::from_secs(10);
+        assert_eq!(pool.select(later), (2, None));
+
+        // If all are benched, the one available first is waited for.
+        pool.bench(0, now + Duration::from_secs(5));
+        pool.bench(1, now + Duration::from_secs(3));
+        pool.bench(2, now + Duration::from_secs(7));
+        assert_eq!(pool.select(now), (1, Some(now + Duration::from_secs(3))));
+    }
+
+    #[tokio::test]
+    async fn rotate_api_keys() {
+        let server = MockServer::start().await;
+        let alice = Context::alice();
+
+        for (key, expected) in &[("key-a", 3), ("key-c", 2)] {
+            Mock::given(method("POST"))
+                .and(header("x-api-key", *key))
+                .respond_with(ResponseTemplate::new(200).set_body_json(transfers_page(0, 1)))
+                .expect(*expected)
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(header("x-api-key", "key-b"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(header("x-api-key", "key-b"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(transfers_page(0, 1)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let bench = Duration::from_secs(60);
+        let api = ChainApi::with_base_url(&server.uri())
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO))
+            .with_api_keys(vec![
+                "key-a".to_string(),
+                "key-b".to_string(),
+                "key-c".to_string(),
+            ])
+            .with_key_bench_duration(bench);
+
+        // The rate limited key-b is benched and the request retried with key-c:
+        // a, b (429) -> c, a.
+        for _ in 0..3 {
+            api.request_transfer(&alice, 10, 1).await.unwrap();
+        }
+
+        // Once available again, key-b takes its turn: b, c, a. The clock is
+        // only paused in between, so it does not affect the requests.
+        tokio::time::pause();
+        tokio::time::advance(bench).await;
+        tokio::time::resume();
+        for _ in 0..3 {
+            api.request_transfer(&alice, 10, 1).await.unwrap();
+        }
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn all_api_keys_rate_limited() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "60"))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let api = ChainApi::with_base_url(&server.uri())
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO))
+            .with_api_keys(vec!["key-a".to_string(), "key-b".to_string()]);
+
+        let err = api
+            .request_transfer(&Context::alice(), 10, 1)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("rate limited"));
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn time_out_slow_requests() {
+        let server = MockServer::start().await;
+        let alice = Context::alice();
+
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(transfers_page(0, 1))
+                    .set_delay(Duration::from_secs(5)),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(transfers_page(0, 1)))
+            .mount(&server)
+            .await;
+
+        let timeout = Duration::from_millis(200);
+        let api = ChainApi::with_base_url(&server.uri())
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO))
+            .with_http_timeout(timeout);
+
+        let start = Instant::now();
+        let err = api.request_transfer(&alice, 10, 1).await.unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(err.to_string().contains("timed out after 200ms"), "{}", err);
+
+        // Subsequent requests are not affected.
+        api.request_transfer(&alice, 10, 1).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_subscan_connection() {
+        let server = MockServer::start().await;
+        let alice = Context::alice();
+
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({ "row": 1, "page": 0 }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(transfers_page(0, 1)))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "code": 10006,
+                "message": "Invalid API Key",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = ChainApi::with_base_url(&server.uri())
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO));
+        api.check_connection(&alice).await.unwrap();
+
+        let err = api.check_connection(&alice).await.unwrap_err();
+        assert!(err.to_string().contains("Invalid API Key"));
+
+        // Unreachable endpoints fail too.
+        let api = ChainApi::with_base_url("http://127.0.0.1:1")
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO));
+        let err = api.check_connection(&alice).await.unwrap_err();
+        assert!(err.to_string().contains("unreachable"));
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn request_api_versions() {
+        let server = MockServer::start().await;
+        let alice = Context::alice();
+
+        Mock::given(method("POST"))
+            .and(path("/api/scan/account/reward_slash"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "code": 0,
+                "message": "Success",
+                "data": {
+                    "count": 1,
+                    "list": [{
+                        "amount": "1000",
+                        "event_index": "100-3",
+                        "block_num": 100,
+                        "extrinsic_idx": 1,
+                        "module_id": "staking",
+                        "event_id": "Rewarded",
+                        "params": "[]",
+                        "extrinsic_hash": "0x00",
+                        "event_idx": 3
+                    }]
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Sample of the v2 response shape, which lacks the block number and
+        // the params of the event.
+        Mock::given(method("POST"))
+            .and(path("/api/v2/scan/account/reward_slash"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "code": 0,
+                "message": "Success",
+                "data": {
+                    "count": 1,
+                    "list": [{
+                        "era": 1200,
+                        "stash": "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5",
+                        "account": "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5",
+                        "validator_stash": "",
+                        "amount": "1000",
+                        "block_timestamp": 1700000000,
+                        "event_index": "100-3",
+                        "module_id": "staking",
+                        "event_id": "Rewarded",
+                        "extrinsic_index": "100-1"
+                    }]
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = ChainApi::with_base_url(&server.uri())
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO))
+            .with_api_version(ApiVersion::V1);
+        let v1 = api.request_reward_slash(&alice, 10, 0).await.unwrap();
+
+        let api = api.with_api_version(ApiVersion::V2);
+        let v2 = api.request_reward_slash(&alice, 10, 0).await.unwrap();
+
+        let v1 = &v1.data.list.unwrap()[0];
+        let v2 = &v2.data.list.unwrap()[0];
+        assert_eq!(v2.amount, v1.amount);
+        assert_eq!(v2.block_num, v1.block_num);
+        assert_eq!(v2.event_idx, v1.event_idx);
+        assert!(v2.params.is_empty());
+
+        server.verify().await;
+    }
+
+    #[test]
+    fn nominations_response_shape() {
+        let resp: Response<NominationsPage> = serde_json::from_value(serde_json::json!({
+            "code": 0,
+            "message": "Success",
+            "ttl": 1,
+            "data": {
+                "list": [serde_json::to_value(Nomination::default()).unwrap()]
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(resp.data.list.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn parse_transfer_direction() {
+        let parse = |direction: serde_json::Value| {
+            let mut json = serde_json::to_value(Transfer::default()).unwrap();
+            if !direction.is_null() {
+                json["transfer_type"] = direction;
+            }
+            serde_json::from_value::<Transfer>(json).unwrap().direction
+        };
+
+        assert_eq!(
+            parse(serde_json::json!("in")),
+            Some(TransferDirection::Incoming)
+        );
+        assert_eq!(
+            parse(serde_json::json!("OUT")),
+            Some(TransferDirection::Outgoing)
+        );
+        assert_eq!(parse(serde_json::json!("unknown")), None);
+        assert_eq!(parse(serde_json::Value::Null), None);
+
+        // Stored as `direction`, which is omitted if unknown, so the checksums
+        // of transfers stored before are unchanged.
+        let mut transfer = Transfer::default();
+        assert!(serde_json::to_value(&transfer)
+            .unwrap()
+            .get("direction")
+            .is_none());
+        transfer.direction = Some(TransferDirection::Outgoing);
+        let json = serde_json::to_value(&transfer).unwrap();
+        assert_eq!(json["direction"], "outgoing");
+        assert_eq!(serde_json::from_value::<Transfer>(json).unwrap(), transfer);
+
+        // Falls back to the sender if unknown.
+        transfer.from = "proxy".to_string();
+        assert!(transfer.is_outgoing("stash"));
+        transfer.direction = None;
+        assert!(!transfer.is_outgoing("stash"));
+        assert!(transfer.is_outgoing("proxy"));
+    }
+
+    #[test]
+    fn parse_balances() {
+        let parse = |json: &str| serde_json::from_str::<Balance>(json).unwrap();
+
+        assert_eq!(parse(r#""10000000000""#).planck(), Some(10_000_000_000));
+        assert_eq!(parse("1500000000").planck(), Some(1_500_000_000));
+        assert_eq!(parse(r#""0""#).planck(), Some(0));
+
+        // Beyond the range of `u64`, e.g. the total issuance of a network.
+        let large = parse(r#""340282366920938463463374607431768211455""#);
+        assert_eq!(large.planck(), Some(u128::MAX));
+        // Beyond the range of `u128`.
+        let overflow = parse(r#""340282366920938463463374607431768211456""#);
+        assert_eq!(overflow.planck(), None);
+        assert_eq!(overflow.as_str(), "340282366920938463463374607431768211456");
+
+        // Missing values.
+        for json in [r#""""#, "null"] {
+            let missing = parse(json);
+            assert!(missing.is_empty());
+            assert_eq!(missing.planck(), None);
+        }
+
+        // Malformed values are kept as received.
+        for raw in ["invalid", "1.5", "-1", " 1"] {
+            let balance = Balance::from(raw);
+            assert_eq!(balance.planck(), None);
+            assert_eq!(
+                serde_json::to_value(&balance).unwrap(),
+                serde_json::json!(raw)
+            );
+        }
+
+        // Serialized as received, in both JSON and BSON.
+        for raw in ["", "10000000000", "340282366920938463463374607431768211455"] {
+            let balance = Balance::from(raw);
+            assert_eq!(
+                serde_json::to_string(&balance).unwrap(),
+                format!("{:?}", raw)
+            );
+            assert_eq!(
+                bson::from_bson::<Balance>(bson::to_bson(&balance).unwrap()).unwrap(),
+                balance
+            );
+        }
+    }
+
+    #[test]
+    fn parse_reward_slash_params() {
+        let reward = |params: &str| RewardSlash {
+            params: params.to_string(),
+            ..Default::default()
+        };
+
+        // Samples of `Staking.Rewarded` and `Staking.Slashed` events as returned
+        // by Subscan.
+        let legacy = reward(
+            r#"[{"type":"AccountId","value":"0x2ed6e4f7a0cee4a9b5d44ba2ccc0a3be09813ab0a5e12e79bfd546f63b0aa839"},{"type":"Balance","value":"31629834590"}]"#,
+        );
+        let current = reward(
+            r#"[{"type":"[U8; 32]","type_name":"AccountId","value":"0x2ed6e4f7a0cee4a9b5d44ba2ccc0a3be09813ab0a5e12e79bfd546f63b0aa839"},{"type":"U128","type_name":"BalanceOf","value":"31629834590"}]"#,
+        );
+        let numeric = reward(
+            r#"[{"type":"AccountId","value":"0x2ed6e4f7a0cee4a9b5d44ba2ccc0a3be09813ab0a5e12e79bfd546f63b0aa839"},{"type":"Balance","value":1500000000}]"#,
+        );
+
+        assert_eq!(legacy.event_params().unwrap().len(), 2);
+        assert_eq!(legacy.balance_param().unwrap().as_str(), "31629834590");
+        assert_eq!(
+            current.event_params().unwrap()[1].type_name.as_deref(),
+            Some("BalanceOf")
+        );
+        assert_eq!(current.balance_param().unwrap().as_str(), "31629834590");
+        assert_eq!(numeric.balance_param().unwrap().as_str(), "1500000000");
+
+        // Malformed params.
+        assert!(reward("").event_params().is_none());
+        assert!(reward("{\"type\":\"Balance\"}").event_params().is_none());
+        assert!(reward(r#"[{"type":"AccountId","value":"0x00"}]"#)
+            .balance_param()
+            .is_none());
+        assert!(reward(r#"[{"type":"Balance","value":[1,2]}]"#)
+            .balance_param()
+            .is_none());
+
+        // The cache does not influence (de)serialization or comparisons.
+        let parsed = legacy.clone();
+        assert!(parsed.event_params().is_some());
+        assert_eq!(parsed, reward(&legacy.params));
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap(),
+            serde_json::to_value(reward(&legacy.params)).unwrap()
+        );
+    }
+
+    #[test]
+    fn normalize_transfer_addresses() {
+        let alice = Context::alice();
+        let bob = Context::bob();
+        let generic =
+            |address: &str| crate::rpc::encode_ss58(42, &crate::rpc::decode_ss58(address).unwrap());
+
+        let mut page = TransfersPage {
+            count: 1,
+            transfers: Some(vec![Transfer {
+                from: generic(&alice.stash),
+                to: bob.stash.clone(),
+                to_account_display: ToAccountDisplay {
+                    address: bob.stash.clone(),
+                    parent: Some(Parent {
+                        address: generic(&alice.stash),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }]),
+        };
+        assert_ne!(page.transfers.as_ref().unwrap()[0].from, alice.stash);
+
+        page.normalize_addresses(Network::Polkadot);
+        let transfer = &page.transfers.as_ref().unwrap()[0];
+        assert_eq!(transfer.from, alice.stash);
+        assert_eq!(transfer.to, bob.stash);
+        assert_eq!(
+            transfer.to_account_display.parent.as_ref().unwrap().address,
+            alice.stash
+        );
+        // Addresses which can not be decoded are kept.
+        assert_eq!(transfer.from_account_display.address, "");
+    }
+
+    #[test]
+    fn empty_response_data() {
+        fn parse<T: DeserializeOwned + Default>(data: serde_json::Value) -> Response<T> {
+            serde_json::from_value(serde_json::json!({
+                "code": 0,
+                "message": "Success",
+                "ttl": 1,
+                "data": data,
+            }))
+            .unwrap()
+        }
+
+        for data in [serde_json::Value::Null, serde_json::json!({})] {
+            let resp: Response<TransfersPage> = parse(data.clone());
+            assert_eq!(resp.data, TransfersPage::default());
+
+            let resp: Response<RewardsSlashesPage> = parse(data.clone());
+            assert!(resp.data.list.is_none());
+
+            let resp: Response<NominationsPage> = parse(data.clone());
+            assert!(resp.data.list.is_none());
+
+            let resp: Response<StakingActionsPage> = parse(data);
+            assert!(resp.data.list.is_none());
+        }
+
+        // The data field may be omitted entirely.
+        let resp: Response<TransfersPage> =
+            serde_json::from_str(r#"{"code": 0, "message": "Success"}"#).unwrap();
+        assert!(resp.data.transfers.is_none());
+    }
+
+    #[test]
+    fn base_url_network_placeholder() {
+        let api = ChainApi::new();
+        assert_eq!(
+            api.url(&Context::alice(), "/api/scan/transfers"),
+            "https://polkadot.api.subscan.io/api/scan/transfers"
+        );
+
+        let api = ChainApi::with_base_url("http://localhost:8080/");
+        assert_eq!(
+            api.url(&Context::alice(), "/api/scan/transfers"),
+            "http://localhost:8080/api/scan/transfers"
+        );
+    }
 }
@@ -1,25 +1,61 @@
-use crate::chain_api::{ChainApi, NominationsPage, Response, RewardsSlashesPage, TransfersPage};
-use crate::database::{Database, DatabaseReader};
-use crate::publishing::{GoogleDrive, Publisher};
+use crate::chain_api::{
+    ChainApi, ChainBackend, NominationsPage, Parent, Response, RewardsSlashesPage,
+    StakingActionsPage, Transfer, TransfersPage,
+};
+use crate::database::{Database, DatabaseReader, StoredCount, TransferSort};
+use crate::publishing::{GoogleStoragePayload, Notification, Publisher};
 use crate::reporting::{
-    GenerateReport, NominationReportGenerator, RewardSlashReportGenerator, TransferReportGenerator,
+    transfer_event, CombinedReportGenerator, GenerateReport, NominationDeltaReportGenerator,
+    NominationReportGenerator, ReportBuckets, ReportFormat, ReportRange,
+    RewardSlashReportGenerator, TransferColumn, TransferReportGenerator, DEFAULT_CSV_DELIMITER,
 };
-use crate::{Context, Result, Timestamp};
-
-use std::collections::HashSet;
-
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use tokio::time::{sleep, Duration};
+use crate::stats::{Stats, StatsSnapshot};
+use crate::{BlockNumber, Context, ExtrinsicType, Labels, Network, Result, Timestamp};
+use bson::oid::ObjectId;
+use chrono::NaiveDate;
+use rand::{thread_rng, Rng};
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{RwLock, Semaphore};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration, Instant};
+use tracing::Instrument;
 
 const ROW_AMOUNT: usize = 10;
+// The maximum page size supported by Subscan.
+const BACKFILL_ROW_AMOUNT: usize = 100;
 const FAILED_TASK_SLEEP: u64 = 30;
 const LOOP_INTERVAL: u64 = 300;
 const MAX_ERR_DIFF: u64 = 60;
+/// The default fraction by which the pause between iterations is randomly
+/// lengthened or shortened, see `jittered`.
+pub const DEFAULT_LOOP_JITTER: f64 = 0.1;
+// Outlasts the pause between iterations, so the lease is kept while idle.
+const FETCHER_LEASE_TTL: u64 = LOOP_INTERVAL * 2;
+/// The maximum amount of unsent alerts kept for retrying, see `TransferAlert`.
+const MAX_PENDING_ALERTS: usize = 100;
+// How often a paused fetcher checks whether it was resumed.
+const PAUSE_CHECK_INTERVAL: u64 = 5;
+
+/// Randomly lengthens or shortens the interval by up to the given fraction,
+/// so the loops which were started at the same time do not keep sending
+/// their requests at the same time.
+fn jittered(interval: Duration, jitter: f64) -> Duration {
+    if jitter <= 0.0 {
+        return interval;
+    }
+
+    interval.mul_f64(1.0 + thread_rng().gen_range(-jitter..=jitter))
+}
 
 pub struct TransferFetcher {
     db: Database,
-    api: Arc<ChainApi>,
+    api: Arc<dyn ChainBackend>,
+    alert: Option<Arc<TransferAlert>>,
 }
 
 #[async_trait]
@@ -29,20 +65,178 @@ impl FetchChainData for TransferFetcher {
     fn name() -> &'static str {
         "TransferFetcher"
     }
-    fn new(db: Database, api: Arc<ChainApi>) -> Self {
-        TransferFetcher { db: db, api: api }
+    fn new(db: Database, api: Arc<dyn ChainBackend>) -> Self {
+        TransferFetcher {
+            db,
+            api,
+            alert: None,
+        }
+    }
+    fn set_transfer_alert(&mut self, alert: Arc<TransferAlert>) {
+        self.alert = Some(alert);
     }
     async fn fetch_data(&self, context: &Context, row: usize, page: usize) -> Result<Self::Data> {
-        self.api.request_transfer(context, row, page).await
+        let mut resp = self.api.request_transfer(context, row, page).await?;
+        resp.data.normalize_addresses(context.network);
+
+        Ok(resp)
+    }
+    async fn store_data(&self, context: &Context, data: &Self::Data) -> Result<StoredCount> {
+        let (inserted, skipped) = self.db.store_new_transfers(context, data).await?;
+
+        if let Some(alert) = &self.alert {
+            // The transfers are already stored, so the alert is queued and
+            // retried with the next stored page rather than failing it.
+            if let Err(err) = alert.check(context, &inserted).await {
+                error!(error = ?err, "Failed to send large transfer alert, retrying later");
+            }
+        }
+
+        Ok(StoredCount {
+            inserted: inserted.len(),
+            skipped,
+        })
+    }
+    async fn mark_orphaned(
+        &self,
+        context: &Context,
+        from: BlockNumber,
+        data: &[Self::Data],
+    ) -> Result<Option<u64>> {
+        self.db
+            .mark_orphaned_transfers(context, from, data)
+            .await
+            .map(Some)
+    }
+    async fn latest_block(&self, context: &Context) -> Result<Option<BlockNumber>> {
+        self.db
+            .latest_block(context, &ScrapingModule::Transfer)
+            .await
+    }
+}
+
+/// Sends a notification about a transfer.
+#[async_trait]
+pub trait Alert: Send + Sync {
+    async fn alert(&self, notification: Notification) -> Result<()>;
+}
+
+/// Sends alerts through a notification publisher, such as Matrix or Discord.
+pub struct PublisherAlert<P: Publisher> {
+    publisher: Arc<P>,
+    info: <P as Publisher>::Info,
+}
+
+impl<P: Publisher> PublisherAlert<P> {
+    pub fn new(publisher: Arc<P>, info: <P as Publisher>::Info) -> Self {
+        PublisherAlert { publisher, info }
+    }
+}
+
+#[async_trait]
+impl<P> Alert for PublisherAlert<P>
+where
+    P: 'static + Send + Sync + Publisher<Data = Notification>,
+    <P as Publisher>::Info: Send + Sync + Clone,
+{
+    async fn alert(&self, notification: Notification) -> Result<()> {
+        self.publisher
+            .upload_data(self.info.clone(), notification)
+            .await
+    }
+}
+
+/// Immediately alerts about single transfers of at least the threshold of
+/// their network as soon as they are stored, rather than with the next
+/// report. Networks without a threshold are not checked. Alerts which fail to
+/// send are queued and retried with the next check, since the transfers are
+/// already stored and would not be alerted about again otherwise.
+pub struct TransferAlert {
+    thresholds: HashMap<Network, f64>,
+    alert: Arc<dyn Alert>,
+    pending: Mutex<Vec<Notification>>,
+}
+
+impl TransferAlert {
+    /// The thresholds are in whole tokens.
+    pub fn new(thresholds: HashMap<Network, f64>, alert: Arc<dyn Alert>) -> Self {
+        TransferAlert {
+            thresholds,
+            alert,
+            pending: Mutex::new(vec![]),
+        }
+    }
+    /// Alerts about the transfers above the threshold, if any, after retrying
+    /// the queued alerts. Returns the amount of transfers alerted about. On
+    /// failure, the unsent alerts are queued again.
+    async fn check(&self, context: &Context, transfers: &[&Transfer]) -> Result<usize> {
+        let mut notifications = std::mem::take(&mut *self.pending.lock().unwrap());
+        if let Some(notification) = self.notification(context, transfers)? {
+            notifications.push(notification);
+        }
+
+        let mut count = 0;
+        let mut notifications = notifications.into_iter();
+        while let Some(notification) = notifications.next() {
+            let events = notification.events.len();
+            info!(count = events, "Alerting about large transfers");
+
+            if let Err(err) = self.alert.alert(notification.clone()).await {
+                self.queue(std::iter::once(notification).chain(notifications));
+                return Err(err);
+            }
+
+            count += events;
+        }
+
+        Ok(count)
+    }
+    /// The alert about the transfers above the threshold, if any.
+    fn notification(
+        &self,
+        context: &Context,
+        transfers: &[&Transfer],
+    ) -> Result<Option<Notification>> {
+        let threshold = match self.thresholds.get(&context.network) {
+            Some(threshold) => *threshold,
+            None => return Ok(None),
+        };
+
+        let mut events = vec![];
+        for transfer in transfers {
+            let event = transfer_event(context, transfer)?;
+            if event.amount.at_least(threshold) {
+                events.push(event);
+            }
+        }
+
+        if events.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Notification {
+            title: format!("Large transfers on {}", context.network.as_str()),
+            events,
+        }))
     }
-    async fn store_data(&self, context: &Context, data: &Self::Data) -> Result<usize> {
-        self.db.store_transfer_event(context, data).await
+    /// Queues the alerts for the next check, ahead of the alerts queued in the
+    /// meantime. The oldest alerts are dropped beyond `MAX_PENDING_ALERTS`.
+    fn queue(&self, notifications: impl Iterator<Item = Notification>) {
+        let mut pending = self.pending.lock().unwrap();
+        let queued = std::mem::take(&mut *pending);
+        pending.extend(notifications.chain(queued));
+
+        if pending.len() > MAX_PENDING_ALERTS {
+            let dropped = pending.len() - MAX_PENDING_ALERTS;
+            warn!(dropped, "Too many unsent alerts, dropping the oldest");
+            pending.drain(..dropped);
+        }
     }
 }
 
 pub struct RewardsSlashesFetcher {
     db: Database,
-    api: Arc<ChainApi>,
+    api: Arc<dyn ChainBackend>,
 }
 
 #[async_trait]
@@ -52,52 +246,135 @@ impl FetchChainData for RewardsSlashesFetcher {
     fn name() -> &'static str {
         "RewardsSlashesFetcher"
     }
-    fn new(db: Database, api: Arc<ChainApi>) -> Self {
-        RewardsSlashesFetcher { db: db, api: api }
+    fn new(db: Database, api: Arc<dyn ChainBackend>) -> Self {
+        RewardsSlashesFetcher { db, api }
     }
     async fn fetch_data(&self, context: &Context, row: usize, page: usize) -> Result<Self::Data> {
         self.api.request_reward_slash(context, row, page).await
     }
-    async fn store_data(&self, context: &Context, data: &Self::Data) -> Result<usize> {
+    async fn store_data(&self, context: &Context, data: &Self::Data) -> Result<StoredCount> {
         self.db.store_reward_slash_event(context, data).await
     }
+    async fn mark_orphaned(
+        &self,
+        context: &Context,
+        from: BlockNumber,
+        data: &[Self::Data],
+    ) -> Result<Option<u64>> {
+        self.db
+            .mark_orphaned_rewards_slashes(context, from, data)
+            .await
+            .map(Some)
+    }
+    async fn latest_block(&self, context: &Context) -> Result<Option<BlockNumber>> {
+        self.db
+            .latest_block(context, &ScrapingModule::RewardsSlashes)
+            .await
+    }
 }
 
 pub struct NominationsFetcher {
     db: Database,
-    api: Arc<ChainApi>,
+    api: Arc<dyn ChainBackend>,
 }
 
 #[async_trait]
 impl FetchChainData for NominationsFetcher {
     type Data = Response<NominationsPage>;
+    // All nominations are returned at once.
+    const PAGINATED: bool = false;
 
     fn name() -> &'static str {
         "NominationsFetcher"
     }
-    fn new(db: Database, api: Arc<ChainApi>) -> Self {
-        NominationsFetcher { db: db, api: api }
+    fn new(db: Database, api: Arc<dyn ChainBackend>) -> Self {
+        NominationsFetcher { db, api }
     }
     async fn fetch_data(&self, context: &Context, _row: usize, _page: usize) -> Result<Self::Data> {
         self.api.request_nominations(context).await
     }
-    async fn store_data(&self, context: &Context, data: &Self::Data) -> Result<usize> {
+    async fn store_data(&self, context: &Context, data: &Self::Data) -> Result<StoredCount> {
         self.db.store_nomination_event(context, data).await
     }
 }
 
+pub struct StakingActionsFetcher {
+    db: Database,
+    api: Arc<dyn ChainBackend>,
+}
+
+#[async_trait]
+impl FetchChainData for StakingActionsFetcher {
+    type Data = Response<StakingActionsPage>;
+
+    fn name() -> &'static str {
+        "StakingActionsFetcher"
+    }
+    fn new(db: Database, api: Arc<dyn ChainBackend>) -> Self {
+        StakingActionsFetcher { db, api }
+    }
+    async fn fetch_data(&self, context: &Context, row: usize, page: usize) -> Result<Self::Data> {
+        self.api.request_staking_actions(context, row, page).await
+    }
+    async fn store_data(&self, context: &Context, data: &Self::Data) -> Result<StoredCount> {
+        self.db.store_staking_action(context, data).await
+    }
+    async fn latest_block(&self, context: &Context) -> Result<Option<BlockNumber>> {
+        self.db
+            .latest_block(context, &ScrapingModule::StakingActions)
+            .await
+    }
+}
+
 #[async_trait]
 pub trait FetchChainData {
     type Data: Send + Sync + std::fmt::Debug + DataInfo;
+    /// Whether the data is fetched page by page, respecting the `row` and
+    /// `page` parameters of `fetch_data`.
+    const PAGINATED: bool = true;
 
     fn name() -> &'static str;
-    fn new(db: Database, api: Arc<ChainApi>) -> Self;
+    fn new(db: Database, api: Arc<dyn ChainBackend>) -> Self;
+    /// Alerts about large transfers as they are stored. Ignored by fetchers
+    /// of other data types.
+    fn set_transfer_alert(&mut self, _alert: Arc<TransferAlert>) {}
     async fn fetch_data(&self, _: &Context, row: usize, page: usize) -> Result<Self::Data>;
-    async fn store_data(&self, _: &Context, data: &Self::Data) -> Result<usize>;
+    /// Stores the entries which are not stored yet.
+    async fn store_data(&self, _: &Context, data: &Self::Data) -> Result<StoredCount>;
+    /// Flags stored entries from the given block onwards which are missing in
+    /// the freshly fetched `data` as orphaned. Returns `None` if the data type
+    /// does not support the reorg check.
+    async fn mark_orphaned(
+        &self,
+        _: &Context,
+        _from: BlockNumber,
+        _data: &[Self::Data],
+    ) -> Result<Option<u64>> {
+        Ok(None)
+    }
+    /// The highest block number of the stored entries of the account, used as
+    /// a watermark to only fetch new entries. Returns `None` if nothing was
+    /// stored yet or if the data type has no block numbers.
+    async fn latest_block(&self, _: &Context) -> Result<Option<BlockNumber>> {
+        Ok(None)
+    }
 }
 
 pub trait DataInfo {
     fn is_empty(&self) -> bool;
+    fn len(&self) -> usize;
+    /// The lowest block number of all entries, if the entries have one.
+    fn oldest_block(&self) -> Option<BlockNumber>;
+    /// The highest block number of all entries, if the entries have one.
+    fn newest_block(&self) -> Option<BlockNumber>;
+    /// Identifies each entry the same way as the database does when
+    /// deduplicating stored entries.
+    fn entry_keys(&self) -> Vec<String>;
+    /// The parent accounts of `stash`, if the entries reveal that it is a
+    /// sub-account.
+    fn parents(&self, _stash: &str) -> Vec<Parent> {
+        vec![]
+    }
 }
 
 #[async_trait]
@@ -105,6 +382,48 @@ impl DataInfo for Response<TransfersPage> {
     fn is_empty(&self) -> bool {
         self.data.transfers.is_none()
     }
+    fn len(&self) -> usize {
+        self.data.transfers.as_ref().map_or(0, Vec::len)
+    }
+    fn oldest_block(&self) -> Option<BlockNumber> {
+        self.data
+            .transfers
+            .as_ref()?
+            .iter()
+            .map(|t| t.block_num)
+            .min()
+    }
+    fn newest_block(&self) -> Option<BlockNumber> {
+        self.data
+            .transfers
+            .as_ref()?
+            .iter()
+            .map(|t| t.block_num)
+            .max()
+    }
+    fn entry_keys(&self) -> Vec<String> {
+        self.data
+            .transfers
+            .iter()
+            .flatten()
+            .map(Transfer::key)
+            .collect()
+    }
+    fn parents(&self, stash: &str) -> Vec<Parent> {
+        self.data
+            .transfers
+            .iter()
+            .flatten()
+            .flat_map(|t| {
+                vec![
+                    (&t.from, &t.from_account_display.parent),
+                    (&t.to, &t.to_account_display.parent),
+                ]
+            })
+            .filter(|(address, _)| address.as_str() == stash)
+            .filter_map(|(_, parent)| parent.clone())
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -112,6 +431,23 @@ impl DataInfo for Response<RewardsSlashesPage> {
     fn is_empty(&self) -> bool {
         self.data.list.is_none()
     }
+    fn len(&self) -> usize {
+        self.data.list.as_ref().map_or(0, Vec::len)
+    }
+    fn oldest_block(&self) -> Option<BlockNumber> {
+        self.data.list.as_ref()?.iter().map(|r| r.block_num).min()
+    }
+    fn newest_block(&self) -> Option<BlockNumber> {
+        self.data.list.as_ref()?.iter().map(|r| r.block_num).max()
+    }
+    fn entry_keys(&self) -> Vec<String> {
+        self.data
+            .list
+            .iter()
+            .flatten()
+            .map(|r| r.event_index.clone())
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -119,6 +455,47 @@ impl DataInfo for Response<NominationsPage> {
     fn is_empty(&self) -> bool {
         self.data.list.is_none()
     }
+    fn len(&self) -> usize {
+        self.data.list.as_ref().map_or(0, Vec::len)
+    }
+    fn oldest_block(&self) -> Option<BlockNumber> {
+        None
+    }
+    fn newest_block(&self) -> Option<BlockNumber> {
+        None
+    }
+    fn entry_keys(&self) -> Vec<String> {
+        self.data
+            .list
+            .iter()
+            .flatten()
+            .map(|n| n.stash_account_display.address.clone())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl DataInfo for Response<StakingActionsPage> {
+    fn is_empty(&self) -> bool {
+        self.data.list.is_none()
+    }
+    fn len(&self) -> usize {
+        self.data.list.as_ref().map_or(0, Vec::len)
+    }
+    fn oldest_block(&self) -> Option<BlockNumber> {
+        self.data.list.as_ref()?.iter().map(|a| a.block_num).min()
+    }
+    fn newest_block(&self) -> Option<BlockNumber> {
+        self.data.list.as_ref()?.iter().map(|a| a.block_num).max()
+    }
+    fn entry_keys(&self) -> Vec<String> {
+        self.data
+            .list
+            .iter()
+            .flatten()
+            .map(|a| a.extrinsic_hash.to_string())
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
@@ -127,274 +504,1394 @@ pub enum ScrapingModule {
     Transfer,
     RewardsSlashes,
     Nominations,
+    StakingActions,
 }
 
-// TODO: lifetime annotation required?
-pub struct ScrapingService<'a> {
+impl FromStr for ScrapingModule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        serde_json::from_value(serde_json::Value::String(s.to_string()))
+            .map_err(|_| anyhow!("unknown scraping module: {}", s))
+    }
+}
+
+/// Pauses and resumes individual fetchers at runtime, e.g. while Subscan is
+/// degraded. All clones control the same fetchers.
+#[derive(Debug, Clone, Default)]
+pub struct FetcherControl {
+    paused: Arc<Mutex<HashMap<ScrapingModule, Arc<AtomicBool>>>>,
+}
+
+impl FetcherControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Pauses the fetcher of the module. It finishes the account it is
+    /// currently processing and sends no further requests until resumed.
+    pub fn pause(&self, module: &ScrapingModule) {
+        self.flag(module).store(true, Ordering::Relaxed);
+    }
+    /// Resumes the fetcher of the module, see `pause`.
+    pub fn resume(&self, module: &ScrapingModule) {
+        self.flag(module).store(false, Ordering::Relaxed);
+    }
+    pub fn is_paused(&self, module: &ScrapingModule) -> bool {
+        self.flag(module).load(Ordering::Relaxed)
+    }
+    /// The flag checked by the fetcher of the module.
+    fn flag(&self, module: &ScrapingModule) -> Arc<AtomicBool> {
+        Arc::clone(
+            self.paused
+                .lock()
+                .unwrap()
+                .entry(module.clone())
+                .or_default(),
+        )
+    }
+}
+
+/// Sleeps for as long as the flag is set, checking it every `interval`.
+async fn wait_while_paused(paused: &AtomicBool, interval: Duration) {
+    if !paused.load(Ordering::Relaxed) {
+        return;
+    }
+
+    info!("Fetcher is paused");
+    while paused.load(Ordering::Relaxed) {
+        sleep(interval).await;
+    }
+    info!("Fetcher resumed");
+}
+
+/// Limits a one-time backfill of an account. Without any limits, all
+/// available pages are fetched.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BackfillLimit {
+    /// The maximum amount of pages to fetch.
+    pub max_pages: Option<usize>,
+    /// Stops after the page which reaches back to this block.
+    pub oldest_block: Option<BlockNumber>,
+}
+
+pub struct ScrapingService {
     db: Database,
-    api: Arc<ChainApi>,
+    api: Arc<dyn ChainBackend>,
     contexts: Arc<RwLock<Vec<Context>>>,
-    running: HashSet<&'a ScrapingModule>,
+    running: HashSet<ScrapingModule>,
+    reorg_check_depth: Option<u64>,
+    max_pages: Option<usize>,
+    track_parents: bool,
+    transfer_alert: Option<Arc<TransferAlert>>,
+    stats: Arc<Stats>,
+    loop_jitter: f64,
+    control: FetcherControl,
+    /// Identifies this instance as the holder of fetcher leases.
+    instance_id: String,
+    once: bool,
+    /// The fetchers running a single cycle, see `set_once`.
+    tasks: Vec<JoinHandle<Result<()>>>,
 }
 
-impl<'a> ScrapingService<'a> {
+impl ScrapingService {
     pub fn new(db: Database) -> Self {
+        Self::with_backend(db, Arc::new(ChainApi::new()))
+    }
+    pub fn with_backend(db: Database, api: Arc<dyn ChainBackend>) -> Self {
         ScrapingService {
-            db: db,
-            api: Arc::new(ChainApi::new()),
+            db,
+            api,
             contexts: Arc::new(RwLock::new(vec![])),
             running: HashSet::new(),
+            reorg_check_depth: None,
+            max_pages: None,
+            track_parents: false,
+            transfer_alert: None,
+            stats: Arc::new(Stats::new()),
+            loop_jitter: DEFAULT_LOOP_JITTER,
+            control: FetcherControl::new(),
+            instance_id: ObjectId::new().to_hex(),
+            once: false,
+            tasks: vec![],
         }
     }
+    /// Re-checks the entries of the given amount of most recent blocks after
+    /// each iteration of the fetchers, flagging the ones which disappeared
+    /// (e.g. because of a chain reorg) as orphaned.
+    pub fn set_reorg_check_depth(&mut self, depth: u64) {
+        self.reorg_check_depth = Some(depth);
+    }
+    /// Moves on to the next account after fetching the given amount of pages
+    /// of an account within a single cycle, e.g. in case the new entries are
+    /// not detected as stored. Older entries beyond the cap are not fetched
+    /// in later cycles either, see `backfill`. Unlimited by default.
+    pub fn set_max_pages(&mut self, max_pages: usize) {
+        self.max_pages = Some(max_pages);
+    }
+    /// Also monitors the parent of each account which turns out to be a
+    /// sub-account, adding it as an implicit account. Parents of implicit
+    /// accounts are not followed.
+    pub fn set_track_parents(&mut self, track: bool) {
+        self.track_parents = track;
+    }
+    /// Alerts about large transfers as soon as the transfer fetcher stores
+    /// them. Not applied to backfills, which would alert about old transfers.
+    pub fn set_transfer_alert(&mut self, alert: TransferAlert) {
+        self.transfer_alert = Some(Arc::new(alert));
+    }
+    /// Counts the requests, inserted entries and errors of the fetchers in
+    /// the given (shared) stats, e.g. the ones of a `ReportGenerator`.
+    pub fn set_stats(&mut self, stats: Arc<Stats>) {
+        self.stats = stats;
+    }
+    /// The current stats of the fetchers, see `set_stats`.
+    pub fn stats(&self) -> StatsSnapshot {
+        self.stats.snapshot()
+    }
+    /// Randomly lengthens or shortens the pause between iterations of the
+    /// fetchers by up to the given fraction, `DEFAULT_LOOP_JITTER` by default.
+    pub fn set_loop_jitter(&mut self, jitter: f64) {
+        self.loop_jitter = jitter;
+    }
+    /// Pauses the fetcher of the module, see `FetcherControl::pause`.
+    pub fn pause(&self, module: &ScrapingModule) {
+        self.control.pause(module);
+    }
+    /// Resumes the paused fetcher of the module.
+    pub fn resume(&self, module: &ScrapingModule) {
+        self.control.resume(module);
+    }
+    /// A handle to pause and resume the fetchers from other tasks.
+    pub fn control(&self) -> FetcherControl {
+        self.control.clone()
+    }
+    /// Runs each fetcher through a single cycle over all accounts instead of
+    /// indefinitely. Use `wait` to wait for the fetchers to complete.
+    pub fn set_once(&mut self, once: bool) {
+        self.once = once;
+    }
+    /// Waits for the fetchers started in single cycle mode to complete and
+    /// returns the first error which occurred, if any.
+    pub async fn wait(&mut self) -> Result<()> {
+        wait_for_tasks(std::mem::take(&mut self.tasks)).await
+    }
     pub async fn add_contexts(&mut self, mut contexts: Vec<Context>) {
         self.contexts.write().await.append(&mut contexts);
     }
-    // TODO: Get rid fo this, use `run_fetcher` directly.
-    pub async fn run(&mut self, module: &'a ScrapingModule) -> Result<()> {
-        if self.running.contains(module) {
+    /// Issues one request per network of the monitored accounts, failing if
+    /// the chain backend is unreachable or rejects requests, e.g. because of
+    /// an invalid API key.
+    pub async fn check_backend(&self) -> Result<()> {
+        let contexts = self.contexts.read().await;
+
+        let mut checked = HashSet::new();
+        for context in contexts.iter() {
+            if checked.insert(context.network) {
+                info!(
+                    network = context.network.as_str(),
+                    "Checking connection to the chain backend"
+                );
+                self.api.check_connection(context).await?;
+            }
+        }
+
+        Ok(())
+    }
+    /// The monitored accounts, including implicitly added parent accounts.
+    /// Can be shared with a `ReportGenerator`, so implicit accounts are
+    /// reported on too.
+    pub fn contexts(&self) -> Arc<RwLock<Vec<Context>>> {
+        Arc::clone(&self.contexts)
+    }
+    /// Starts the fetcher of the given module. Each module can only be
+    /// started once.
+    pub async fn run(&mut self, module: &ScrapingModule) -> Result<()> {
+        if !self.running.insert(module.clone()) {
             return Err(anyhow!(
                 "configuration contains the same module multiple times"
             ));
         }
 
-        self.running.insert(module);
-
         match module {
-            ScrapingModule::Transfer => self.run_fetcher::<TransferFetcher>().await,
-            ScrapingModule::RewardsSlashes => self.run_fetcher::<RewardsSlashesFetcher>().await,
-            ScrapingModule::Nominations => self.run_fetcher::<NominationsFetcher>().await,
+            ScrapingModule::Transfer => self.run_fetcher::<TransferFetcher>(module).await,
+            ScrapingModule::RewardsSlashes => {
+                self.run_fetcher::<RewardsSlashesFetcher>(module).await
+            }
+            ScrapingModule::Nominations => self.run_fetcher::<NominationsFetcher>(module).await,
+            ScrapingModule::StakingActions => {
+                self.run_fetcher::<StakingActionsFetcher>(module).await
+            }
+        }
+
+        Ok(())
+    }
+    /// Starts the fetchers of all given modules. Fails without starting any
+    /// fetcher if a module is given multiple times or is already running.
+    pub async fn run_all(&mut self, modules: &[ScrapingModule]) -> Result<()> {
+        let mut unique = HashSet::new();
+        for module in modules {
+            if self.running.contains(module) || !unique.insert(module) {
+                return Err(anyhow!(
+                    "configuration contains the same module multiple times"
+                ));
+            }
+        }
+
+        for module in modules {
+            self.run(module).await?;
         }
 
         Ok(())
     }
-    async fn run_fetcher<T>(&self)
+    /// Fetches the history of a single account once and returns how many
+    /// entries were newly inserted. Unlike `run`, this does not stop at
+    /// already known entries and does not pause between iterations, but
+    /// requests are still subject to the rate limits of the backend.
+    pub async fn backfill(
+        &self,
+        context: &Context,
+        module: &ScrapingModule,
+        limit: BackfillLimit,
+    ) -> Result<usize> {
+        match module {
+            ScrapingModule::Transfer => self.backfill_with::<TransferFetcher>(context, limit).await,
+            ScrapingModule::RewardsSlashes => {
+                self.backfill_with::<RewardsSlashesFetcher>(context, limit)
+                    .await
+            }
+            ScrapingModule::Nominations => {
+                self.backfill_with::<NominationsFetcher>(context, limit)
+                    .await
+            }
+            ScrapingModule::StakingActions => {
+                self.backfill_with::<StakingActionsFetcher>(context, limit)
+                    .await
+            }
+        }
+    }
+    async fn backfill_with<T>(&self, context: &Context, limit: BackfillLimit) -> Result<usize>
+    where
+        T: 'static + Send + Sync + FetchChainData,
+    {
+        let fetcher = T::new(self.db.clone(), Arc::clone(&self.api));
+
+        backfill_context(&fetcher, context, limit)
+            .instrument(info_span!(
+                "backfill",
+                module = T::name(),
+                network = context.network.as_str(),
+                stash = %context.stash
+            ))
+            .await
+    }
+    async fn run_fetcher<T>(&mut self, module: &ScrapingModule)
     where
         T: 'static + Send + Sync + FetchChainData,
     {
-        async fn local<T>(fetcher: &T, contexts: &Arc<RwLock<Vec<Context>>>) -> Result<()>
+        #[allow(clippy::too_many_arguments)]
+        async fn local<T>(
+            fetcher: &T,
+            contexts: &Arc<RwLock<Vec<Context>>>,
+            reorg_check_depth: Option<u64>,
+            max_pages: Option<usize>,
+            lease: &Lease,
+            parents: Option<usize>,
+            stats: &Stats,
+            paused: &AtomicBool,
+            loop_jitter: f64,
+        ) -> Result<()>
         where
             T: 'static + Send + Sync + FetchChainData,
         {
-            let mut page: usize = 1;
-
             loop {
-                // This `read()` can result in a quite long-running lock.
-                // However, it is not expected that `Self::add_contexts` will be
-                // called after a fetcher is running, since those are loaded on
-                // application startup.
-                for context in contexts.read().await.iter() {
-                    loop {
-                        let resp = fetcher.fetch_data(context, ROW_AMOUNT, page).await?;
-
-                        // No entires were found, continue with next account.
-                        if resp.is_empty() {
-                            debug!(
-                                "{}: No new entries were found for {:?}, moving on...",
-                                T::name(),
-                                context
-                            );
-                            break;
-                        }
-
-                        // The cache tries to filter all unprocessed extrinsics,
-                        // but the cache is not persisted and is wiped on
-                        // application shutdown. The database method will return
-                        // how many extrinsics have been *newly* inserted into
-                        // the database. If it's 0, then no new extrinsics were
-                        // detected. Continue with the next account.
-                        let newly_inserted = fetcher.store_data(context, &resp).await?;
-                        if newly_inserted == 0 {
-                            debug!(
-                                "{}: No new entries were found for {:?}, moving on...",
-                                T::name(),
-                                context
-                            );
-                            break;
-                        }
+                fetch_cycle(
+                    fetcher,
+                    contexts,
+                    reorg_check_depth,
+                    max_pages,
+                    lease,
+                    parents,
+                    stats,
+                    paused,
+                )
+                .await?;
 
-                        info!(
-                            "{}: {} new entries found for {:?}",
-                            T::name(),
-                            newly_inserted,
-                            context
-                        );
+                // Once all accounts have been processed, pause so other active
+                // fetchers are not blocked (by the time guard) from executing
+                // requests.
+                sleep(jittered(Duration::from_secs(LOOP_INTERVAL), loop_jitter)).await;
+            }
+        }
 
-                        // If new extrinsics were all on one page, continue with
-                        // the next account. Otherwise, fetch the next page.
-                        if newly_inserted < ROW_AMOUNT {
-                            debug!(
-                                "{}: All new entries have been fetched for {:?}, \
-                            continuing with the next accounts.",
-                                T::name(),
-                                context
-                            );
-                            break;
-                        }
+        let mut fetcher = T::new(self.db.clone(), Arc::clone(&self.api));
+        if let Some(alert) = &self.transfer_alert {
+            fetcher.set_transfer_alert(Arc::clone(alert));
+        }
+        let contexts = Arc::clone(&self.contexts);
+        let reorg_check_depth = self.reorg_check_depth;
+        let max_pages = self.max_pages;
+        // The amount of configured (explicit) accounts.
+        let parents = if self.track_parents {
+            Some(self.contexts.read().await.len())
+        } else {
+            None
+        };
+        let lease = Lease {
+            db: self.db.clone(),
+            name: T::name(),
+            holder: self.instance_id.clone(),
+        };
+        let stats = Arc::clone(&self.stats);
+        let loop_jitter = self.loop_jitter;
+        let paused = self.control.flag(module);
+        if self.once {
+            self.tasks.push(tokio::spawn(
+                async move {
+                    info!("Running a single cycle...");
+                    let failed = fetch_cycle(
+                        &fetcher,
+                        &contexts,
+                        reorg_check_depth,
+                        max_pages,
+                        &lease,
+                        parents,
+                        &stats,
+                        &paused,
+                    )
+                    .await
+                    .map_err(|err| {
+                        error!(error = ?err, "Failed task while running fetcher");
+                        stats.add_error();
+                        err
+                    })?;
 
-                        page += 1;
+                    // The errors were already logged and counted per account.
+                    if failed > 0 {
+                        return Err(anyhow!("failed to fetch {} account(s)", failed));
                     }
 
-                    // Reset to page 1.
-                    page = 1;
+                    Ok(())
                 }
+                .instrument(info_span!("fetcher", module = T::name())),
+            ));
 
-                // Once all accounts have been processed, pause so other active
-                // fetchers are not blocked (by the time guard) from executing
-                // requests.
-                sleep(Duration::from_secs(LOOP_INTERVAL)).await;
-            }
+            return;
         }
 
-        let fetcher = T::new(self.db.clone(), Arc::clone(&self.api));
-        let contexts = Arc::clone(&self.contexts);
         let mut last_err = Timestamp::now();
 
-        tokio::spawn(async move {
-            info!("{}: Running event loop...", T::name());
-            loop {
-                if let Err(err) = local(&fetcher, &contexts).await {
-                    // Only print errors when two or more occur within one
-                    // minute. Sometimes the Subscan API just returns an empty
-                    // value.
-                    if Timestamp::now().as_secs() - last_err.as_secs() < MAX_ERR_DIFF {
-                        error!(
-                            "Failed task while running fetcher '{}': {:?}",
-                            T::name(),
-                            err
-                        );
-                    } else {
-                        debug!(
-                            "(Acceptable) Failed task while running fetcher '{}'",
-                            T::name(),
-                        );
+        tokio::spawn(
+            async move {
+                info!("Running event loop...");
+                loop {
+                    if let Err(err) = local(
+                        &fetcher,
+                        &contexts,
+                        reorg_check_depth,
+                        max_pages,
+                        &lease,
+                        parents,
+                        &stats,
+                        &paused,
+                        loop_jitter,
+                    )
+                    .await
+                    {
+                        stats.add_error();
+
+                        // Only print errors when two or more occur within one
+                        // minute. Sometimes the Subscan API just returns an
+                        // empty value.
+                        if (Timestamp::now() - last_err).as_secs() < MAX_ERR_DIFF {
+                            error!(error = ?err, "Failed task while running fetcher");
+                        } else {
+                            debug!(error = ?err, "(Acceptable) Failed task while running fetcher");
+                        }
+
+                        last_err = Timestamp::now();
                     }
 
-                    last_err = Timestamp::now();
+                    sleep(Duration::from_secs(FAILED_TASK_SLEEP)).await;
                 }
-
-                sleep(Duration::from_secs(FAILED_TASK_SLEEP)).await;
             }
-        });
+            .instrument(info_span!("fetcher", module = T::name())),
+        );
     }
 }
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum ReportModule {
-    Transfers,
-    RewardsSlashes,
-    Nominations,
+/// Waits for all tasks to complete and returns the first error which occurred,
+/// if any.
+async fn wait_for_tasks(tasks: Vec<JoinHandle<Result<()>>>) -> Result<()> {
+    let mut result = Ok(());
+    for task in tasks {
+        let res = task.await.map_err(|err| err.into()).and_then(|res| res);
+        if result.is_ok() {
+            result = res;
+        }
+    }
+
+    result
 }
 
-pub struct ReportGenerator {
-    db: DatabaseReader,
-    contexts: Arc<RwLock<Vec<Context>>>,
+/// A database-backed lease which ensures that a fetcher only runs on a single
+/// instance among all instances sharing the database.
+struct Lease {
+    db: Database,
+    name: &'static str,
+    holder: String,
 }
 
-impl ReportGenerator {
-    pub fn new(db: DatabaseReader) -> Self {
-        ReportGenerator {
-            db: db,
-            contexts: Default::default(),
-        }
-    }
-    // TODO: make this part of `new()` and wrap it in an `Arc`.
-    pub async fn add_contexts(&mut self, mut contexts: Vec<Context>) {
-        self.contexts.write().await.append(&mut contexts);
-    }
-    pub async fn run(
-        &mut self,
-        module: ReportModule,
-        publisher: Arc<GoogleDrive>,
-        info: <GoogleDrive as Publisher>::Info,
-    ) {
-        match module {
-            ReportModule::Transfers => {
-                let generator =
-                    TransferReportGenerator::new(self.db.clone(), Arc::clone(&self.contexts));
-                self.do_run(generator, publisher, info).await;
-            }
-            ReportModule::RewardsSlashes => {
-                let generator =
-                    RewardSlashReportGenerator::new(self.db.clone(), Arc::clone(&self.contexts));
-                self.do_run(generator, publisher, info).await;
-            }
-            ReportModule::Nominations => {
-                let generator =
-                    NominationReportGenerator::new(self.db.clone(), Arc::clone(&self.contexts));
-                self.do_run(generator, publisher, info).await;
-            }
-        }
+impl Lease {
+    /// Acquires or renews the lease. Returns `false` if it is held by another
+    /// instance.
+    async fn acquire(&self) -> Result<bool> {
+        self.db
+            .acquire_lease(self.name, &self.holder, FETCHER_LEASE_TTL)
+            .await
     }
-    async fn do_run<T, P>(&self, generator: T, publisher: Arc<P>, info: <P as Publisher>::Info)
-    where
-        T: 'static + Send + Sync + GenerateReport<P>,
-        P: 'static + Send + Sync + Publisher,
-        <T as GenerateReport<P>>::Data: Send + Sync,
-        <T as GenerateReport<P>>::Report: Send + Sync,
-        <P as Publisher>::Info: Send + Sync + Clone,
+}
+
+/// Processes each account once. Accounts are fetched one page at a time in
+/// turns, so accounts with many new entries do not delay the others until
+/// all of their entries are fetched. An account which fails, e.g. because
+/// the API of its network is down, is skipped for the rest of the cycle
+/// instead of stalling the other accounts. Returns the amount of failed
+/// accounts, which are retried in the next cycle.
+#[allow(clippy::too_many_arguments)]
+async fn fetch_cycle<T>(
+    fetcher: &T,
+    contexts: &Arc<RwLock<Vec<Context>>>,
+    reorg_check_depth: Option<u64>,
+    max_pages: Option<usize>,
+    lease: &Lease,
+    parents: Option<usize>,
+    stats: &Stats,
+    paused: &AtomicBool,
+) -> Result<usize>
+where
+    T: 'static + Send + Sync + FetchChainData,
+{
+    let start = Instant::now();
+    let mut discovered = vec![];
+    let mut failed = 0;
+
     {
-        async fn local<T, P>(
-            generator: &T,
-            publisher: Arc<P>,
-            info: <P as Publisher>::Info,
-        ) -> Result<()>
-        where
-            P: 'static + Send + Sync + Publisher,
-            T: 'static + Send + Sync + GenerateReport<P>,
-            <P as Publisher>::Info: Send + Sync + Clone,
-        {
-            let mut first_run = true;
-            loop {
-                if let Some(data) = generator.fetch_data().await? {
-                    for report in generator.generate(&data).await? {
-                        debug!("New report generated, uploading...");
-                        generator
-                            .publish(Arc::clone(&publisher), info.clone(), report)
-                            .await?;
-                    }
-                } else {
-                    if first_run {
-                        warn!("No data found to generate report");
-                        first_run = false;
-                    }
+        // A copy of the accounts, so the lock is not held for the whole cycle,
+        // including while the fetcher is paused. Accounts which are added in
+        // the meantime are processed in the next cycle.
+        let contexts = contexts.read().await.clone();
+        let mut progress: Vec<_> = contexts.iter().map(|_| ContextProgress::new()).collect();
+
+        'passes: while progress.iter().any(|progress| !progress.done) {
+            for (idx, (context, progress)) in contexts.iter().zip(progress.iter_mut()).enumerate() {
+                if progress.done {
+                    continue;
                 }
 
-                sleep(Duration::from_secs(LOOP_INTERVAL)).await;
-            }
-        }
-
-        tokio::spawn(async move {
-            info!("{}: Running event loop...", T::name());
+                // Checked before the lease, which lapses if paused for long,
+                // so another instance can take over.
+                wait_while_paused(paused, Duration::from_secs(PAUSE_CHECK_INTERVAL)).await;
 
-            loop {
-                if let Err(err) =
-                    local::<T, P>(&generator, Arc::clone(&publisher), info.clone()).await
-                {
-                    error!(
-                        "Failed task while running report generator '{}': {:?}",
-                        T::name(),
-                        err
-                    );
+                // Only one instance per module may fetch at a time, so
+                // redundant replicas do not fetch the same data. The lease is
+                // renewed for each page.
+                if !lease.acquire().await? {
+                    debug!("Fetcher is active on another instance, pausing");
+                    break 'passes;
                 }
 
-                sleep(Duration::from_secs(FAILED_TASK_SLEEP)).await;
-            }
+                let span = debug_span!(
+                    "context",
+                    network = context.network.as_str(),
+                    stash = %context.stash
+                );
+
+                // Implicit accounts are appended after the configured ones.
+                // Only the parents of the latter are followed, so the chain
+                // of parents is never walked recursively.
+                let follow_parents = parents.is_some_and(|configured| idx < configured);
+
+                let res = async {
+                    fetch_page(
+                        fetcher,
+                        context,
+                        stats,
+                        progress,
+                        follow_parents.then_some(&mut discovered),
+                    )
+                    .await?;
+
+                    // A guardrail on top of the regular stop conditions of
+                    // `fetch_page`, see `ScrapingService::set_max_pages`.
+                    if !progress.done && max_pages.is_some_and(|max| progress.page > max) {
+                        // The next cycle starts from the most recent page
+                        // again and stops once it reaches stored entries, so
+                        // the remaining pages are never fetched.
+                        warn!(
+                            max_pages,
+                            "Reached the maximum amount of pages per cycle, older entries of the account are not fetched (run a backfill to fetch them), continuing with the next account"
+                        );
+                        progress.done = true;
+                    }
+
+                    if progress.done {
+                        if let Some(depth) = reorg_check_depth {
+                            check_reorg(fetcher, context, depth, stats).await?;
+                        }
+                    }
+
+                    Result::<()>::Ok(())
+                }
+                .instrument(span.clone())
+                .await;
+
+                if let Err(err) = res {
+                    stats.add_error();
+                    failed += 1;
+                    progress.done = true;
+
+                    span.in_scope(|| {
+                        warn!(error = ?err, "Failed to fetch account, continuing with the other accounts")
+                    });
+                }
+            }
+        }
+    }
+
+    if !discovered.is_empty() {
+        let added = add_implicit_contexts(&mut *contexts.write().await, discovered);
+        if added > 0 {
+            info!(added, "Monitoring parents of sub-accounts");
+        }
+    }
+
+    stats.cycle_completed(start.elapsed());
+
+    Ok(failed)
+}
+
+/// The progress of fetching the new entries of an account within a cycle,
+/// see `fetch_page`.
+struct ContextProgress {
+    /// The next page to fetch.
+    page: usize,
+    /// The amount of newly inserted entries.
+    inserted: usize,
+    /// The highest block stored before this cycle, see `fetch_page`.
+    watermark: Option<BlockNumber>,
+    /// The keys of all entries fetched in this cycle.
+    seen: HashSet<String>,
+    /// Whether all new entries have been fetched.
+    done: bool,
+}
+
+impl ContextProgress {
+    fn new() -> Self {
+        ContextProgress {
+            page: 1,
+            inserted: 0,
+            watermark: None,
+            seen: HashSet::new(),
+            done: false,
+        }
+    }
+}
+
+/// Fetches and stores the next page of entries of the account, marking the
+/// progress as done once no further new entries are expected.
+async fn fetch_page<T>(
+    fetcher: &T,
+    context: &Context,
+    stats: &Stats,
+    progress: &mut ContextProgress,
+    parents: Option<&mut Vec<Context>>,
+) -> Result<()>
+where
+    T: 'static + Send + Sync + FetchChainData,
+{
+    let page = progress.page;
+
+    stats.add_request();
+    let resp = fetcher.fetch_data(context, ROW_AMOUNT, page).await?;
+
+    // No entires were found, continue with next account. Unpaginated data,
+    // such as nominations, is still stored, since an empty response means
+    // that all entries were removed.
+    if resp.is_empty() && T::PAGINATED {
+        debug!(page, "No new entries were found, moving on...");
+        progress.done = true;
+        return Ok(());
+    }
+
+    // Also checked on already known entries, since implicit contexts are not
+    // persisted.
+    if let Some(parents) = parents {
+        for parent in resp.parents(&context.stash) {
+            parents.push(parent_context(context, &parent));
+        }
+    }
+
+    // Entries are sorted by block number in descending order, so anything
+    // below the highest stored block was already fetched in a previous cycle.
+    // Entries of the highest stored block itself are fetched again, since not
+    // all of them might have been stored, the unique index skips duplicates.
+    if page == 1 {
+        progress.watermark = fetcher.latest_block(context).await?;
+    }
+    let watermark = progress.watermark;
+    let is_known = |block: Option<BlockNumber>| {
+        block
+            .zip(watermark)
+            .is_some_and(|(block, watermark)| block < watermark)
+    };
+
+    if is_known(resp.newest_block()) {
+        debug!(page, "Reached already fetched entries, moving on...");
+        progress.done = true;
+        return Ok(());
+    }
+
+    // New entries shift older ones onto the next page, so pages can overlap
+    // if entries are added while paginating. Only the entries which were not
+    // part of a previous page of this cycle are counted.
+    let mut fresh = 0;
+    for key in resp.entry_keys() {
+        if progress.seen.insert(key) {
+            fresh += 1;
+        }
+    }
+
+    // Nothing but repeated entries, e.g. if the API ignores the page.
+    if fresh == 0 && T::PAGINATED {
+        debug!(
+            page,
+            "Page only contains entries of previous pages, moving on..."
+        );
+        progress.done = true;
+        return Ok(());
+    }
+
+    // The cache tries to filter all unprocessed extrinsics, but the cache is
+    // not persisted and is wiped on application shutdown. The database method
+    // will return how many extrinsics have been *newly* inserted into the
+    // database.
+    let stored = fetcher.store_data(context, &resp).await?;
+    let newly_inserted = stored.inserted;
+    if newly_inserted > 0 {
+        info!(inserted = newly_inserted, page, "New entries found");
+        stats.add_inserted(newly_inserted);
+        progress.inserted += newly_inserted;
+    }
+
+    // If some of the entries were already stored, all new entries have been
+    // fetched. Continue with the next account. Entries which were given up on
+    // were not stored before, so the following pages are still fetched.
+    if stored.handled() < fresh {
+        debug!(page, "Reached already stored entries, moving on...");
+        progress.done = true;
+        return Ok(());
+    }
+
+    // If the page is not full, there are no further pages. Otherwise, fetch
+    // the next page in the next turn.
+    if !T::PAGINATED || resp.len() < ROW_AMOUNT || is_known(resp.oldest_block()) {
+        debug!("All new entries have been fetched, continuing with the next accounts.");
+        progress.done = true;
+        return Ok(());
+    }
+
+    progress.page += 1;
+
+    Ok(())
+}
+
+/// Creates the implicit context of the parent of a sub-account.
+fn parent_context(context: &Context, parent: &Parent) -> Context {
+    let description = if parent.display.is_empty() {
+        format!("Parent of {}", context.description)
+    } else {
+        format!("{} (parent of {})", parent.display, context.description)
+    };
+
+    Context {
+        stash: parent.address.clone(),
+        network: context.network,
+        description,
+        labels: Default::default(),
+    }
+}
+
+/// Appends the discovered contexts which are not monitored yet. Returns the
+/// amount of added contexts.
+fn add_implicit_contexts(contexts: &mut Vec<Context>, discovered: Vec<Context>) -> usize {
+    let mut added = 0;
+    for context in discovered {
+        if !contexts.iter().any(|c| c.id() == context.id()) {
+            contexts.push(context);
+            added += 1;
+        }
+    }
+
+    added
+}
+
+/// Re-fetches the entries of the most recent `depth` blocks of an account and
+/// flags the stored entries which no longer exist as orphaned. Returns the
+/// amount of newly orphaned entries.
+async fn check_reorg<T>(fetcher: &T, context: &Context, depth: u64, stats: &Stats) -> Result<u64>
+where
+    T: 'static + Send + Sync + FetchChainData,
+{
+    let mut recent = vec![];
+    let mut from = None;
+    let mut page: usize = 1;
+
+    loop {
+        stats.add_request();
+        let resp = fetcher.fetch_data(context, ROW_AMOUNT, page).await?;
+        if resp.is_empty() {
+            break;
+        }
+
+        // The range is relative to the most recent entry.
+        let from = *from.get_or_insert_with(|| {
+            resp.newest_block()
+                .unwrap_or_default()
+                .saturating_sub(depth)
         });
+
+        // Entries are sorted by block number in descending order. Entries of
+        // the same block can span multiple pages, so only stop once the page
+        // reaches beyond the range.
+        let done = resp.len() < ROW_AMOUNT || resp.oldest_block().is_none_or(|b| b < from);
+
+        recent.push(resp);
+        if done {
+            break;
+        }
+
+        page += 1;
+    }
+
+    // Nothing could be fetched. Sometimes the Subscan API just returns an
+    // empty value, so this is not treated as all entries being orphaned.
+    let from = match from {
+        Some(from) => from,
+        None => return Ok(0),
+    };
+
+    let orphaned = fetcher
+        .mark_orphaned(context, from, &recent)
+        .await?
+        .unwrap_or(0);
+
+    if orphaned > 0 {
+        warn!(
+            orphaned,
+            from_block = %from,
+            "Flagged entries which no longer exist as orphaned, possibly caused by a reorg"
+        );
+    }
+
+    Ok(orphaned)
+}
+
+/// Fetches and stores all pages of a single account until the history is
+/// exhausted or one of the limits is reached. Returns the amount of newly
+/// inserted entries.
+async fn backfill_context<T>(fetcher: &T, context: &Context, limit: BackfillLimit) -> Result<usize>
+where
+    T: 'static + Send + Sync + FetchChainData,
+{
+    let mut total = 0;
+
+    for page in 1.. {
+        if limit.max_pages.is_some_and(|max| page > max) {
+            info!("Reached the maximum amount of pages");
+            break;
+        }
+
+        let resp = fetcher
+            .fetch_data(context, BACKFILL_ROW_AMOUNT, page)
+            .await?;
+
+        if resp.is_empty() {
+            break;
+        }
+
+        let inserted = fetcher.store_data(context, &resp).await?.inserted;
+        total += inserted;
+
+        info!(page, inserted, total, "Backfill progress");
+
+        // The last page is not full.
+        if !T::PAGINATED || resp.len() < BACKFILL_ROW_AMOUNT {
+            break;
+        }
+
+        if let (Some(oldest), Some(block)) = (limit.oldest_block, resp.oldest_block()) {
+            if block <= oldest {
+                info!(block = %block, "Reached the oldest block");
+                break;
+            }
+        }
+    }
+
+    Ok(total)
+}
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportModule {
+    Transfers,
+    RewardsSlashes,
+    Nominations,
+    /// Only the validators which were most recently added to or removed from
+    /// the nominations of each account.
+    NominationDeltas,
+    Combined,
+}
+
+impl ReportModule {
+    /// The scraping modules collecting the data the reports are based on.
+    pub fn scraping_modules(&self) -> &'static [ScrapingModule] {
+        match self {
+            ReportModule::Transfers => &[ScrapingModule::Transfer],
+            ReportModule::RewardsSlashes => &[ScrapingModule::RewardsSlashes],
+            ReportModule::Nominations | ReportModule::NominationDeltas => {
+                &[ScrapingModule::Nominations]
+            }
+            ReportModule::Combined => &[
+                ScrapingModule::Transfer,
+                ScrapingModule::RewardsSlashes,
+                ScrapingModule::Nominations,
+            ],
+        }
     }
 }
 
+impl FromStr for ReportModule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        serde_json::from_value(serde_json::Value::String(s.to_string()))
+            .map_err(|_| anyhow!("unknown report module: {}", s))
+    }
+}
+
+pub struct ReportGenerator {
+    db: DatabaseReader,
+    contexts: Arc<RwLock<Vec<Context>>>,
+    transfer_sort: TransferSort,
+    transfer_counterparty: Option<String>,
+    transfer_extrinsics: Vec<ExtrinsicType>,
+    transfer_exclude_failed: bool,
+    transfer_buckets: Option<ReportBuckets>,
+    transfer_columns: Option<Vec<TransferColumn>>,
+    transfer_max_rows: Option<usize>,
+    transfer_format: ReportFormat,
+    reward_slash_range: Option<ReportRange>,
+    labels: Labels,
+    csv_delimiter: u8,
+    report_metadata: bool,
+    stats: Arc<Stats>,
+    loop_jitter: f64,
+    /// Limits how many report modules generate and publish reports at the
+    /// same time. Unlimited if not set.
+    concurrency: Option<Arc<Semaphore>>,
+    once: bool,
+    /// The report modules running a single time, see `set_once`.
+    tasks: Vec<JoinHandle<Result<()>>>,
+}
+
+impl ReportGenerator {
+    pub fn new(db: DatabaseReader) -> Self {
+        Self::with_contexts(db, Default::default())
+    }
+    /// Reports on the given (shared) list of accounts, e.g. the one of a
+    /// `ScrapingService`.
+    pub fn with_contexts(db: DatabaseReader, contexts: Arc<RwLock<Vec<Context>>>) -> Self {
+        ReportGenerator {
+            db,
+            contexts,
+            transfer_sort: Default::default(),
+            transfer_counterparty: None,
+            transfer_extrinsics: vec![],
+            transfer_exclude_failed: false,
+            transfer_buckets: None,
+            transfer_columns: None,
+            transfer_max_rows: None,
+            transfer_format: ReportFormat::default(),
+            reward_slash_range: None,
+            labels: Labels::new(),
+            csv_delimiter: DEFAULT_CSV_DELIMITER,
+            report_metadata: false,
+            stats: Arc::new(Stats::new()),
+            loop_jitter: DEFAULT_LOOP_JITTER,
+            concurrency: None,
+            once: false,
+            tasks: vec![],
+        }
+    }
+    pub fn set_transfer_sort(&mut self, sort: TransferSort) {
+        self.transfer_sort = sort;
+    }
+    /// Only reports transfers from or to the given address.
+    pub fn set_transfer_counterparty(&mut self, counterparty: String) {
+        self.transfer_counterparty = Some(counterparty);
+    }
+    /// Only reports transfers of the given extrinsic types, or all transfers
+    /// if empty.
+    pub fn set_transfer_extrinsics(&mut self, extrinsics: Vec<ExtrinsicType>) {
+        self.transfer_extrinsics = extrinsics;
+    }
+    /// Leaves the transfers of failed extrinsics out of the transfers report.
+    pub fn set_transfer_exclude_failed(&mut self, exclude: bool) {
+        self.transfer_exclude_failed = exclude;
+    }
+    /// Splits the transfers report into one file per bucket of time.
+    pub fn set_transfer_buckets(&mut self, buckets: Option<ReportBuckets>) {
+        self.transfer_buckets = buckets;
+    }
+    /// Only includes the given columns in the transfers report, in the given
+    /// order.
+    pub fn set_transfer_columns(&mut self, columns: Option<Vec<TransferColumn>>) {
+        self.transfer_columns = columns;
+    }
+    /// Splits each transfers report into parts of at most the given amount
+    /// of rows.
+    pub fn set_transfer_max_rows(&mut self, max_rows: Option<usize>) {
+        self.transfer_max_rows = max_rows;
+    }
+    /// Sets the file format of the transfers report, e.g. Parquet.
+    pub fn set_transfer_format(&mut self, format: ReportFormat) {
+        self.transfer_format = format;
+    }
+    /// Limits the rewards/slashes report to the given range.
+    pub fn set_reward_slash_range(&mut self, range: Option<ReportRange>) {
+        self.reward_slash_range = range;
+    }
+    /// Only reports on the accounts with all of the given labels, e.g.
+    /// `team: treasury`.
+    pub fn set_labels(&mut self, labels: Labels) {
+        self.labels = labels;
+    }
+    /// Sets the delimiter of CSV reports.
+    pub fn set_csv_delimiter(&mut self, delimiter: u8) {
+        self.csv_delimiter = delimiter;
+    }
+    /// Prepends a block of comment lines to CSV reports, listing the time of
+    /// generation, the version of the monitor, the amount of accounts and the
+    /// period covered.
+    pub fn set_report_metadata(&mut self, metadata: bool) {
+        self.report_metadata = metadata;
+    }
+    /// Counts the published reports and errors in the given (shared) stats,
+    /// e.g. the ones of a `ScrapingService`.
+    pub fn set_stats(&mut self, stats: Arc<Stats>) {
+        self.stats = stats;
+    }
+    /// The current stats of the report modules, see `set_stats`.
+    pub fn stats(&self) -> StatsSnapshot {
+        self.stats.snapshot()
+    }
+    /// Randomly lengthens or shortens the pause between generating reports by
+    /// up to the given fraction, `DEFAULT_LOOP_JITTER` by default.
+    pub fn set_loop_jitter(&mut self, jitter: f64) {
+        self.loop_jitter = jitter;
+    }
+    /// Sets the maximum amount of report modules which generate and publish
+    /// reports at the same time. Must be greater than zero.
+    pub fn set_max_concurrency(&mut self, max: usize) {
+        self.concurrency = Some(Arc::new(Semaphore::new(max)));
+    }
+    /// Generates and publishes the reports of each module a single time
+    /// instead of periodically. Use `wait` to wait for the modules to
+    /// complete.
+    pub fn set_once(&mut self, once: bool) {
+        self.once = once;
+    }
+    /// Waits for the report modules started in single run mode to complete
+    /// and returns the first error which occurred, if any.
+    pub async fn wait(&mut self) -> Result<()> {
+        wait_for_tasks(std::mem::take(&mut self.tasks)).await
+    }
+    // TODO: make this part of `new()` and wrap it in an `Arc`.
+    pub async fn add_contexts(&mut self, mut contexts: Vec<Context>) {
+        self.contexts.write().await.append(&mut contexts);
+    }
+    /// Runs a report module with a publisher which uploads full reports,
+    /// such as `GoogleStorage` or `StdoutPublisher`.
+    pub async fn run<P>(
+        &mut self,
+        module: ReportModule,
+        publisher: Arc<P>,
+        info: <P as Publisher>::Info,
+    ) where
+        P: 'static + Send + Sync + Publisher<Data = GoogleStoragePayload>,
+        <P as Publisher>::Info: Send + Sync + Clone,
+    {
+        match module {
+            ReportModule::Transfers => {
+                let generator = self.transfer_generator();
+                self.do_run(generator, publisher, info).await;
+            }
+            ReportModule::RewardsSlashes => {
+                let generator = self.reward_slash_generator();
+                self.do_run(generator, publisher, info).await;
+            }
+            ReportModule::Nominations => {
+                let generator =
+                    NominationReportGenerator::new(self.db.clone(), Arc::clone(&self.contexts))
+                        .with_labels(self.labels.clone())
+                        .with_delimiter(self.csv_delimiter)
+                        .with_metadata(self.report_metadata);
+                self.do_run(generator, publisher, info).await;
+            }
+            ReportModule::NominationDeltas => {
+                let generator = NominationDeltaReportGenerator::new(
+                    self.db.clone(),
+                    Arc::clone(&self.contexts),
+                )
+                .with_labels(self.labels.clone())
+                .with_delimiter(self.csv_delimiter)
+                .with_metadata(self.report_metadata);
+                self.do_run(generator, publisher, info).await;
+            }
+            ReportModule::Combined => {
+                let generator =
+                    CombinedReportGenerator::new(self.db.clone(), Arc::clone(&self.contexts))
+                        .with_labels(self.labels.clone());
+                self.do_run(generator, publisher, info).await;
+            }
+        }
+    }
+    /// Runs a report module with a publisher which notifies about notable
+    /// events rather than uploading full reports. Only modules which can be
+    /// summarized as events are supported.
+    pub async fn run_notification<P>(
+        &mut self,
+        module: ReportModule,
+        publisher: Arc<P>,
+        info: <P as Publisher>::Info,
+    ) -> Result<()>
+    where
+        P: 'static + Send + Sync + Publisher<Data = Notification>,
+        <P as Publisher>::Info: Send + Sync + Clone,
+    {
+        match module {
+            ReportModule::Transfers => {
+                let generator = self.transfer_generator();
+                self.do_run(generator, publisher, info).await;
+            }
+            ReportModule::RewardsSlashes => {
+                let generator = self.reward_slash_generator();
+                self.do_run(generator, publisher, info).await;
+            }
+            _ => {
+                return Err(anyhow!(
+                    "report module {:?} is not supported by notification publishers",
+                    module
+                ))
+            }
+        }
+
+        Ok(())
+    }
+    /// Generates and publishes the reports of a module a single time for the
+    /// given window of days, from the start of `from` until (excluding) `to`,
+    /// e.g. to regenerate historical reports from the stored data. Only
+    /// modules which cover a window of time are supported.
+    pub async fn generate_once<P>(
+        &self,
+        module: ReportModule,
+        from: NaiveDate,
+        to: NaiveDate,
+        publisher: Arc<P>,
+        info: <P as Publisher>::Info,
+    ) -> Result<()>
+    where
+        P: 'static + Send + Sync + Publisher<Data = GoogleStoragePayload>,
+        <P as Publisher>::Info: Send + Sync + Clone,
+    {
+        if from >= to {
+            return Err(anyhow!(
+                "the window must end after it starts, found {} to {}",
+                from,
+                to
+            ));
+        }
+
+        let concurrency = self.concurrency.as_deref();
+        let published = match module {
+            ReportModule::Transfers => {
+                let generator = self.transfer_generator().with_window(from, to);
+                publish_reports(&generator, &publisher, &info, concurrency, &self.stats).await
+            }
+            ReportModule::RewardsSlashes => {
+                let generator = self.reward_slash_generator().with_window(from, to);
+                publish_reports(&generator, &publisher, &info, concurrency, &self.stats).await
+            }
+            _ => {
+                return Err(anyhow!(
+                    "report module {:?} does not support a window of time",
+                    module
+                ))
+            }
+        };
+
+        match published {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                warn!("No data found to generate report");
+                Ok(())
+            }
+            Err(err) => {
+                self.stats.add_error();
+                Err(err)
+            }
+        }
+    }
+    fn transfer_generator(&self) -> TransferReportGenerator<'static> {
+        TransferReportGenerator::new(self.db.clone(), Arc::clone(&self.contexts))
+            .with_sort(self.transfer_sort)
+            .with_counterparty(self.transfer_counterparty.clone())
+            .with_extrinsics(self.transfer_extrinsics.clone())
+            .with_exclude_failed(self.transfer_exclude_failed)
+            .with_buckets(self.transfer_buckets)
+            .with_columns(self.transfer_columns.clone())
+            .with_max_rows(self.transfer_max_rows)
+            .with_format(self.transfer_format)
+            .with_labels(self.labels.clone())
+            .with_delimiter(self.csv_delimiter)
+            .with_metadata(self.report_metadata)
+    }
+    fn reward_slash_generator(&self) -> RewardSlashReportGenerator<'static> {
+        RewardSlashReportGenerator::new(self.db.clone(), Arc::clone(&self.contexts))
+            .with_range(self.reward_slash_range)
+            .with_labels(self.labels.clone())
+            .with_delimiter(self.csv_delimiter)
+            .with_metadata(self.report_metadata)
+    }
+    async fn do_run<T, P>(&mut self, generator: T, publisher: Arc<P>, info: <P as Publisher>::Info)
+    where
+        T: 'static + Send + Sync + GenerateReport<P>,
+        P: 'static + Send + Sync + Publisher,
+        <T as GenerateReport<P>>::Data: Send + Sync,
+        <T as GenerateReport<P>>::Report: Send + Sync,
+        <P as Publisher>::Info: Send + Sync + Clone,
+    {
+        async fn local<T, P>(
+            generator: &T,
+            publisher: Arc<P>,
+            info: <P as Publisher>::Info,
+            concurrency: Option<&Semaphore>,
+            stats: &Stats,
+            loop_jitter: f64,
+        ) -> Result<()>
+        where
+            P: 'static + Send + Sync + Publisher,
+            T: 'static + Send + Sync + GenerateReport<P>,
+            <P as Publisher>::Info: Send + Sync + Clone,
+        {
+            let mut first_run = true;
+            loop {
+                if !publish_reports(generator, &publisher, &info, concurrency, stats).await?
+                    && first_run
+                {
+                    warn!("No data found to generate report");
+                    first_run = false;
+                }
+
+                sleep(jittered(Duration::from_secs(LOOP_INTERVAL), loop_jitter)).await;
+            }
+        }
+
+        let concurrency = self.concurrency.clone();
+        let stats = Arc::clone(&self.stats);
+        let loop_jitter = self.loop_jitter;
+
+        if self.once {
+            self.tasks.push(tokio::spawn(
+                async move {
+                    info!("Generating reports once...");
+                    match publish_reports::<T, P>(
+                        &generator,
+                        &publisher,
+                        &info,
+                        concurrency.as_deref(),
+                        &stats,
+                    )
+                    .await
+                    {
+                        Ok(true) => Ok(()),
+                        Ok(false) => {
+                            warn!("No data found to generate report");
+                            Ok(())
+                        }
+                        Err(err) => {
+                            error!(error = ?err, "Failed task while running report generator");
+                            stats.add_error();
+                            Err(err)
+                        }
+                    }
+                }
+                .instrument(info_span!("report_generator", module = T::name())),
+            ));
+
+            return;
+        }
+
+        tokio::spawn(
+            async move {
+                info!("Running event loop...");
+
+                loop {
+                    if let Err(err) = local::<T, P>(
+                        &generator,
+                        Arc::clone(&publisher),
+                        info.clone(),
+                        concurrency.as_deref(),
+                        &stats,
+                        loop_jitter,
+                    )
+                    .await
+                    {
+                        error!(error = ?err, "Failed task while running report generator");
+                        stats.add_error();
+                    }
+
+                    sleep(Duration::from_secs(FAILED_TASK_SLEEP)).await;
+                }
+            }
+            .instrument(info_span!("report_generator", module = T::name())),
+        );
+    }
+}
+
+/// Generates and publishes the reports of a report module once. Returns
+/// `false` if there was no data to report on.
+async fn publish_reports<T, P>(
+    generator: &T,
+    publisher: &Arc<P>,
+    info: &<P as Publisher>::Info,
+    concurrency: Option<&Semaphore>,
+    stats: &Stats,
+) -> Result<bool>
+where
+    P: 'static + Send + Sync + Publisher,
+    T: 'static + Send + Sync + GenerateReport<P>,
+    <P as Publisher>::Info: Send + Sync + Clone,
+{
+    // Held until the reports are published.
+    let _permit = match concurrency {
+        Some(semaphore) => Some(semaphore.acquire().await?),
+        None => None,
+    };
+
+    let data = match generator.fetch_data().await? {
+        Some(data) => data,
+        None => return Ok(false),
+    };
+
+    for report in generator.generate(&data).await? {
+        debug!("New report generated, uploading...");
+        generator
+            .publish(Arc::clone(publisher), info.clone(), report)
+            .await?;
+        stats.add_report();
+    }
+
+    Ok(true)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::database::DatabaseReader;
-    use crate::publishing::GoogleDrive;
+    use crate::chain_api::tests::{mock_transfers_page, transfers_page};
+    use crate::publishing::{GoogleStorage, StdoutPublisher};
+    use crate::rate_limit::RateLimiter;
     use crate::reporting::TransferReport;
-    use crate::tests::{db, init};
+    use crate::tests::{db, init, memory_db};
     use crate::wait_blocking;
-    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
     use std::vec;
 
+    /// Serves empty pages (or errors) and records which transfer pages were
+    /// requested.
+    #[derive(Default)]
+    struct MockSource {
+        requested: Mutex<Vec<usize>>,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl ChainBackend for MockSource {
+        async fn request_transfer(
+            &self,
+            _context: &Context,
+            _row: usize,
+            page: usize,
+        ) -> Result<Response<TransfersPage>> {
+            self.requested.lock().unwrap().push(page);
+
+            if self.fail {
+                return Err(anyhow!("mock failure"));
+            }
+
+            Ok(Default::default())
+        }
+        async fn request_reward_slash(
+            &self,
+            _context: &Context,
+            _row: usize,
+            _page: usize,
+        ) -> Result<Response<RewardsSlashesPage>> {
+            Ok(Default::default())
+        }
+        async fn request_staking_actions(
+            &self,
+            _context: &Context,
+            _row: usize,
+            _page: usize,
+        ) -> Result<Response<StakingActionsPage>> {
+            Ok(Default::default())
+        }
+        async fn request_nominations(
+            &self,
+            _context: &Context,
+        ) -> Result<Response<NominationsPage>> {
+            Ok(Default::default())
+        }
+    }
+
+    /// Runs a single cycle over the given accounts, like the fetchers do.
+    /// Returns the amount of failed accounts, see `fetch_cycle`.
+    async fn run_cycle<T>(fetcher: &T, contexts: &[Context], stats: &Stats) -> usize
+    where
+        T: 'static + Send + Sync + FetchChainData,
+    {
+        let lease = Lease {
+            db: memory_db().await,
+            name: T::name(),
+            holder: "instance".to_string(),
+        };
+
+        fetch_cycle(
+            fetcher,
+            &Arc::new(RwLock::new(contexts.to_vec())),
+            None,
+            None,
+            &lease,
+            None,
+            stats,
+            &AtomicBool::new(false),
+        )
+        .await
+        .unwrap()
+    }
+
     struct StdOut;
 
     #[async_trait]
@@ -408,6 +1905,992 @@ mod tests {
         }
     }
 
+    /// Records how many instances fetch data at the same time.
+    #[derive(Default)]
+    struct InFlight {
+        current: AtomicUsize,
+        max: AtomicUsize,
+    }
+
+    struct SlowGenerator(Arc<InFlight>);
+
+    #[async_trait]
+    impl GenerateReport<StdOut> for SlowGenerator {
+        type Data = ();
+        type Report = TransferReport;
+
+        fn name() -> &'static str {
+            "SlowGenerator"
+        }
+        async fn fetch_data(&self) -> Result<Option<Self::Data>> {
+            let current = self.0.current.fetch_add(1, Ordering::SeqCst) + 1;
+            self.0.max.fetch_max(current, Ordering::SeqCst);
+            sleep(Duration::from_millis(50)).await;
+            self.0.current.fetch_sub(1, Ordering::SeqCst);
+
+            Ok(None)
+        }
+        async fn generate(&self, _data: &Self::Data) -> Result<Vec<Self::Report>> {
+            Ok(vec![])
+        }
+        async fn publish(
+            &self,
+            _publisher: Arc<StdOut>,
+            _info: (),
+            _report: Self::Report,
+        ) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn jitter_loop_interval() {
+        let interval = Duration::from_secs(LOOP_INTERVAL);
+        assert_eq!(jittered(interval, 0.0), interval);
+
+        let intervals: Vec<_> = (0..100).map(|_| jittered(interval, 0.2)).collect();
+        for jittered in &intervals {
+            assert!(*jittered >= interval.mul_f64(0.8));
+            assert!(*jittered <= interval.mul_f64(1.2));
+        }
+        // The intervals are spread out rather than realigned.
+        assert!(intervals.iter().any(|jittered| *jittered != intervals[0]));
+    }
+
+    #[tokio::test]
+    async fn pause_and_resume_fetcher() {
+        let service = ScrapingService::new(memory_db().await);
+        let control = service.control();
+        assert!(!control.is_paused(&ScrapingModule::Transfer));
+
+        service.pause(&ScrapingModule::Transfer);
+        assert!(control.is_paused(&ScrapingModule::Transfer));
+        assert!(!control.is_paused(&ScrapingModule::Nominations));
+
+        // The fetcher keeps waiting while paused, no matter how much time
+        // passes.
+        tokio::time::pause();
+        let paused = control.flag(&ScrapingModule::Transfer);
+        let mut waiting = tokio::spawn(async move {
+            wait_while_paused(&paused, Duration::from_secs(PAUSE_CHECK_INTERVAL)).await;
+        });
+        assert!(
+            tokio::time::timeout(Duration::from_secs(3600), &mut waiting)
+                .await
+                .is_err()
+        );
+
+        control.resume(&ScrapingModule::Transfer);
+        assert!(!service.control().is_paused(&ScrapingModule::Transfer));
+        tokio::time::timeout(Duration::from_secs(PAUSE_CHECK_INTERVAL), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn release_contexts_while_paused() {
+        let alice = Context::alice();
+        let fetcher = AccountsFetcher {
+            pages: HashMap::from([(alice.stash.clone(), vec![transfers_page(0, 5)])]),
+            ..Default::default()
+        };
+        let contexts = Arc::new(RwLock::new(vec![alice]));
+        let paused = Arc::new(AtomicBool::new(true));
+        let lease = Lease {
+            db: memory_db().await,
+            name: AccountsFetcher::name(),
+            holder: "instance".to_string(),
+        };
+        tokio::time::pause();
+
+        let cycle = tokio::spawn({
+            let contexts = Arc::clone(&contexts);
+            let paused = Arc::clone(&paused);
+            async move {
+                fetch_cycle(
+                    &fetcher,
+                    &contexts,
+                    None,
+                    None,
+                    &lease,
+                    None,
+                    &Stats::new(),
+                    &paused,
+                )
+                .await
+            }
+        });
+        // Lets the cycle run until it waits for the fetcher to be resumed.
+        tokio::task::yield_now().await;
+        assert!(!cycle.is_finished());
+
+        // Other fetchers can add accounts in the meantime.
+        tokio::time::timeout(Duration::from_secs(1), contexts.write())
+            .await
+            .unwrap()
+            .push(Context::bob());
+
+        paused.store(false, Ordering::Relaxed);
+        tokio::time::advance(Duration::from_secs(PAUSE_CHECK_INTERVAL)).await;
+        // The store runs on the regular clock.
+        tokio::time::resume();
+        assert_eq!(cycle.await.unwrap().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn limit_report_concurrency() {
+        let db = memory_db().await;
+        let publisher = Arc::new(StdOut);
+        // The generators overlap deterministically on the paused clock.
+        tokio::time::pause();
+
+        // Unlimited by default.
+        for (max_concurrency, expected) in [(None, 3), (Some(1), 1)] {
+            let in_flight = Arc::new(InFlight::default());
+            let mut service = ReportGenerator::new(db.reader());
+            service.set_once(true);
+            if let Some(max) = max_concurrency {
+                service.set_max_concurrency(max);
+            }
+            for _ in 0..3 {
+                service
+                    .do_run(
+                        SlowGenerator(Arc::clone(&in_flight)),
+                        Arc::clone(&publisher),
+                        (),
+                    )
+                    .await;
+            }
+
+            service.wait().await.unwrap();
+            assert_eq!(in_flight.max.load(Ordering::SeqCst), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn run_reports_once() {
+        let db = memory_db().await;
+        let publisher = Arc::new(StdOut);
+        let in_flight = Arc::new(InFlight::default());
+        tokio::time::pause();
+
+        let mut service = ReportGenerator::new(db.reader());
+        service.set_once(true);
+        for _ in 0..3 {
+            service
+                .do_run(
+                    SlowGenerator(Arc::clone(&in_flight)),
+                    Arc::clone(&publisher),
+                    (),
+                )
+                .await;
+        }
+
+        // All modules have completed once waiting returns, rather than
+        // running in the background.
+        service.wait().await.unwrap();
+        assert_eq!(in_flight.max.load(Ordering::SeqCst), 3);
+        assert_eq!(in_flight.current.load(Ordering::SeqCst), 0);
+        assert!(service.tasks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn generate_once_rejects_invalid_arguments() {
+        let db = memory_db().await;
+        let service = ReportGenerator::new(db.reader());
+        let publisher = Arc::new(StdoutPublisher::new());
+        let june = NaiveDate::from_ymd_opt(2021, 6, 1).unwrap();
+        let july = NaiveDate::from_ymd_opt(2021, 7, 1).unwrap();
+
+        // Both are rejected before the database is queried.
+        let res = service
+            .generate_once(
+                ReportModule::Transfers,
+                july,
+                june,
+                Arc::clone(&publisher),
+                (),
+            )
+            .await;
+        assert!(res.is_err());
+
+        let res = service
+            .generate_once(ReportModule::Nominations, june, july, publisher, ())
+            .await;
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn parse_report_module() {
+        assert_eq!(
+            "rewards_slashes".parse::<ReportModule>().unwrap(),
+            ReportModule::RewardsSlashes
+        );
+        assert!("rewards".parse::<ReportModule>().is_err());
+    }
+
+    #[tokio::test]
+    async fn fetch_cycle_with_mock_source() {
+        let db = memory_db().await;
+        let alice = Context::alice();
+
+        // An empty first page stops the run.
+        let source = Arc::new(MockSource::default());
+        let fetcher =
+            TransferFetcher::new(db.clone(), Arc::clone(&source) as Arc<dyn ChainBackend>);
+
+        let stats = Stats::new();
+        assert_eq!(
+            run_cycle(&fetcher, std::slice::from_ref(&alice), &stats).await,
+            0
+        );
+        assert_eq!(stats.snapshot().inserted, 0);
+        assert_eq!(*source.requested.lock().unwrap(), vec![1]);
+
+        // Errors of the source fail the account.
+        let source = Arc::new(MockSource {
+            fail: true,
+            ..Default::default()
+        });
+        let fetcher = TransferFetcher::new(db, Arc::clone(&source) as Arc<dyn ChainBackend>);
+
+        let stats = Stats::new();
+        assert_eq!(run_cycle(&fetcher, &[alice], &stats).await, 1);
+        assert_eq!(stats.snapshot().errors, 1);
+        assert_eq!(*source.requested.lock().unwrap(), vec![1]);
+    }
+
+    /// Serves the given transfer pages and stores the entries in memory,
+    /// deduplicated like the database does. The `skipped` entries are given
+    /// up on, like entries of the dead letter collection.
+    #[derive(Default)]
+    struct PagedFetcher {
+        pages: Vec<Response<TransfersPage>>,
+        skipped: HashSet<String>,
+        stored: Mutex<HashSet<String>>,
+        requested: Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl FetchChainData for PagedFetcher {
+        type Data = Response<TransfersPage>;
+
+        fn name() -> &'static str {
+            "PagedFetcher"
+        }
+        fn new(_db: Database, _api: Arc<dyn ChainBackend>) -> Self {
+            Self::default()
+        }
+        async fn fetch_data(&self, _: &Context, _row: usize, page: usize) -> Result<Self::Data> {
+            self.requested.lock().unwrap().push(page);
+            Ok(self.pages.get(page - 1).cloned().unwrap_or_default())
+        }
+        async fn store_data(&self, _: &Context, data: &Self::Data) -> Result<StoredCount> {
+            let mut stored = self.stored.lock().unwrap();
+            let mut count = StoredCount::default();
+            for key in data.entry_keys() {
+                if self.skipped.contains(&key) {
+                    count.skipped += 1;
+                } else if stored.insert(key) {
+                    count.inserted += 1;
+                }
+            }
+            Ok(count)
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_overlapping_pages() {
+        let alice = Context::alice();
+
+        // A new transfer arrived after the first page was fetched, shifting
+        // the last two entries of the first page onto the second page. The
+        // entries from 20 onwards were stored in a previous cycle.
+        let fetcher = PagedFetcher {
+            pages: vec![
+                transfers_page(0, 10),
+                transfers_page(8, 10),
+                transfers_page(18, 10),
+                transfers_page(28, 10),
+            ],
+            ..Default::default()
+        };
+        fetcher
+            .store_data(&alice, &transfers_page(20, 20))
+            .await
+            .unwrap();
+
+        // The overlap does not stop the pagination, only the stored entries
+        // on the third page do.
+        let stats = Stats::new();
+        run_cycle(&fetcher, std::slice::from_ref(&alice), &stats).await;
+        assert_eq!(stats.snapshot().inserted, 20);
+        assert_eq!(*fetcher.requested.lock().unwrap(), vec![1, 2, 3]);
+
+        // A page which only repeats previous entries stops the pagination.
+        let fetcher = PagedFetcher {
+            pages: vec![transfers_page(0, 10), transfers_page(0, 10)],
+            ..Default::default()
+        };
+
+        let stats = Stats::new();
+        run_cycle(&fetcher, &[alice], &stats).await;
+        assert_eq!(stats.snapshot().inserted, 10);
+        assert_eq!(*fetcher.requested.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn fetch_pages_behind_skipped_entries() {
+        let alice = Context::alice();
+
+        // An entry of the first page can not be stored and is given up on.
+        let skipped = transfers_page(3, 1).entry_keys().into_iter().collect();
+        let fetcher = PagedFetcher {
+            pages: vec![transfers_page(0, 10), transfers_page(10, 3)],
+            skipped,
+            ..Default::default()
+        };
+
+        let stats = Stats::new();
+        run_cycle(&fetcher, &[alice], &stats).await;
+        assert_eq!(stats.snapshot().inserted, 12);
+        assert_eq!(*fetcher.requested.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn store_removed_nominations() {
+        let alice = Context::alice();
+        let db = memory_db().await;
+
+        let mut resp: Response<NominationsPage> = Default::default();
+        let mut nomination = crate::chain_api::Nomination::default();
+        nomination.stash_account_display.address = "validator".to_string();
+        resp.data.list = Some(vec![nomination]);
+        db.store_nomination_event(&alice, &resp).await.unwrap();
+
+        // The mock returns no list, as Subscan does once all validators were
+        // removed.
+        let fetcher = NominationsFetcher::new(db.clone(), Arc::new(MockSource::default()));
+        assert_eq!(
+            run_cycle(&fetcher, std::slice::from_ref(&alice), &Stats::new()).await,
+            0
+        );
+
+        let snapshots = db
+            .reader()
+            .fetch_nomination_snapshots(&alice, 2)
+            .await
+            .unwrap();
+        assert!(snapshots[0].data.validators.is_empty());
+        assert_eq!(snapshots[1].data.validators.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn fetch_updates_stats() {
+        let alice = Context::alice();
+        let stats = Stats::new();
+
+        let fetcher = PagedFetcher {
+            pages: vec![transfers_page(0, 10), transfers_page(10, 3)],
+            ..Default::default()
+        };
+
+        run_cycle(&fetcher, std::slice::from_ref(&alice), &stats).await;
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.requests, 2);
+        assert_eq!(snapshot.inserted, 13);
+
+        // Nothing new on the second run, but the request is counted.
+        run_cycle(&fetcher, &[alice], &stats).await;
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.requests, 3);
+        assert_eq!(snapshot.inserted, 13);
+        assert_eq!(snapshot.errors, 0);
+    }
+
+    /// Serves the given transfer pages of each account, recording the order
+    /// of the requests.
+    #[derive(Default)]
+    struct AccountsFetcher {
+        pages: HashMap<String, Vec<Response<TransfersPage>>>,
+        requested: Mutex<Vec<(String, usize)>>,
+        /// The requests of these networks fail.
+        unavailable: Vec<Network>,
+    }
+
+    #[async_trait]
+    impl FetchChainData for AccountsFetcher {
+        type Data = Response<TransfersPage>;
+
+        fn name() -> &'static str {
+            "AccountsFetcher"
+        }
+        fn new(_db: Database, _api: Arc<dyn ChainBackend>) -> Self {
+            Self::default()
+        }
+        async fn fetch_data(
+            &self,
+            context: &Context,
+            _row: usize,
+            page: usize,
+        ) -> Result<Self::Data> {
+            self.requested
+                .lock()
+                .unwrap()
+                .push((context.description.clone(), page));
+            if self.unavailable.contains(&context.network) {
+                return Err(anyhow!("{} is unavailable", context.network.as_str()));
+            }
+            Ok(self.pages[&context.stash]
+                .get(page - 1)
+                .cloned()
+                .unwrap_or_default())
+        }
+        async fn store_data(&self, _: &Context, data: &Self::Data) -> Result<StoredCount> {
+            Ok(StoredCount {
+                inserted: data.len(),
+                skipped: 0,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_accounts_in_turns() {
+        let db = memory_db().await;
+        let alice = Context::alice();
+        let bob = Context::bob();
+        let eve = Context {
+            stash: "eve".to_string(),
+            description: "Eve".to_string(),
+            ..Context::alice()
+        };
+
+        // Alice has many new entries, Bob a few and Eve none.
+        let fetcher = AccountsFetcher {
+            pages: HashMap::from([
+                (
+                    alice.stash.clone(),
+                    vec![
+                        transfers_page(0, 10),
+                        transfers_page(10, 10),
+                        transfers_page(20, 10),
+                        transfers_page(30, 5),
+                    ],
+                ),
+                (
+                    bob.stash.clone(),
+                    vec![transfers_page(100, 10), transfers_page(110, 5)],
+                ),
+                (eve.stash.clone(), vec![]),
+            ]),
+            ..Default::default()
+        };
+        let contexts = Arc::new(RwLock::new(vec![alice.clone(), bob.clone(), eve.clone()]));
+        let lease = Lease {
+            db,
+            name: AccountsFetcher::name(),
+            holder: "instance".to_string(),
+        };
+
+        let stats = Stats::new();
+        fetch_cycle(
+            &fetcher,
+            &contexts,
+            None,
+            None,
+            &lease,
+            None,
+            &stats,
+            &AtomicBool::new(false),
+        )
+        .await
+        .unwrap();
+
+        // Bob and Eve are fetched before the remaining pages of Alice.
+        let expected: Vec<(String, usize)> = [
+            (&alice, 1),
+            (&bob, 1),
+            (&eve, 1),
+            (&alice, 2),
+            (&bob, 2),
+            (&alice, 3),
+            (&alice, 4),
+        ]
+        .iter()
+        .map(|(context, page)| (context.description.clone(), *page))
+        .collect();
+        assert_eq!(*fetcher.requested.lock().unwrap(), expected);
+        assert_eq!(stats.snapshot().inserted, 50);
+    }
+
+    #[tokio::test]
+    async fn stop_at_max_pages() {
+        let db = memory_db().await;
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        // Every page of Alice is full of new entries.
+        let fetcher = AccountsFetcher {
+            pages: HashMap::from([
+                (
+                    alice.stash.clone(),
+                    (0..5).map(|page| transfers_page(page * 10, 10)).collect(),
+                ),
+                (bob.stash.clone(), vec![transfers_page(100, 5)]),
+            ]),
+            ..Default::default()
+        };
+        let contexts = Arc::new(RwLock::new(vec![alice.clone(), bob.clone()]));
+        let lease = Lease {
+            db,
+            name: AccountsFetcher::name(),
+            holder: "instance".to_string(),
+        };
+
+        let stats = Stats::new();
+        let failed = fetch_cycle(
+            &fetcher,
+            &contexts,
+            None,
+            Some(2),
+            &lease,
+            None,
+            &stats,
+            &AtomicBool::new(false),
+        )
+        .await
+        .unwrap();
+        assert_eq!(failed, 0);
+
+        let expected: Vec<(String, usize)> = [(&alice, 1), (&bob, 1), (&alice, 2)]
+            .iter()
+            .map(|(context, page)| (context.description.clone(), *page))
+            .collect();
+        assert_eq!(*fetcher.requested.lock().unwrap(), expected);
+        assert_eq!(stats.snapshot().inserted, 25);
+    }
+
+    #[tokio::test]
+    async fn continue_after_failed_account() {
+        let db = memory_db().await;
+        let alice = Context::alice();
+        let bob = Context::bob();
+        let mut kusama = Context::alice();
+        kusama.description = "Alice (Kusama)".to_string();
+        kusama.network = Network::Kusama;
+
+        // The API of Kusama is down, Polkadot is up.
+        let fetcher = AccountsFetcher {
+            pages: HashMap::from([
+                (
+                    alice.stash.clone(),
+                    vec![transfers_page(0, 10), transfers_page(10, 5)],
+                ),
+                (bob.stash.clone(), vec![transfers_page(100, 5)]),
+            ]),
+            unavailable: vec![Network::Kusama],
+            ..Default::default()
+        };
+        let contexts = Arc::new(RwLock::new(vec![
+            kusama.clone(),
+            alice.clone(),
+            bob.clone(),
+        ]));
+        let lease = Lease {
+            db,
+            name: AccountsFetcher::name(),
+            holder: "instance".to_string(),
+        };
+
+        let stats = Stats::new();
+        let failed = fetch_cycle(
+            &fetcher,
+            &contexts,
+            None,
+            None,
+            &lease,
+            None,
+            &stats,
+            &AtomicBool::new(false),
+        )
+        .await
+        .unwrap();
+        assert_eq!(failed, 1);
+
+        // The failed account is not retried within the cycle.
+        let expected: Vec<(String, usize)> = [(&kusama, 1), (&alice, 1), (&bob, 1), (&alice, 2)]
+            .iter()
+            .map(|(context, page)| (context.description.clone(), *page))
+            .collect();
+        assert_eq!(*fetcher.requested.lock().unwrap(), expected);
+
+        let stats = stats.snapshot();
+        assert_eq!(stats.inserted, 20);
+        assert_eq!(stats.errors, 1);
+        assert!(stats.last_cycle_at.is_some());
+    }
+
+    /// Records the notifications instead of sending them, or fails to send
+    /// them while `fail` is set.
+    #[derive(Default)]
+    struct RecordedAlerts {
+        sent: Mutex<Vec<Notification>>,
+        fail: AtomicBool,
+    }
+
+    #[async_trait]
+    impl Alert for RecordedAlerts {
+        async fn alert(&self, notification: Notification) -> Result<()> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(anyhow!("alert failed"));
+            }
+
+            self.sent.lock().unwrap().push(notification);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn alert_large_transfers() {
+        let recorded = Arc::new(RecordedAlerts::default());
+        let mut fetcher = TransferFetcher::new(
+            memory_db().await,
+            Arc::new(MockSource::default()) as Arc<dyn ChainBackend>,
+        );
+        fetcher.set_transfer_alert(Arc::new(TransferAlert::new(
+            vec![(Network::Polkadot, 1_000.0)].into_iter().collect(),
+            Arc::clone(&recorded) as Arc<dyn Alert>,
+        )));
+
+        // In Planck, 1 DOT equals 10^10 Planck.
+        let page = |amounts: &[&str]| {
+            let mut page = transfers_page(0, 0);
+            page.data.transfers = Some(
+                amounts
+                    .iter()
+                    .map(|amount| Transfer {
+                        amount: (*amount).into(),
+                        extrinsic_index: format!("{}-1", amount).into(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            );
+            page
+        };
+        let sent_amounts = || -> Vec<Vec<String>> {
+            recorded
+                .sent
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|n| n.events.iter().map(|e| e.amount.to_string()).collect())
+                .collect()
+        };
+
+        let alice = Context::alice();
+        fetcher
+            .store_data(&alice, &page(&["9990000000000"]))
+            .await
+            .unwrap();
+        assert!(sent_amounts().is_empty());
+
+        // Only newly stored transfers are alerted about.
+        let stored = fetcher
+            .store_data(
+                &alice,
+                &page(&["9990000000000", "10000000000000", "50000000000000"]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(stored.inserted, 2);
+        assert_eq!(sent_amounts(), vec![vec!["1000", "5000"]]);
+
+        // Failed alerts do not fail storing, but are retried with the next
+        // stored page.
+        recorded.fail.store(true, Ordering::SeqCst);
+        let stored = fetcher
+            .store_data(&alice, &page(&["20000000000000"]))
+            .await
+            .unwrap();
+        assert_eq!(stored.inserted, 1);
+        assert_eq!(sent_amounts().len(), 1);
+
+        recorded.fail.store(false, Ordering::SeqCst);
+        fetcher
+            .store_data(&alice, &page(&["30000000000000"]))
+            .await
+            .unwrap();
+        assert_eq!(
+            sent_amounts(),
+            vec![vec!["1000", "5000"], vec!["2000"], vec!["3000"]]
+        );
+
+        // No threshold is configured for Kusama.
+        let mut kusama = Context::alice();
+        kusama.network = Network::Kusama;
+        fetcher
+            .store_data(&kusama, &page(&["50000000000000000"]))
+            .await
+            .unwrap();
+        assert_eq!(sent_amounts().len(), 3);
+    }
+
+    #[test]
+    fn discover_parent_contexts() {
+        let alice = Context::alice();
+        let parent = Parent {
+            address: Context::bob().stash,
+            display: "Bob".to_string(),
+            ..Default::default()
+        };
+
+        // Alice is revealed as a sub-account by an outgoing transfer, the
+        // recipient's parent is ignored.
+        let mut page = transfers_page(0, 2);
+        let transfers = page.data.transfers.as_mut().unwrap();
+        transfers[0].from = alice.stash.clone();
+        transfers[0].from_account_display.parent = Some(parent.clone());
+        transfers[1].to_account_display.parent = Some(Parent {
+            address: Context::eve().stash,
+            ..Default::default()
+        });
+
+        let parents = page.parents(&alice.stash);
+        assert_eq!(parents, vec![parent.clone()]);
+
+        let context = parent_context(&alice, &parent);
+        assert_eq!(context.stash, Context::bob().stash);
+        assert_eq!(context.network, alice.network);
+        assert_eq!(
+            context.description,
+            format!("Bob (parent of {})", alice.description)
+        );
+
+        // Already monitored accounts are not added again.
+        let mut contexts = vec![alice.clone()];
+        let added = add_implicit_contexts(
+            &mut contexts,
+            vec![context.clone(), context.clone(), alice.clone()],
+        );
+        assert_eq!(added, 1);
+        assert_eq!(contexts, vec![alice, context]);
+    }
+
+    #[tokio::test]
+    async fn check_backend_once_per_network() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(wiremock::ResponseTemplate::new(200).set_body_json(transfers_page(0, 1)))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let db = memory_db().await;
+        let api = ChainApi::with_base_url(&server.uri())
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO));
+        let mut service = ScrapingService::with_backend(db, Arc::new(api));
+        service
+            .add_contexts(vec![
+                Context::alice(),
+                Context::bob(),
+                Context {
+                    network: Network::Kusama,
+                    ..Context::eve()
+                },
+            ])
+            .await;
+
+        service.check_backend().await.unwrap();
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn run_all_rejects_duplicate_modules() {
+        let db = memory_db().await;
+        let mut service = ScrapingService::with_backend(db, Arc::new(MockSource::default()));
+        service.set_once(true);
+
+        // Nothing is started if any module is duplicated.
+        let res = service
+            .run_all(&[
+                ScrapingModule::Transfer,
+                ScrapingModule::Nominations,
+                ScrapingModule::Transfer,
+            ])
+            .await;
+        assert!(res.is_err());
+        assert!(service.running.is_empty());
+        assert!(service.tasks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fetch_transfers_from_mock_server() {
+        init();
+
+        let server = wiremock::MockServer::start().await;
+        let db = memory_db().await;
+        let api = ChainApi::with_base_url(&server.uri())
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO));
+        let fetcher = TransferFetcher::new(db.clone(), Arc::new(api));
+        let alice = Context::alice();
+
+        // A full first page, followed by a partial second page. The first page
+        // is requested again on the second run.
+        mock_transfers_page(&server, 1, &transfers_page(0, ROW_AMOUNT), 2).await;
+        mock_transfers_page(&server, 2, &transfers_page(ROW_AMOUNT, 3), 1).await;
+
+        let stats = Stats::new();
+        run_cycle(&fetcher, std::slice::from_ref(&alice), &stats).await;
+        assert_eq!(stats.snapshot().inserted, ROW_AMOUNT as u64 + 3);
+
+        // All entries are already stored, so the first page stops the run.
+        let stats = Stats::new();
+        run_cycle(&fetcher, std::slice::from_ref(&alice), &stats).await;
+        assert_eq!(stats.snapshot().inserted, 0);
+
+        let count = db
+            .reader()
+            .count_transfers(
+                &[alice],
+                Timestamp::from(0),
+                Timestamp::from(i64::MAX as u64),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, ROW_AMOUNT as u64 + 3);
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn fetch_only_new_entries() {
+        init();
+
+        let server = wiremock::MockServer::start().await;
+        let db = memory_db().await;
+        let api = ChainApi::with_base_url(&server.uri())
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO));
+        let fetcher = TransferFetcher::new(db.clone(), Arc::new(api));
+        let alice = Context::alice();
+
+        // Entries are sorted by block number in descending order.
+        let mut page = transfers_page(0, ROW_AMOUNT);
+        for (idx, transfer) in page.data.transfers.as_mut().unwrap().iter_mut().enumerate() {
+            transfer.block_num = BlockNumber::from((ROW_AMOUNT - idx) as u64 * 100);
+        }
+
+        // A full first page, followed by an empty second page. Only the first
+        // page is requested again on the second run.
+        mock_transfers_page(&server, 1, &page, 2).await;
+        mock_transfers_page(&server, 2, &Default::default(), 1).await;
+
+        let stats = Stats::new();
+        run_cycle(&fetcher, std::slice::from_ref(&alice), &stats).await;
+        assert_eq!(stats.snapshot().inserted, ROW_AMOUNT as u64);
+
+        let latest = db
+            .latest_block(&alice, &ScrapingModule::Transfer)
+            .await
+            .unwrap();
+        assert_eq!(latest, Some(BlockNumber::from(ROW_AMOUNT as u64 * 100)));
+
+        // No new data, so the run stops at the watermark.
+        let stats = Stats::new();
+        run_cycle(&fetcher, &[alice], &stats).await;
+        assert_eq!(stats.snapshot().inserted, 0);
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn fetch_new_entries_of_watermark_block() {
+        let server = wiremock::MockServer::start().await;
+        let db = memory_db().await;
+        let api = ChainApi::with_base_url(&server.uri())
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO));
+        let fetcher = TransferFetcher::new(db.clone(), Arc::new(api));
+        let alice = Context::alice();
+
+        // Only one of the two entries of the highest block was stored.
+        let mut page = transfers_page(0, 2);
+        for transfer in page.data.transfers.as_mut().unwrap() {
+            transfer.block_num = BlockNumber::from(100);
+        }
+        let mut stored = page.clone();
+        stored.data.transfers.as_mut().unwrap().truncate(1);
+        fetcher.store_data(&alice, &stored).await.unwrap();
+
+        mock_transfers_page(&server, 1, &page, 1).await;
+
+        let stats = Stats::new();
+        run_cycle(&fetcher, &[alice], &stats).await;
+        assert_eq!(stats.snapshot().inserted, 1);
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn backfill_with_mock_source() {
+        let db = memory_db().await;
+        let alice = Context::alice();
+        let source = Arc::new(MockSource::default());
+        let fetcher = TransferFetcher::new(db, Arc::clone(&source) as Arc<dyn ChainBackend>);
+
+        // No pages are fetched.
+        let limit = BackfillLimit {
+            max_pages: Some(0),
+            ..Default::default()
+        };
+        let inserted = backfill_context(&fetcher, &alice, limit).await.unwrap();
+        assert_eq!(inserted, 0);
+        assert!(source.requested.lock().unwrap().is_empty());
+
+        // An empty first page ends the backfill.
+        let inserted = backfill_context(&fetcher, &alice, Default::default())
+            .await
+            .unwrap();
+        assert_eq!(inserted, 0);
+        assert_eq!(*source.requested.lock().unwrap(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn backfill_from_mock_server() {
+        init();
+
+        let server = wiremock::MockServer::start().await;
+        let db = memory_db().await;
+        let api = ChainApi::with_base_url(&server.uri())
+            .with_rate_limiter(RateLimiter::every(Duration::ZERO));
+        let service = ScrapingService::with_backend(db.clone(), Arc::new(api));
+        let alice = Context::alice();
+
+        mock_transfers_page(&server, 1, &transfers_page(0, BACKFILL_ROW_AMOUNT), 2).await;
+        mock_transfers_page(&server, 2, &transfers_page(BACKFILL_ROW_AMOUNT, 5), 1).await;
+
+        // Only the first page is fetched.
+        let limit = BackfillLimit {
+            max_pages: Some(1),
+            ..Default::default()
+        };
+        let inserted = service
+            .backfill(&alice, &ScrapingModule::Transfer, limit)
+            .await
+            .unwrap();
+        assert_eq!(inserted, BACKFILL_ROW_AMOUNT);
+
+        // Already known entries do not stop the backfill, only the last
+        // (partial) page does.
+        let inserted = service
+            .backfill(&alice, &ScrapingModule::Transfer, Default::default())
+            .await
+            .unwrap();
+        assert_eq!(inserted, 5);
+
+        server.verify().await;
+    }
+
     #[tokio::test]
     #[ignore]
     async fn live_run_transfer_fetcher() {
@@ -421,9 +2904,11 @@ mod tests {
             "11uMPbeaEDJhUxzU4ZfWW9VQEsryP9XqFcNRfPdYda6aFWJ",
         )];
 
-        let mut service = ScrapingService::new(db);
+        let mut service = ScrapingService::new(db.clone());
         service.add_contexts(contexts).await;
-        service.run_fetcher::<TransferFetcher>().await;
+        service
+            .run_fetcher::<TransferFetcher>(&ScrapingModule::Transfer)
+            .await;
         wait_blocking().await;
     }
 
@@ -440,16 +2925,18 @@ mod tests {
             "11uMPbeaEDJhUxzU4ZfWW9VQEsryP9XqFcNRfPdYda6aFWJ",
         )];
 
-        let mut service = ScrapingService::new(db);
+        let mut service = ScrapingService::new(db.clone());
         service.add_contexts(contexts).await;
-        service.run_fetcher::<RewardsSlashesFetcher>().await;
+        service
+            .run_fetcher::<RewardsSlashesFetcher>(&ScrapingModule::RewardsSlashes)
+            .await;
         wait_blocking().await;
     }
 
     #[tokio::test]
     #[ignore]
-    async fn live_google_drive_init() {
-        let _ = GoogleDrive::new("config/credentials.json").await.unwrap();
+    async fn live_google_storage_init() {
+        let _ = GoogleStorage::new("config/credentials.json").await.unwrap();
     }
 
     #[tokio::test]
@@ -459,9 +2946,7 @@ mod tests {
 
         info!("Running live test for transfer report generator");
 
-        let db = DatabaseReader::new("mongodb://localhost:27017/", "monitor")
-            .await
-            .unwrap();
+        let db = memory_db().await.reader();
         let contexts = vec![Context::from("")];
         let publisher = Arc::new(StdOut);
 
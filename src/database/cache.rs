@@ -0,0 +1,118 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// An in-process cache of query results, so identical queries in quick
+/// succession (e.g. by multiple report modules) are only sent to the
+/// database once. Entries expire after the TTL and the least recently used
+/// entry is evicted once the capacity is reached. Writes do not invalidate
+/// entries, so results may be up to one TTL old.
+pub(crate) struct QueryCache {
+    capacity: usize,
+    ttl: Duration,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, Entry>,
+    /// Incremented with each access, to find the least recently used entry.
+    clock: u64,
+}
+
+struct Entry {
+    value: Arc<dyn Any + Send + Sync>,
+    inserted: Instant,
+    used: u64,
+}
+
+impl QueryCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        QueryCache {
+            capacity,
+            ttl,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+    /// The cached result of the query, unless missing or expired.
+    pub fn get<T: Clone + 'static>(&self, key: &str) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+
+        if state.entries.get(key)?.inserted.elapsed() >= self.ttl {
+            state.entries.remove(key);
+            return None;
+        }
+
+        let entry = state.entries.get_mut(key)?;
+        entry.used = clock;
+        entry.value.downcast_ref::<T>().cloned()
+    }
+    pub fn insert<T: Send + Sync + 'static>(&self, key: String, value: T) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+
+        let ttl = self.ttl;
+        state
+            .entries
+            .retain(|_, entry| entry.inserted.elapsed() < ttl);
+        if state.entries.len() >= self.capacity && !state.entries.contains_key(&key) {
+            let lru = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                state.entries.remove(&lru);
+            }
+        }
+
+        state.entries.insert(
+            key,
+            Entry {
+                value: Arc::new(value),
+                inserted: Instant::now(),
+                used: clock,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evict_least_recently_used() {
+        let cache = QueryCache::new(2, Duration::from_secs(60));
+        cache.insert("a".to_string(), 1_u64);
+        cache.insert("b".to_string(), 2_u64);
+
+        // Makes `b` the least recently used entry.
+        assert_eq!(cache.get::<u64>("a"), Some(1));
+        cache.insert("c".to_string(), 3_u64);
+
+        assert_eq!(cache.get::<u64>("a"), Some(1));
+        assert_eq!(cache.get::<u64>("b"), None);
+        assert_eq!(cache.get::<u64>("c"), Some(3));
+        // Not of the cached type.
+        assert_eq!(cache.get::<String>("c"), None);
+    }
+
+    #[test]
+    fn expire_entries() {
+        let cache = QueryCache::new(2, Duration::from_millis(50));
+        cache.insert("a".to_string(), 1_u64);
+        assert_eq!(cache.get::<u64>("a"), Some(1));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(cache.get::<u64>("a"), None);
+    }
+}
@@ -0,0 +1,2757 @@
+use crate::chain_api::{
+    Nomination, NominationsPage, Response, RewardSlash, RewardsSlashesPage, StakingAction,
+    StakingActionsPage, Transfer, TransfersPage,
+};
+use crate::{BlockNumber, Context, ContextId, Labels, Network, Result, ScrapingModule, Timestamp};
+use bson::Bson;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::future::Future;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod cache;
+mod mongo;
+mod sqlite;
+
+use self::cache::QueryCache;
+
+pub use self::mongo::{MongoStore, ReadPreference, WriteAcknowledgment, WriteConcern};
+pub use self::sqlite::SqliteStore;
+
+// The names of the collections, or tables respectively.
+const COLL_TRANSFER_RAW: &str = "raw_transfers";
+const COLL_REWARD_SLASH_RAW: &str = "raw_rewards_slashes";
+const COLL_NOMINATIONS_RAW: &str = "raw_nominations";
+const COLL_NOMINATION_SNAPSHOTS: &str = "nomination_snapshots";
+const COLL_STAKING_ACTIONS_RAW: &str = "raw_staking_actions";
+const COLL_LEASES: &str = "leases";
+const COLL_CONTEXTS: &str = "contexts";
+const COLL_DEAD_LETTER: &str = "dead_letter";
+const COLL_RAW_RESPONSES: &str = "raw_responses";
+const COLL_NOTIFICATIONS: &str = "sent_notifications";
+const INDEX_CONTEXT_TIMESTAMP: &str = "context_id_timestamp";
+const INDEX_TIMESTAMP: &str = "timestamp";
+
+/// The fields of a stored transfer which may change once it is finalized, see
+/// `Database::with_transfer_updates`.
+const TRANSFER_MUTABLE_FIELDS: [&str; 2] = ["success", "fee"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ContextData<'a, T: Clone> {
+    pub context_id: ContextId<'a>,
+    pub timestamp: Timestamp,
+    pub data: Cow<'a, T>,
+    /// The labels of the account at the time the entry was stored.
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
+    /// The SHA-256 checksum of `data`, if enabled when the entry was stored.
+    /// See `Database::verify_integrity`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+impl<T: Clone> ContextData<'_, T> {
+    /// A copy of the entry which borrows its data instead of cloning it.
+    pub fn borrowed(&self) -> ContextData<'_, T> {
+        ContextData {
+            context_id: ContextId {
+                stash: Cow::Borrowed(self.context_id.stash.as_ref()),
+                network: self.context_id.network,
+            },
+            timestamp: self.timestamp,
+            data: Cow::Borrowed(self.data.as_ref()),
+            labels: self.labels.clone(),
+            checksum: self.checksum.clone(),
+        }
+    }
+}
+
+/// The hex encoded SHA-256 hash of the JSON serialization of `data`.
+fn checksum<T: Serialize>(data: &T) -> Result<String> {
+    Ok(hex::encode(Sha256::digest(&serde_json::to_vec(data)?)))
+}
+
+/// The result of `Database::verify_integrity`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntegrityReport {
+    /// The amount of entries with a checksum.
+    pub checked: usize,
+    /// The amount of entries stored without a checksum, which can not be
+    /// verified.
+    pub unchecked: usize,
+    /// The ID of each entry whose data does not match its checksum, i.e. the
+    /// `_id` of MongoDB documents or the row ID of SQLite entries.
+    pub mismatched: Vec<Bson>,
+}
+
+/// The validators an account nominated when the snapshot was stored. A new
+/// snapshot is only stored once the nominated validators change, see
+/// `Database::store_nomination_event`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NominationSnapshot {
+    pub validators: Vec<Nomination>,
+}
+
+impl NominationSnapshot {
+    /// The addresses of the nominated validators.
+    pub fn addresses(&self) -> HashSet<&str> {
+        self.validators
+            .iter()
+            .map(|v| v.stash_account_display.address.as_str())
+            .collect()
+    }
+}
+
+/// The outcome of storing a single entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stored {
+    Inserted,
+    /// The mutable fields of an already stored transfer changed, see
+    /// `Database::with_transfer_updates`.
+    Updated,
+    /// The entry was already stored and is kept as is.
+    Existing,
+}
+
+/// The outcome of storing a page of entries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StoredCount {
+    /// The entries which were not stored before.
+    pub inserted: usize,
+    /// The entries which failed to be stored and were given up on, see
+    /// `Database::with_dead_letter`.
+    pub skipped: usize,
+}
+
+impl StoredCount {
+    /// The entries which were not stored before, including the ones which
+    /// were given up on. Those need no further attempt either.
+    pub fn handled(&self) -> usize {
+        self.inserted + self.skipped
+    }
+}
+
+/// The rewards/slashes to fetch, either by block number or by the time of
+/// insertion. Both bounds are inclusive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RewardSlashFilter {
+    Blocks { from: BlockNumber, to: BlockNumber },
+    Inserted { from: Timestamp, to: Timestamp },
+}
+
+/// An entry returned by `DatabaseReader::fetch_recent`, of the queried module.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecentEntry {
+    Transfer(ContextData<'static, Transfer>),
+    RewardSlash(ContextData<'static, RewardSlash>),
+    Nomination(ContextData<'static, Nomination>),
+}
+
+impl RecentEntry {
+    pub fn context_id(&self) -> &ContextId<'static> {
+        match self {
+            RecentEntry::Transfer(entry) => &entry.context_id,
+            RecentEntry::RewardSlash(entry) => &entry.context_id,
+            RecentEntry::Nomination(entry) => &entry.context_id,
+        }
+    }
+    /// When the entry was stored.
+    pub fn timestamp(&self) -> Timestamp {
+        match self {
+            RecentEntry::Transfer(entry) => entry.timestamp,
+            RecentEntry::RewardSlash(entry) => entry.timestamp,
+            RecentEntry::Nomination(entry) => entry.timestamp,
+        }
+    }
+}
+
+/// An entry which repeatedly failed to be stored, see
+/// `Database::with_dead_letter`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadLetterEntry<'a> {
+    /// The collection the entry belongs to, e.g. `raw_transfers`.
+    pub collection: &'a str,
+    pub context_id: ContextId<'a>,
+    /// Identifies the entry within the collection and account.
+    pub key: &'a str,
+    pub timestamp: Timestamp,
+    pub attempts: u32,
+    pub error: String,
+    /// The JSON serialization of the entry, or its debug representation.
+    pub data: String,
+}
+
+/// The body of a response of the chain backend as received, stored for
+/// debugging, see `ChainApi::with_raw_responses`. Responses are identified by
+/// the URL and the body of their request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawResponse {
+    pub timestamp: Timestamp,
+    pub url: String,
+    /// The JSON body of the request.
+    pub request: String,
+    /// The gzipped body of the response, see `RawResponse::body`.
+    pub compressed: Vec<u8>,
+}
+
+impl RawResponse {
+    /// Compresses the given response body.
+    pub fn new(url: &str, request: &str, body: &[u8]) -> Result<Self> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body)?;
+
+        Ok(RawResponse {
+            timestamp: Timestamp::now(),
+            url: url.to_string(),
+            request: request.to_string(),
+            compressed: encoder.finish()?,
+        })
+    }
+    /// The decompressed body of the response.
+    pub fn body(&self) -> Result<Vec<u8>> {
+        let mut body = vec![];
+        GzDecoder::new(self.compressed.as_slice()).read_to_end(&mut body)?;
+        Ok(body)
+    }
+}
+
+/// A backend storing the collected data, e.g. MongoDB or SQLite. Stores only
+/// persist and query entries, everything else (checksums, retries) is handled
+/// by `Database` and `DatabaseReader`. Entries of the same account are
+/// identified by the same fields as in `Database::store_new_transfers` and
+/// the like, so storing an entry twice must not insert it again.
+#[async_trait]
+pub trait Store: Send + Sync {
+    /// Fails if the store can not be reached.
+    async fn check_connection(&self) -> Result<()>;
+    /// Creates the indexes used by time-bounded queries. Called on every
+    /// startup, so creating existing indexes must be a no-op.
+    async fn create_indexes(&self) -> Result<()>;
+    /// Stores the transfer unless already stored. If `update` is set, the
+    /// `TRANSFER_MUTABLE_FIELDS` (and the checksum) of a stored transfer are
+    /// updated instead.
+    async fn store_transfer(
+        &self,
+        entry: &ContextData<'_, Transfer>,
+        update: bool,
+    ) -> Result<Stored>;
+    async fn store_reward_slash(&self, entry: &ContextData<'_, RewardSlash>) -> Result<Stored>;
+    async fn store_nomination(&self, entry: &ContextData<'_, Nomination>) -> Result<Stored>;
+    async fn store_staking_action(&self, entry: &ContextData<'_, StakingAction>) -> Result<Stored>;
+    /// Whether the error is caused by the connection to the store rather
+    /// than by the entry, so storing the entry again may succeed. Such errors
+    /// do not count as failed attempts, see `Database::with_dead_letter`.
+    fn is_transient(&self, err: &anyhow::Error) -> bool;
+    /// Whether the entry was moved to the dead letter collection.
+    async fn is_dead_letter(&self, collection: &str, context: &Context, key: &str) -> Result<bool>;
+    /// Adds the entry to the dead letter collection, or replaces it.
+    async fn store_dead_letter(&self, entry: &DeadLetterEntry<'_>) -> Result<()>;
+    /// Whether the notification event was already sent to the destination.
+    async fn is_notified(&self, destination: &str, event_id: &str) -> Result<bool>;
+    /// Records that the notification event was sent to the destination.
+    async fn store_notified(&self, destination: &str, event_id: &str) -> Result<()>;
+    /// Flags the stored transfers or rewards/slashes of the account from the
+    /// given block onwards as orphaned, unless their key (the extrinsic index
+    /// of transfers, the event index of rewards/slashes) is `present`.
+    /// Present entries which are flagged are unflagged. Returns how many
+    /// entries were newly flagged.
+    async fn mark_orphaned(
+        &self,
+        module: &ScrapingModule,
+        context: &Context,
+        from: BlockNumber,
+        present: &[String],
+    ) -> Result<u64>;
+    /// See `Database::latest_block`.
+    async fn latest_block(
+        &self,
+        context: &Context,
+        module: &ScrapingModule,
+    ) -> Result<Option<BlockNumber>>;
+    /// See `Database::acquire_lease`.
+    async fn acquire_lease(&self, name: &str, holder: &str, ttl: u64) -> Result<bool>;
+    /// See `Database::load_contexts`.
+    async fn load_contexts(&self) -> Result<Vec<Context>>;
+    /// See `Database::upsert_context`.
+    async fn upsert_context(&self, context: &Context) -> Result<bool>;
+    /// See `Database::verify_integrity`.
+    async fn verify_integrity(&self, context: &Context) -> Result<IntegrityReport>;
+    /// The store used by the `DatabaseReader`, e.g. reading from replicas.
+    fn reader(&self) -> Arc<dyn Store>;
+    /// See `DatabaseReader::distinct_contexts`.
+    async fn distinct_contexts(
+        &self,
+        module: &ScrapingModule,
+    ) -> Result<HashSet<(String, Network)>>;
+    /// The non-orphaned transfers of the given accounts within the block
+    /// timestamps, both inclusive.
+    async fn fetch_transfers(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+        sort: TransferSort,
+        counterparty: Option<&str>,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'static, Transfer>>>;
+    /// Counts the entries of `fetch_transfers`.
+    async fn count_transfers(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+        counterparty: Option<&str>,
+    ) -> Result<u64>;
+    /// The non-orphaned rewards/slashes of the given accounts, the highest
+    /// block first.
+    async fn fetch_rewards_slashes(
+        &self,
+        contexts: &[Context],
+        filter: RewardSlashFilter,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'static, RewardSlash>>>;
+    /// Counts the entries of `fetch_rewards_slashes`.
+    async fn count_rewards_slashes(
+        &self,
+        contexts: &[Context],
+        filter: RewardSlashFilter,
+    ) -> Result<u64>;
+    /// The nominations of the given accounts, in the order of insertion.
+    async fn fetch_nominations(
+        &self,
+        contexts: &[Context],
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'static, Nomination>>>;
+    async fn count_nominations(&self, contexts: &[Context]) -> Result<u64>;
+    /// At most `limit` non-orphaned entries of all accounts stored after
+    /// `since`, the most recently stored first. Only supports transfers,
+    /// rewards/slashes and nominations.
+    async fn fetch_recent(
+        &self,
+        module: &ScrapingModule,
+        since: Timestamp,
+        limit: u64,
+    ) -> Result<Vec<RecentEntry>>;
+    /// Adds a snapshot of the nominations of an account. Snapshots are never
+    /// deduplicated by the store.
+    async fn store_nomination_snapshot(
+        &self,
+        entry: &ContextData<'_, NominationSnapshot>,
+    ) -> Result<()>;
+    /// The given amount of most recent nomination snapshots of the account,
+    /// the latest first.
+    async fn fetch_nomination_snapshots(
+        &self,
+        context: &Context,
+        limit: u64,
+    ) -> Result<Vec<ContextData<'static, NominationSnapshot>>>;
+    /// Adds the response and removes the oldest ones beyond the most recent
+    /// `max_entries`.
+    async fn store_raw_response(&self, response: &RawResponse, max_entries: u64) -> Result<()>;
+    /// The given amount of most recent responses of the URL, the latest
+    /// first. Only the responses to the given request body if set.
+    async fn fetch_raw_responses(
+        &self,
+        url: &str,
+        request: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<RawResponse>>;
+}
+
+#[derive(Clone)]
+pub struct Database {
+    store: Arc<dyn Store>,
+    checksums: bool,
+    transfer_updates: bool,
+    dead_letter: Option<DeadLetter>,
+    /// Shared by all readers, see `with_query_cache`.
+    query_cache: Option<Arc<QueryCache>>,
+}
+
+/// Counts the failed attempts to store each entry, see
+/// `Database::with_dead_letter`. The counts are shared between clones, but
+/// are not persisted.
+#[derive(Clone)]
+struct DeadLetter {
+    max_attempts: u32,
+    failures: Arc<Mutex<HashMap<String, u32>>>,
+}
+
+impl DeadLetter {
+    fn new(max_attempts: u32) -> Self {
+        DeadLetter {
+            max_attempts,
+            failures: Default::default(),
+        }
+    }
+    /// Records a failed attempt and returns the amount of failed attempts so
+    /// far.
+    fn failed(&self, id: &str) -> u32 {
+        let mut failures = self.failures.lock().unwrap();
+        let attempts = failures.entry(id.to_string()).or_insert(0);
+        *attempts += 1;
+        *attempts
+    }
+    /// Whether the entry should be given up on after the given amount of
+    /// failed attempts.
+    fn is_exhausted(&self, attempts: u32) -> bool {
+        attempts >= self.max_attempts
+    }
+    fn forget(&self, id: &str) {
+        self.failures.lock().unwrap().remove(id);
+    }
+}
+
+impl Database {
+    /// Stores the data in MongoDB, see `MongoStore::new`.
+    pub async fn new(uri: &str, db: &str) -> Result<Self> {
+        Ok(Database::with_store(MongoStore::new(uri, db).await?))
+    }
+    /// Stores the data in the given store, e.g. a `SqliteStore`.
+    pub fn with_store<S: 'static + Store>(store: S) -> Self {
+        Database {
+            store: Arc::new(store),
+            checksums: false,
+            transfer_updates: false,
+            dead_letter: None,
+            query_cache: None,
+        }
+    }
+    /// Stores a checksum with each new entry, so tampering or corruption can
+    /// be detected with `verify_integrity`. Existing entries are not changed.
+    pub fn with_checksums(mut self, enabled: bool) -> Self {
+        self.checksums = enabled;
+        self
+    }
+    /// Updates the `TRANSFER_MUTABLE_FIELDS` (and the checksum) of transfers
+    /// which are already stored, e.g. once their success status is final.
+    /// Stored transfers are never changed by default.
+    pub fn with_transfer_updates(mut self, enabled: bool) -> Self {
+        self.transfer_updates = enabled;
+        self
+    }
+    /// Gives up on entries which failed to be stored `max_attempts` times,
+    /// e.g. because they can not be represented by the store, and writes them
+    /// to the `dead_letter` collection with the error. Entries in that
+    /// collection are skipped from then on. Each failure skips the account
+    /// for the rest of the fetcher cycle, so the entry is retried in the next
+    /// one. Transient errors, e.g. a lost connection, are not counted as
+    /// failed attempts, see `Store::is_transient`. Failing entries are
+    /// retried indefinitely by default.
+    pub fn with_dead_letter(mut self, max_attempts: u32) -> Self {
+        self.dead_letter = Some(DeadLetter::new(max_attempts));
+        self
+    }
+    /// Caches the results of up to `capacity` queries of the readers for
+    /// `ttl`, so identical queries within the TTL are answered without
+    /// querying the database. Entries stored in the meantime are only
+    /// returned once the cached result expired, see
+    /// `DatabaseReader::uncached`. Not cached by default.
+    pub fn with_query_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.query_cache = Some(Arc::new(QueryCache::new(capacity, ttl)));
+        self
+    }
+    /// Handles the result of storing a single entry, identified by `key`
+    /// within the collection. Returns `None` if the entry failed to be stored
+    /// and was given up on, so the remaining entries can still be stored.
+    /// Those entries count as skipped in the `StoredCount` of the page.
+    async fn check_stored<T, R>(
+        &self,
+        coll: &str,
+        context: &Context,
+        key: &str,
+        data: &T,
+        res: Result<R>,
+    ) -> Result<Option<R>>
+    where
+        T: Serialize + Debug,
+    {
+        let dead_letter = match &self.dead_letter {
+            Some(dead_letter) => dead_letter,
+            None => return res.map(Some),
+        };
+
+        let id = format!("{}/{}/{}", coll, context.id(), key);
+        let err = match res {
+            Ok(res) => {
+                dead_letter.forget(&id);
+                return Ok(Some(res));
+            }
+            Err(err) => err,
+        };
+
+        if self.store.is_dead_letter(coll, context, key).await? {
+            trace!(
+                collection = coll,
+                key,
+                "Skipping entry of the dead letter collection"
+            );
+            return Ok(None);
+        }
+
+        if self.store.is_transient(&err) {
+            return Err(err);
+        }
+
+        let attempts = dead_letter.failed(&id);
+        if !dead_letter.is_exhausted(attempts) {
+            return Err(err);
+        }
+
+        warn!(
+            collection = coll,
+            key,
+            attempts,
+            error = ?err,
+            "Failed to store entry, moving it to the dead letter collection"
+        );
+
+        self.store
+            .store_dead_letter(&DeadLetterEntry {
+                collection: coll,
+                context_id: context.id(),
+                key,
+                timestamp: Timestamp::now(),
+                attempts,
+                error: format!("{:#}", err),
+                // The entry itself might be what can not be stored.
+                data: serde_json::to_string(data).unwrap_or_else(|_| format!("{:?}", data)),
+            })
+            .await?;
+
+        dead_letter.forget(&id);
+
+        Ok(None)
+    }
+    /// Adds the full context to an entry, so the corresponding account can be
+    /// identified.
+    fn entry<'a, T>(&self, context: &'a Context, data: &'a T) -> Result<ContextData<'a, T>>
+    where
+        T: Clone + Serialize,
+    {
+        Ok(ContextData {
+            context_id: context.id(),
+            timestamp: Timestamp::now(),
+            data: Cow::Borrowed(data),
+            labels: context.labels.clone(),
+            checksum: if self.checksums {
+                Some(checksum(data)?)
+            } else {
+                None
+            },
+        })
+    }
+    /// Recomputes the checksums of all stored entries of the account and
+    /// reports the ones which do not match.
+    pub async fn verify_integrity(&self, context: &Context) -> Result<IntegrityReport> {
+        self.store.verify_integrity(context).await
+    }
+    pub async fn check_connection(&self) -> Result<()> {
+        self.store.check_connection().await
+    }
+    /// Whether the notification event was already sent to the destination,
+    /// e.g. a Matrix room, see `mark_notified`.
+    pub async fn is_notified(&self, destination: &str, event_id: &str) -> Result<bool> {
+        self.store.is_notified(destination, event_id).await
+    }
+    /// Records that the notification event was sent to the destination, so
+    /// it is not sent again, even after a restart.
+    pub async fn mark_notified(&self, destination: &str, event_id: &str) -> Result<()> {
+        self.store.store_notified(destination, event_id).await
+    }
+    /// Creates the `(context_id, timestamp)` index on all raw collections, used
+    /// by time-bounded queries on the time of insertion. Creating an index
+    /// which already exists is a no-op, so this is called on every startup.
+    pub async fn create_indexes(&self) -> Result<()> {
+        self.store.create_indexes().await
+    }
+    pub async fn store_transfer_event(
+        &self,
+        context: &Context,
+        data: &Response<TransfersPage>,
+    ) -> Result<StoredCount> {
+        self.store_new_transfers(context, data)
+            .await
+            .map(|(inserted, skipped)| StoredCount {
+                inserted: inserted.len(),
+                skipped,
+            })
+    }
+    /// Like `store_transfer_event`, but returns the transfers which were newly
+    /// inserted instead of their count, along with the amount of skipped
+    /// entries.
+    pub async fn store_new_transfers<'a>(
+        &self,
+        context: &Context,
+        data: &'a Response<TransfersPage>,
+    ) -> Result<(Vec<&'a Transfer>, usize)> {
+        let transfers = data
+            .data
+            .transfers
+            .as_ref()
+            .ok_or(anyhow!("No transfers found in response body"))?;
+
+        // Add the full context to each transfer, so the corresponding account
+        // can be identified.
+        let extrinsics: Vec<ContextData<Transfer>> = transfers
+            .iter()
+            .map(|t| self.entry(context, t))
+            .collect::<Result<_>>()?;
+
+        // Insert new entries, identified by `Transfer::key`. Return the ones
+        // which were newly inserted.
+        let mut inserted = vec![];
+        let mut skipped = 0;
+        for (transfer, extrinsic) in transfers.iter().zip(&extrinsics) {
+            let res = self
+                .store
+                .store_transfer(extrinsic, self.transfer_updates)
+                .await;
+
+            let key = transfer.key();
+            let res = match self
+                .check_stored(COLL_TRANSFER_RAW, context, &key, transfer, res)
+                .await?
+            {
+                Some(res) => res,
+                None => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+
+            match res {
+                Stored::Updated => {
+                    debug!("Updated stored transfer for {:?}: {:?}", context, extrinsic);
+                }
+                Stored::Inserted => {
+                    trace!(
+                        "Added new transfer to database for {:?}: {:?}",
+                        context,
+                        extrinsic
+                    );
+                    inserted.push(transfer);
+                }
+                Stored::Existing => {}
+            }
+        }
+
+        Ok((inserted, skipped))
+    }
+    pub async fn store_reward_slash_event(
+        &self,
+        context: &Context,
+        data: &Response<RewardsSlashesPage>,
+    ) -> Result<StoredCount> {
+        // Add the full context to each entry, so the corresponding account
+        // can be identified.
+        let reward_slashes: Vec<ContextData<RewardSlash>> = data
+            .data
+            .list
+            .as_ref()
+            .ok_or(anyhow!("No rewards/slashes found in response body"))?
+            .iter()
+            .map(|rs| self.entry(context, rs))
+            .collect::<Result<_>>()?;
+
+        // Insert new entries. Return count of how many were newly inserted.
+        let mut count = StoredCount::default();
+        for reward_slash in &reward_slashes {
+            let res = self.store.store_reward_slash(reward_slash).await;
+
+            let key = reward_slash.data.event_index.clone();
+            let res = match self
+                .check_stored(
+                    COLL_REWARD_SLASH_RAW,
+                    context,
+                    &key,
+                    &reward_slash.data,
+                    res,
+                )
+                .await?
+            {
+                Some(res) => res,
+                None => {
+                    count.skipped += 1;
+                    continue;
+                }
+            };
+
+            if res == Stored::Inserted {
+                trace!(
+                    "Added new rewards_slash to database for {:?}: {:?}",
+                    context,
+                    reward_slash
+                );
+                count.inserted += 1;
+            }
+        }
+
+        Ok(count)
+    }
+    pub async fn store_nomination_event(
+        &self,
+        context: &Context,
+        data: &Response<NominationsPage>,
+    ) -> Result<StoredCount> {
+        // Subscan omits the list once an account stopped nominating, which
+        // is stored as an empty snapshot, so the removal is reported.
+        let list: &[Nomination] = data.data.list.as_deref().unwrap_or_default();
+
+        // Add the full context to each entry, so the corresponding account
+        // can be identified.
+        let validators: Vec<ContextData<Nomination>> = list
+            .iter()
+            .map(|v| self.entry(context, v))
+            .collect::<Result<_>>()?;
+
+        // Insert new entries. Return count of how many were newly inserted.
+        let mut count = StoredCount::default();
+        for validator in &validators {
+            let res = self.store.store_nomination(validator).await;
+
+            let key = validator.data.stash_account_display.address.clone();
+            let res = match self
+                .check_stored(COLL_NOMINATIONS_RAW, context, &key, &validator.data, res)
+                .await?
+            {
+                Some(res) => res,
+                None => {
+                    count.skipped += 1;
+                    continue;
+                }
+            };
+
+            if res == Stored::Inserted {
+                trace!(
+                    "Added new rewards_slash to database for {:?}: {:?}",
+                    context,
+                    validator
+                );
+                count.inserted += 1;
+            }
+        }
+
+        // Only changes are stored, so the latest two snapshots tell which
+        // validators were added or removed most recently.
+        let snapshot = NominationSnapshot {
+            validators: list.to_vec(),
+        };
+        let latest = self.store.fetch_nomination_snapshots(context, 1).await?;
+        if latest
+            .first()
+            .is_none_or(|latest| latest.data.addresses() != snapshot.addresses())
+        {
+            self.store
+                .store_nomination_snapshot(&self.entry(context, &snapshot)?)
+                .await?;
+            debug!(
+                "Stored a new snapshot of {} nominations for {:?}",
+                snapshot.validators.len(),
+                context
+            );
+        }
+
+        Ok(count)
+    }
+    pub async fn store_staking_action(
+        &self,
+        context: &Context,
+        data: &Response<StakingActionsPage>,
+    ) -> Result<StoredCount> {
+        // Add the full context to each entry, so the corresponding account
+        // can be identified.
+        let actions: Vec<ContextData<StakingAction>> = data
+            .data
+            .list
+            .as_ref()
+            .ok_or(anyhow!("No staking actions found in response body"))?
+            .iter()
+            .map(|a| self.entry(context, a))
+            .collect::<Result<_>>()?;
+
+        // Insert new entries. Return count of how many were newly inserted.
+        let mut count = StoredCount::default();
+        for action in &actions {
+            let res = self.store.store_staking_action(action).await;
+
+            let key = action.data.extrinsic_hash.to_string();
+            let res = match self
+                .check_stored(COLL_STAKING_ACTIONS_RAW, context, &key, &action.data, res)
+                .await?
+            {
+                Some(res) => res,
+                None => {
+                    count.skipped += 1;
+                    continue;
+                }
+            };
+
+            if res == Stored::Inserted {
+                trace!(
+                    "Added new staking action to database for {:?}: {:?}",
+                    context,
+                    action
+                );
+                count.inserted += 1;
+            }
+        }
+
+        Ok(count)
+    }
+    /// Flags the stored transfers of the account from the given block onwards
+    /// whose extrinsic is missing in the freshly fetched `data` as orphaned,
+    /// e.g. because of a chain reorg. Entries which reappear are unflagged.
+    /// Returns how many entries were newly flagged.
+    pub async fn mark_orphaned_transfers(
+        &self,
+        context: &Context,
+        from: BlockNumber,
+        data: &[Response<TransfersPage>],
+    ) -> Result<u64> {
+        let present: Vec<String> = data
+            .iter()
+            .flat_map(|r| r.data.transfers.iter().flatten())
+            .map(|transfer| transfer.extrinsic_index.to_string())
+            .collect();
+
+        self.store
+            .mark_orphaned(&ScrapingModule::Transfer, context, from, &present)
+            .await
+    }
+    /// Like `mark_orphaned_transfers`, for rewards/slashes.
+    pub async fn mark_orphaned_rewards_slashes(
+        &self,
+        context: &Context,
+        from: BlockNumber,
+        data: &[Response<RewardsSlashesPage>],
+    ) -> Result<u64> {
+        let present: Vec<String> = data
+            .iter()
+            .flat_map(|r| r.data.list.iter().flatten())
+            .map(|reward_slash| reward_slash.event_index.clone())
+            .collect();
+
+        self.store
+            .mark_orphaned(&ScrapingModule::RewardsSlashes, context, from, &present)
+            .await
+    }
+    /// Returns the highest block number of the stored (non-orphaned) entries
+    /// of the account for the given module, if any. Nominations do not carry
+    /// a block number, so `None` is always returned for those.
+    pub async fn latest_block(
+        &self,
+        context: &Context,
+        module: &ScrapingModule,
+    ) -> Result<Option<BlockNumber>> {
+        self.store.latest_block(context, module).await
+    }
+    /// Acquires the lease of the given name for `holder`, or renews it if
+    /// `holder` already owns it, for `ttl` seconds. Returns `false` if another
+    /// holder owns a lease which has not expired yet.
+    pub async fn acquire_lease(&self, name: &str, holder: &str, ttl: u64) -> Result<bool> {
+        self.store.acquire_lease(name, holder, ttl).await
+    }
+    /// Loads the accounts to monitor from the `contexts` collection, ordered
+    /// by network and stash.
+    pub async fn load_contexts(&self) -> Result<Vec<Context>> {
+        self.store.load_contexts().await
+    }
+    /// Adds the account to the `contexts` collection, or replaces its
+    /// description and labels if it already exists. Returns whether it was
+    /// newly added.
+    pub async fn upsert_context(&self, context: &Context) -> Result<bool> {
+        self.store.upsert_context(context).await
+    }
+    /// Adds the response to the `raw_responses` collection, keeping only the
+    /// most recent `max_entries` responses.
+    pub async fn store_raw_response(&self, response: &RawResponse, max_entries: u64) -> Result<()> {
+        self.store.store_raw_response(response, max_entries).await
+    }
+    pub fn reader(&self) -> DatabaseReader {
+        DatabaseReader {
+            store: self.store.reader(),
+            cache: self.query_cache.clone(),
+        }
+    }
+}
+
+/// Restricts the entries returned by the paginated `DatabaseReader` methods,
+/// so large result sets can be processed in chunks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pagination {
+    pub skip: u64,
+    pub limit: u64,
+}
+
+/// The order of fetched transfers. Defaults to the most recent block first.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct TransferSort {
+    pub key: TransferSortKey,
+    pub direction: SortDirection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferSortKey {
+    #[default]
+    BlockNum,
+    Timestamp,
+    Amount,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    Ascending,
+    #[default]
+    Descending,
+}
+
+#[derive(Clone)]
+// TODO: Rename
+pub struct DatabaseReader {
+    store: Arc<dyn Store>,
+    /// See `Database::with_query_cache`.
+    cache: Option<Arc<QueryCache>>,
+}
+
+impl DatabaseReader {
+    #[cfg(test)]
+    pub async fn new(uri: &str, db: &str) -> Result<Self> {
+        Ok(DatabaseReader {
+            store: Arc::new(MongoStore::new(uri, db).await?),
+            cache: None,
+        })
+    }
+    /// A reader which always queries the database, bypassing the cache of
+    /// this one, e.g. for reports which must include the latest entries.
+    pub fn uncached(&self) -> Self {
+        DatabaseReader {
+            store: Arc::clone(&self.store),
+            cache: None,
+        }
+    }
+    /// Returns the cached result of the query identified by `key`, or
+    /// executes `query` and caches its result.
+    async fn cached<T, F>(&self, key: String, query: F) -> Result<T>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: Future<Output = Result<T>>,
+    {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return query.await,
+        };
+
+        if let Some(result) = cache.get::<T>(&key) {
+            trace!(key = %key, "Returning cached query result");
+            return Ok(result);
+        }
+
+        let result = query.await?;
+        cache.insert(key, result.clone());
+        Ok(result)
+    }
+    /// The accounts with any stored entries of the given module, as
+    /// `(stash, network)` pairs. Allows to find monitored accounts without
+    /// data, or data of accounts which are no longer monitored.
+    pub async fn distinct_contexts(
+        &self,
+        module: &ScrapingModule,
+    ) -> Result<HashSet<(String, Network)>> {
+        self.cached(
+            format!("distinct_contexts:{:?}", module),
+            self.store.distinct_contexts(module),
+        )
+        .await
+    }
+    /// Fetches the transfers of the given accounts. If a counterparty is
+    /// specified, only transfers from or to that address are returned.
+    pub async fn fetch_transfers<'a>(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+        counterparty: Option<&str>,
+    ) -> Result<Vec<ContextData<'a, Transfer>>> {
+        self.fetch_transfers_with(
+            contexts,
+            from,
+            to,
+            TransferSort::default(),
+            counterparty,
+            None,
+        )
+        .await
+    }
+    /// Fetches the transfers of a single account, without requiring a full
+    /// `Context`.
+    pub async fn fetch_transfers_for_stash<'a>(
+        &self,
+        stash: &str,
+        network: Network,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<Vec<ContextData<'a, Transfer>>> {
+        self.fetch_transfers(&[stash_context(stash, network)], from, to, None)
+            .await
+    }
+    pub async fn fetch_transfers_paginated<'a>(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'a, Transfer>>> {
+        self.fetch_transfers_with(
+            contexts,
+            from,
+            to,
+            TransferSort::default(),
+            None,
+            pagination,
+        )
+        .await
+    }
+    pub async fn fetch_transfers_sorted<'a>(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+        sort: TransferSort,
+        counterparty: Option<&str>,
+    ) -> Result<Vec<ContextData<'a, Transfer>>> {
+        self.fetch_transfers_with(contexts, from, to, sort, counterparty, None)
+            .await
+    }
+    async fn fetch_transfers_with(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+        sort: TransferSort,
+        counterparty: Option<&str>,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'static, Transfer>>> {
+        self.cached(
+            query_key(
+                "fetch_transfers",
+                contexts,
+                (from, to, sort, counterparty, pagination),
+            ),
+            self.store
+                .fetch_transfers(contexts, from, to, sort, counterparty, pagination),
+        )
+        .await
+    }
+    pub async fn count_transfers(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+        counterparty: Option<&str>,
+    ) -> Result<u64> {
+        self.cached(
+            query_key("count_transfers", contexts, (from, to, counterparty)),
+            self.store.count_transfers(contexts, from, to, counterparty),
+        )
+        .await
+    }
+    pub async fn fetch_rewards_slashes<'a>(
+        &self,
+        contexts: &[Context],
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> Result<Vec<ContextData<'a, RewardSlash>>> {
+        self.fetch_rewards_slashes_paginated(contexts, from, to, None)
+            .await
+    }
+    /// Fetches the rewards/slashes of a single account, without requiring a
+    /// full `Context`.
+    pub async fn fetch_rewards_slashes_for_stash<'a>(
+        &self,
+        stash: &str,
+        network: Network,
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> Result<Vec<ContextData<'a, RewardSlash>>> {
+        self.fetch_rewards_slashes(&[stash_context(stash, network)], from, to)
+            .await
+    }
+    pub async fn fetch_rewards_slashes_paginated<'a>(
+        &self,
+        contexts: &[Context],
+        from: BlockNumber,
+        to: BlockNumber,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'a, RewardSlash>>> {
+        self.fetch_rewards_slashes_with(
+            contexts,
+            RewardSlashFilter::Blocks { from, to },
+            pagination,
+        )
+        .await
+    }
+    /// Fetches the rewards/slashes which were stored within the given time
+    /// range. Unlike transfers, rewards/slashes do not carry a block
+    /// timestamp, so the time of insertion is used instead.
+    pub async fn fetch_rewards_slashes_by_timestamp<'a>(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<Vec<ContextData<'a, RewardSlash>>> {
+        self.fetch_rewards_slashes_with(contexts, RewardSlashFilter::Inserted { from, to }, None)
+            .await
+    }
+    async fn fetch_rewards_slashes_with(
+        &self,
+        contexts: &[Context],
+        filter: RewardSlashFilter,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'static, RewardSlash>>> {
+        self.cached(
+            query_key("fetch_rewards_slashes", contexts, (filter, pagination)),
+            self.store
+                .fetch_rewards_slashes(contexts, filter, pagination),
+        )
+        .await
+    }
+    pub async fn count_rewards_slashes(
+        &self,
+        contexts: &[Context],
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> Result<u64> {
+        self.count_rewards_slashes_with(contexts, RewardSlashFilter::Blocks { from, to })
+            .await
+    }
+    pub async fn count_rewards_slashes_by_timestamp(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<u64> {
+        self.count_rewards_slashes_with(contexts, RewardSlashFilter::Inserted { from, to })
+            .await
+    }
+    async fn count_rewards_slashes_with(
+        &self,
+        contexts: &[Context],
+        filter: RewardSlashFilter,
+    ) -> Result<u64> {
+        self.cached(
+            query_key("count_rewards_slashes", contexts, filter),
+            self.store.count_rewards_slashes(contexts, filter),
+        )
+        .await
+    }
+    pub async fn fetch_nominations<'a>(
+        &self,
+        contexts: &[Context],
+    ) -> Result<Vec<ContextData<'a, Nomination>>> {
+        self.fetch_nominations_paginated(contexts, None).await
+    }
+    /// Fetches the nominations of a single account, without requiring a full
+    /// `Context`.
+    pub async fn fetch_nominations_for_stash<'a>(
+        &self,
+        stash: &str,
+        network: Network,
+    ) -> Result<Vec<ContextData<'a, Nomination>>> {
+        self.fetch_nominations(&[stash_context(stash, network)])
+            .await
+    }
+    pub async fn fetch_nominations_paginated<'a>(
+        &self,
+        contexts: &[Context],
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'a, Nomination>>> {
+        self.cached(
+            query_key("fetch_nominations", contexts, pagination),
+            self.store.fetch_nominations(contexts, pagination),
+        )
+        .await
+    }
+    pub async fn count_nominations(&self, contexts: &[Context]) -> Result<u64> {
+        self.cached(
+            query_key("count_nominations", contexts, ()),
+            self.store.count_nominations(contexts),
+        )
+        .await
+    }
+    /// The given amount of entries of the module of all accounts which were
+    /// stored after `since`, e.g. to show recent activity, the most recently
+    /// stored first. Orphaned entries are excluded. Never cached, so new
+    /// entries show up immediately.
+    pub async fn fetch_recent(
+        &self,
+        module: &ScrapingModule,
+        since: Timestamp,
+        limit: u64,
+    ) -> Result<Vec<RecentEntry>> {
+        self.store.fetch_recent(module, since, limit).await
+    }
+    /// The given amount of most recent nomination snapshots of the account,
+    /// the latest first.
+    pub async fn fetch_nomination_snapshots<'a>(
+        &self,
+        context: &Context,
+        limit: u64,
+    ) -> Result<Vec<ContextData<'a, NominationSnapshot>>> {
+        self.cached(
+            query_key(
+                "fetch_nomination_snapshots",
+                std::slice::from_ref(context),
+                limit,
+            ),
+            self.store.fetch_nomination_snapshots(context, limit),
+        )
+        .await
+    }
+    /// The given amount of most recent raw responses of the URL, the latest
+    /// first, optionally only those to the given request body. Never cached.
+    pub async fn fetch_raw_responses(
+        &self,
+        url: &str,
+        request: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<RawResponse>> {
+        self.store.fetch_raw_responses(url, request, limit).await
+    }
+}
+
+/// Identifies a query of the given accounts for `QueryCache`. Accounts are
+/// identified by their network and stash, like in the stored entries.
+fn query_key<P: Debug>(query: &str, contexts: &[Context], params: P) -> String {
+    let ids: Vec<String> = contexts.iter().map(|c| c.id().to_string()).collect();
+    format!("{}:{}:{:?}", query, ids.join(","), params)
+}
+
+/// Creates a context matching the `ContextId` of the given account. Only the
+/// stash and network are used for lookups, the description stays empty.
+fn stash_context(stash: &str, network: Network) -> Context {
+    Context {
+        stash: stash.to_string(),
+        network,
+        description: String::new(),
+        labels: Default::default(),
+    }
+}
+
+/// The collection of the raw entries of a module.
+fn module_collection(module: &ScrapingModule) -> &'static str {
+    match module {
+        ScrapingModule::Transfer => COLL_TRANSFER_RAW,
+        ScrapingModule::RewardsSlashes => COLL_REWARD_SLASH_RAW,
+        ScrapingModule::Nominations => COLL_NOMINATIONS_RAW,
+        ScrapingModule::StakingActions => COLL_STAKING_ACTIONS_RAW,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_api::{Response, TransfersPage};
+    use crate::tests::db;
+    use crate::Context;
+    use tokio::time::sleep;
+
+    #[tokio::test]
+    async fn checksum_of_entries() {
+        // No connection is established until a query is executed.
+        let db = Database::new("mongodb://localhost:27017/", "monitor")
+            .await
+            .unwrap();
+        let alice = Context::alice();
+        let transfer = Transfer {
+            amount: "10000000000".into(),
+            extrinsic_index: "1-1".to_string().into(),
+            ..Default::default()
+        };
+
+        assert!(db.entry(&alice, &transfer).unwrap().checksum.is_none());
+
+        let db = db.with_checksums(true);
+        let entry = db.entry(&alice, &transfer).unwrap();
+        let expected = entry.checksum.clone().unwrap();
+        assert_eq!(expected.len(), 64);
+
+        // The checksum survives the round trip through BSON.
+        let stored = bson::to_bson(&entry).unwrap();
+        let stored = stored.as_document().unwrap();
+        assert_eq!(stored.get_str("checksum").unwrap(), expected);
+        let data: Transfer =
+            bson::from_document(stored.get_document("data").unwrap().clone()).unwrap();
+        assert_eq!(checksum(&data).unwrap(), expected);
+
+        let tampered = Transfer {
+            amount: "20000000000".into(),
+            ..data
+        };
+        assert_ne!(checksum(&tampered).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn update_stored_transfer() {
+        let db = db().await;
+        let alice = Context::alice();
+
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Transfer {
+            amount: "10000000000".into(),
+            extrinsic_index: "1-1".to_string().into(),
+            success: false,
+            ..Default::default()
+        }]);
+        assert_eq!(
+            db.store_transfer_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            1
+        );
+
+        let mut finalized = resp.clone();
+        finalized.data.transfers.as_mut().unwrap()[0].success = true;
+        let (from, to) = (Timestamp::from(0), Timestamp::from(i64::MAX as u64));
+
+        // Stored transfers are not changed by default.
+        assert_eq!(
+            db.store_transfer_event(&alice, &finalized)
+                .await
+                .unwrap()
+                .inserted,
+            0
+        );
+        let stored = db
+            .reader()
+            .fetch_transfers(std::slice::from_ref(&alice), from, to, None)
+            .await
+            .unwrap();
+        assert!(!stored[0].data.success);
+
+        let updating = db.clone().with_transfer_updates(true);
+        // Updated transfers do not count as new ones.
+        assert_eq!(
+            updating
+                .store_transfer_event(&alice, &finalized)
+                .await
+                .unwrap()
+                .inserted,
+            0
+        );
+        let stored = db
+            .reader()
+            .fetch_transfers(std::slice::from_ref(&alice), from, to, None)
+            .await
+            .unwrap();
+        assert_eq!(stored.len(), 1);
+        assert!(stored[0].data.success);
+    }
+
+    #[tokio::test]
+    async fn cache_query_results() {
+        let db = db().await;
+        let alice = Context::alice();
+        let contexts = std::slice::from_ref(&alice);
+        let (from, to) = (Timestamp::from(0), Timestamp::from(i64::MAX as u64));
+
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Transfer {
+            extrinsic_index: "1-1".to_string().into(),
+            ..Default::default()
+        }]);
+        assert_eq!(
+            db.store_transfer_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            1
+        );
+
+        let cached = db.clone().with_query_cache(10, Duration::from_secs(60));
+        let expiring = db.clone().with_query_cache(10, Duration::from_millis(100));
+        for db in [&cached, &expiring] {
+            let reader = db.reader();
+            assert_eq!(
+                reader
+                    .fetch_transfers(contexts, from, to, None)
+                    .await
+                    .unwrap()
+                    .len(),
+                1
+            );
+            assert_eq!(
+                reader
+                    .count_transfers(contexts, from, to, None)
+                    .await
+                    .unwrap(),
+                1
+            );
+        }
+
+        // Stored after the results were cached.
+        resp.data.transfers.as_mut().unwrap()[0].extrinsic_index = "2-1".to_string().into();
+        assert_eq!(
+            db.store_transfer_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            1
+        );
+
+        // Answered from the cache, which is shared by all readers of the
+        // database, without querying the database again.
+        let reader = cached.reader();
+        assert_eq!(
+            reader
+                .fetch_transfers(contexts, from, to, None)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            reader
+                .count_transfers(contexts, from, to, None)
+                .await
+                .unwrap(),
+            1
+        );
+
+        // Unless bypassed.
+        assert_eq!(
+            reader
+                .uncached()
+                .fetch_transfers(contexts, from, to, None)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // Or expired.
+        sleep(Duration::from_millis(150)).await;
+        assert_eq!(
+            expiring
+                .reader()
+                .fetch_transfers(contexts, from, to, None)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn count_failed_store_attempts() {
+        let dead_letter = DeadLetter::new(2);
+
+        assert_eq!(dead_letter.failed("a"), 1);
+        assert!(!dead_letter.is_exhausted(1));
+        assert_eq!(dead_letter.clone().failed("a"), 2);
+        assert!(dead_letter.is_exhausted(2));
+        assert_eq!(dead_letter.failed("b"), 1);
+
+        dead_letter.forget("a");
+        assert_eq!(dead_letter.failed("a"), 1);
+    }
+
+    #[tokio::test]
+    async fn list_distinct_contexts() {
+        let db = db().await;
+        let reader = db.reader();
+
+        let alice = Context::alice();
+        let bob = Context::bob();
+        let kusama = Context {
+            network: Network::Kusama,
+            ..Context::alice()
+        };
+
+        let mut transfers: Response<TransfersPage> = Default::default();
+        transfers.data.transfers = Some(vec![Default::default(); 2]);
+        transfers
+            .data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| t.extrinsic_index = idx.to_string().into());
+
+        let mut nominations: Response<NominationsPage> = Default::default();
+        nominations.data.list = Some(vec![Default::default()]);
+
+        let mut rewards: Response<RewardsSlashesPage> = Default::default();
+        rewards.data.list = Some(vec![Default::default()]);
+
+        // Several entries of the same account are listed once.
+        for context in [&alice, &kusama] {
+            db.store_transfer_event(context, &transfers).await.unwrap();
+        }
+        db.store_nomination_event(&bob, &nominations).await.unwrap();
+        db.store_reward_slash_event(&kusama, &rewards)
+            .await
+            .unwrap();
+
+        let id = |context: &Context| (context.stash.clone(), context.network);
+
+        assert_eq!(
+            reader
+                .distinct_contexts(&ScrapingModule::Transfer)
+                .await
+                .unwrap(),
+            vec![id(&alice), id(&kusama)].into_iter().collect()
+        );
+        assert_eq!(
+            reader
+                .distinct_contexts(&ScrapingModule::Nominations)
+                .await
+                .unwrap(),
+            vec![id(&bob)].into_iter().collect()
+        );
+        assert_eq!(
+            reader
+                .distinct_contexts(&ScrapingModule::RewardsSlashes)
+                .await
+                .unwrap(),
+            vec![id(&kusama)].into_iter().collect()
+        );
+        assert!(reader
+            .distinct_contexts(&ScrapingModule::StakingActions)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn isolate_networks() {
+        let db = db().await;
+        let report = db.reader();
+
+        // The same address on both networks.
+        let polkadot = Context::alice();
+        let kusama = Context {
+            network: Network::Kusama,
+            ..Context::alice()
+        };
+
+        let mut transfers: Response<TransfersPage> = Default::default();
+        transfers.data.transfers = Some(vec![Default::default(); 2]);
+        transfers
+            .data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| t.extrinsic_index = idx.to_string().into());
+
+        let mut nominations: Response<NominationsPage> = Default::default();
+        nominations.data.list = Some(vec![Default::default(); 3]);
+        nominations
+            .data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, n)| n.stash_account_display.address = idx.to_string());
+
+        // Identical entries are stored for each network.
+        for context in [&polkadot, &kusama] {
+            assert_eq!(
+                db.store_transfer_event(context, &transfers)
+                    .await
+                    .unwrap()
+                    .inserted,
+                2
+            );
+            assert_eq!(
+                db.store_nomination_event(context, &nominations)
+                    .await
+                    .unwrap()
+                    .inserted,
+                3
+            );
+        }
+
+        let (from, to) = (Timestamp::from(0), Timestamp::from(i64::MAX as u64));
+        for context in [&polkadot, &kusama] {
+            let contexts = std::slice::from_ref(context);
+
+            let res = report
+                .fetch_transfers(contexts, from, to, None)
+                .await
+                .unwrap();
+            assert_eq!(res.len(), 2);
+            assert!(res.iter().all(|t| t.context_id == context.id()));
+
+            let res = report.fetch_nominations(contexts).await.unwrap();
+            assert_eq!(res.len(), 3);
+            assert!(res.iter().all(|n| n.context_id == context.id()));
+            assert_eq!(report.count_nominations(contexts).await.unwrap(), 3);
+        }
+
+        let res = report
+            .fetch_nominations_for_stash(&kusama.stash, Network::Kusama)
+            .await
+            .unwrap();
+        assert!(res.iter().all(|n| n.context_id.network == Network::Kusama));
+    }
+
+    #[tokio::test]
+    async fn upsert_and_load_contexts() {
+        let db = db().await;
+
+        assert!(db.load_contexts().await.unwrap().is_empty());
+
+        let alice = Context::alice();
+        let mut bob = Context::bob();
+        assert!(db.upsert_context(&bob).await.unwrap());
+        assert!(db.upsert_context(&alice).await.unwrap());
+
+        // Updates the existing entry.
+        bob.description = "Bob's account".to_string();
+        bob.labels
+            .insert("team".to_string(), "treasury".to_string());
+        assert!(!db.upsert_context(&bob).await.unwrap());
+
+        let contexts = db.load_contexts().await.unwrap();
+        assert_eq!(contexts, vec![alice, bob]);
+    }
+
+    #[tokio::test]
+    async fn store_transfer_event() {
+        let db = db().await;
+
+        // Must now have an influence on data.
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        // Gen test data
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Default::default(); 10]);
+        resp.data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| t.extrinsic_index = idx.to_string().into());
+
+        // New data is inserted
+        let count = db
+            .store_transfer_event(&alice, &resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 10);
+
+        // No new data is inserted
+        let count = db
+            .store_transfer_event(&alice, &resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 0);
+
+        // Gen new test data
+        let mut new_resp: Response<TransfersPage> = Default::default();
+        new_resp.data.transfers = Some(vec![Default::default(); 15]);
+        new_resp
+            .data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| t.extrinsic_index = (idx + 10).to_string().into());
+
+        // New data is inserted
+        let count = db
+            .store_transfer_event(&bob, &new_resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 15);
+
+        // No new data is inserted
+        let count = db
+            .store_transfer_event(&bob, &new_resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 0);
+
+        // Insert previous data (under a new context)
+        let count = db.store_transfer_event(&bob, &resp).await.unwrap().inserted;
+        assert_eq!(count, 10);
+    }
+
+    #[tokio::test]
+    async fn store_batch_transfer_event() {
+        let db = db().await;
+
+        let alice = Context::alice();
+
+        // Two identical transfers of the same batch call share the extrinsic
+        // index and hash, only their events differ.
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Default::default(); 2]);
+        resp.data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| {
+                t.extrinsic_index = "100-1".to_string().into();
+                t.hash = "0x00".to_string();
+                t.from = alice.stash.clone();
+                t.to = "bob".to_string();
+                t.event_idx = Some(idx as i64 + 2);
+            });
+
+        // Both transfers are inserted
+        let count = db
+            .store_transfer_event(&alice, &resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 2);
+
+        // No new data is inserted
+        let count = db
+            .store_transfer_event(&alice, &resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 0);
+
+        // Transfers stored without an event index are not inserted again
+        // once it is known.
+        let mut legacy: Response<TransfersPage> = Default::default();
+        legacy.data.transfers = Some(vec![Transfer {
+            extrinsic_index: "200-1".to_string().into(),
+            ..Default::default()
+        }]);
+        let count = db
+            .store_transfer_event(&alice, &legacy)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 1);
+
+        legacy.data.transfers.as_mut().unwrap()[0].event_idx = Some(5);
+        let count = db
+            .store_transfer_event(&alice, &legacy)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn store_reward_slash_event() {
+        let db = db().await;
+
+        // Must now have an influence on data.
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        // Gen test data
+        let mut resp: Response<RewardsSlashesPage> = Default::default();
+        resp.data.list = Some(vec![Default::default(); 10]);
+        resp.data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, e)| e.event_index = idx.to_string());
+
+        // New data is inserted
+        let count = db
+            .store_reward_slash_event(&alice, &resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 10);
+
+        // No new data is inserted
+        let count = db
+            .store_reward_slash_event(&alice, &resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 0);
+
+        // Gen new test data
+        let mut new_resp: Response<RewardsSlashesPage> = Default::default();
+        new_resp.data.list = Some(vec![Default::default(); 15]);
+        new_resp
+            .data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, e)| e.event_index = (idx + 10).to_string());
+
+        // New data is inserted
+        let count = db
+            .store_reward_slash_event(&bob, &new_resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 15);
+
+        // No new data is inserted
+        let count = db
+            .store_reward_slash_event(&bob, &new_resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 0);
+
+        // Insert previous data (under a new context)
+        let count = db
+            .store_reward_slash_event(&bob, &resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 10);
+    }
+
+    #[tokio::test]
+    async fn store_nomination_event() {
+        let db = db().await;
+
+        // Must now have an influence on data.
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        // Gen test data
+        let mut resp: Response<NominationsPage> = Default::default();
+        resp.data.list = Some(vec![Default::default(); 10]);
+        resp.data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, e)| e.stash_account_display.address = idx.to_string());
+
+        // New data is inserted
+        let count = db
+            .store_nomination_event(&alice, &resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 10);
+
+        // No new data is inserted
+        let count = db
+            .store_nomination_event(&alice, &resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 0);
+
+        // Gen new test data
+        let mut new_resp: Response<NominationsPage> = Default::default();
+        new_resp.data.list = Some(vec![Default::default(); 15]);
+        new_resp
+            .data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, e)| e.stash_account_display.address = (idx + 10).to_string());
+
+        // New data is inserted
+        let count = db
+            .store_nomination_event(&bob, &new_resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 15);
+
+        // No new data is inserted
+        let count = db
+            .store_nomination_event(&bob, &new_resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 0);
+
+        // Insert previous data (under a new context)
+        let count = db
+            .store_nomination_event(&bob, &resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 10);
+    }
+
+    #[tokio::test]
+    async fn store_staking_action() {
+        let db = db().await;
+
+        // Must now have an influence on data.
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        // Gen test data
+        let mut resp: Response<StakingActionsPage> = Default::default();
+        resp.data.list = Some(vec![Default::default(); 10]);
+        resp.data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, e)| e.extrinsic_hash = idx.to_string().into());
+
+        // New data is inserted
+        let count = db
+            .store_staking_action(&alice, &resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 10);
+
+        // No new data is inserted
+        let count = db
+            .store_staking_action(&alice, &resp)
+            .await
+            .unwrap()
+            .inserted;
+        assert_eq!(count, 0);
+
+        // Insert previous data (under a new context)
+        let count = db.store_staking_action(&bob, &resp).await.unwrap().inserted;
+        assert_eq!(count, 10);
+    }
+
+    #[tokio::test]
+    async fn fetch_transfers() {
+        let db = db().await;
+        let report = db.reader();
+
+        // Must now have an influence on data.
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        // Gen test data
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Default::default(); 10]);
+        resp.data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| {
+                t.block_timestamp = Timestamp::from(idx as u64 * 100);
+                t.extrinsic_index = idx.to_string().into();
+            });
+
+        // New data is inserted
+        let _ = db.store_transfer_event(&alice, &resp).await.unwrap();
+
+        // Fetch data
+        let res = report
+            .fetch_transfers(&[alice], Timestamp::from(300), Timestamp::from(800), None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            res.iter()
+                .map(|c| c.data.clone().into_owned())
+                .collect::<Vec<Transfer>>()
+                .as_slice(),
+            &resp.data.transfers.unwrap()[3..9]
+        );
+
+        // Fetch data (invalid)
+        let res = report
+            .fetch_transfers(&[bob], Timestamp::from(300), Timestamp::from(800), None)
+            .await
+            .unwrap();
+
+        assert!(res.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fetch_transfers_by_counterparty() {
+        let db = db().await;
+        let report = db.reader();
+
+        let alice = Context::alice();
+        let exchange = Context::bob().stash;
+        let other = Context::eve().stash;
+
+        // Transfers alternate between the exchange and another account, in
+        // both directions.
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Default::default(); 8]);
+        resp.data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| {
+                let counterparty = if idx % 2 == 0 { &exchange } else { &other };
+                if idx % 4 < 2 {
+                    t.from = alice.stash.clone();
+                    t.to = counterparty.clone();
+                } else {
+                    t.from = counterparty.clone();
+                    t.to = alice.stash.clone();
+                }
+                t.block_timestamp = Timestamp::from(idx as u64 * 100);
+                t.extrinsic_index = idx.to_string().into();
+            });
+
+        let _ = db.store_transfer_event(&alice, &resp).await.unwrap();
+
+        let res = report
+            .fetch_transfers(
+                std::slice::from_ref(&alice),
+                Timestamp::from(0),
+                Timestamp::from(i64::MAX as u64),
+                Some(&exchange),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.len(), 4);
+        assert!(res
+            .iter()
+            .all(|c| c.data.from == exchange || c.data.to == exchange));
+
+        let count = report
+            .count_transfers(
+                std::slice::from_ref(&alice),
+                Timestamp::from(0),
+                Timestamp::from(i64::MAX as u64),
+                Some(&exchange),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(count, 4);
+
+        // Unknown counterparties match nothing.
+        let res = report
+            .fetch_transfers(
+                &[alice],
+                Timestamp::from(0),
+                Timestamp::from(i64::MAX as u64),
+                Some("unknown"),
+            )
+            .await
+            .unwrap();
+
+        assert!(res.is_empty());
+    }
+
+    #[test]
+    fn stash_context_maps_to_context_id() {
+        let alice = Context::alice();
+        let context = stash_context(&alice.stash, alice.network);
+
+        assert_eq!(context.id(), alice.id());
+    }
+
+    #[tokio::test]
+    async fn fetch_stored_nomination() {
+        let db = db().await;
+        let report = db.reader();
+
+        let alice = Context::alice();
+        let mut nomination = Nomination::default();
+        nomination.stash_account_display.address = "validator".to_string();
+
+        let mut resp: Response<NominationsPage> = Default::default();
+        resp.data.list = Some(vec![nomination.clone()]);
+        db.store_nomination_event(&alice, &resp).await.unwrap();
+
+        let res = report
+            .fetch_nominations(std::slice::from_ref(&alice))
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].context_id, alice.id());
+        assert_eq!(res[0].data.as_ref(), &nomination);
+
+        let res = report
+            .fetch_nominations_for_stash(&alice.stash, alice.network)
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert!(report
+            .fetch_nominations(&[Context::bob()])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn fetch_for_stash() {
+        let db = db().await;
+        let report = db.reader();
+
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Default::default(); 10]);
+        resp.data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| {
+                t.block_timestamp = Timestamp::from(idx as u64 * 100);
+                t.extrinsic_index = idx.to_string().into();
+            });
+
+        let _ = db.store_transfer_event(&alice, &resp).await.unwrap();
+
+        let mut rewards: Response<RewardsSlashesPage> = Default::default();
+        rewards.data.list = Some(vec![Default::default(); 5]);
+        rewards
+            .data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, e)| e.event_index = idx.to_string());
+
+        let _ = db.store_reward_slash_event(&bob, &rewards).await.unwrap();
+
+        // Only the entries of the given account are returned.
+        let res = report
+            .fetch_transfers_for_stash(
+                &alice.stash,
+                alice.network,
+                Timestamp::from(300),
+                Timestamp::from(800),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.len(), 6);
+        assert!(res.iter().all(|c| c.context_id == alice.id()));
+
+        let res = report
+            .fetch_transfers_for_stash(
+                &bob.stash,
+                bob.network,
+                Timestamp::from(0),
+                Timestamp::from(i64::MAX as u64),
+            )
+            .await
+            .unwrap();
+
+        assert!(res.is_empty());
+
+        let res = report
+            .fetch_rewards_slashes_for_stash(
+                &bob.stash,
+                bob.network,
+                BlockNumber::from(0),
+                BlockNumber::from(i64::MAX as u64),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.len(), 5);
+        assert!(res.iter().all(|c| c.context_id == bob.id()));
+
+        // The network is part of the lookup.
+        let res = report
+            .fetch_rewards_slashes_for_stash(
+                &bob.stash,
+                Network::Kusama,
+                BlockNumber::from(0),
+                BlockNumber::from(i64::MAX as u64),
+            )
+            .await
+            .unwrap();
+
+        assert!(res.is_empty());
+
+        let res = report
+            .fetch_nominations_for_stash(&alice.stash, alice.network)
+            .await
+            .unwrap();
+
+        assert!(res.is_empty());
+    }
+
+    #[tokio::test]
+    async fn mark_orphaned_transfers() {
+        let db = db().await;
+        let report = db.reader();
+
+        let alice = Context::alice();
+
+        // Gen test data
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Default::default(); 10]);
+        resp.data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| {
+                t.block_num = BlockNumber::from(idx as u64 * 100);
+                t.block_timestamp = Timestamp::from(idx as u64 * 100);
+                t.extrinsic_index = idx.to_string().into();
+            });
+
+        // New data is inserted
+        let _ = db.store_transfer_event(&alice, &resp).await.unwrap();
+
+        // The extrinsic at block 700 vanished from the chain.
+        let mut recent = resp.clone();
+        recent.data.transfers.as_mut().unwrap().remove(7);
+
+        let orphaned = db
+            .mark_orphaned_transfers(&alice, BlockNumber::from(500), &[recent.clone()])
+            .await
+            .unwrap();
+
+        assert_eq!(orphaned, 1);
+
+        // Orphaned entries are excluded from reports.
+        let res = report
+            .fetch_transfers(
+                std::slice::from_ref(&alice),
+                Timestamp::from(0),
+                Timestamp::from(i64::MAX as u64),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.len(), 9);
+        assert!(res
+            .iter()
+            .all(|c| c.data.extrinsic_index
+                != resp.data.transfers.as_ref().unwrap()[7].extrinsic_index));
+
+        // Already orphaned entries are not counted again.
+        let orphaned = db
+            .mark_orphaned_transfers(&alice, BlockNumber::from(500), &[recent])
+            .await
+            .unwrap();
+
+        assert_eq!(orphaned, 0);
+
+        // The extrinsic reappears.
+        let orphaned = db
+            .mark_orphaned_transfers(&alice, BlockNumber::from(500), &[resp])
+            .await
+            .unwrap();
+
+        assert_eq!(orphaned, 0);
+
+        let count = report
+            .count_transfers(
+                &[alice],
+                Timestamp::from(0),
+                Timestamp::from(i64::MAX as u64),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(count, 10);
+    }
+
+    #[tokio::test]
+    async fn acquire_lease_contention() {
+        let db = db().await;
+
+        // The first instance acquires the lease, the second one is rejected.
+        assert!(db.acquire_lease("transfer", "first", 60).await.unwrap());
+        assert!(!db.acquire_lease("transfer", "second", 60).await.unwrap());
+
+        // The owner can renew it. Leases of other names are independent.
+        assert!(db.acquire_lease("transfer", "first", 0).await.unwrap());
+        assert!(db.acquire_lease("nominations", "second", 60).await.unwrap());
+
+        // Once expired, the lease can be taken over.
+        assert!(db.acquire_lease("transfer", "second", 60).await.unwrap());
+        assert!(!db.acquire_lease("transfer", "first", 60).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn latest_block() {
+        let db = db().await;
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        // Nothing was stored yet.
+        let latest = db
+            .latest_block(&alice, &ScrapingModule::Transfer)
+            .await
+            .unwrap();
+        assert!(latest.is_none());
+
+        // Gen test data
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Default::default(); 10]);
+        resp.data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| {
+                t.block_num = BlockNumber::from(idx as u64 * 100);
+                t.extrinsic_index = idx.to_string().into();
+            });
+
+        let _ = db.store_transfer_event(&alice, &resp).await.unwrap();
+
+        let latest = db
+            .latest_block(&alice, &ScrapingModule::Transfer)
+            .await
+            .unwrap();
+        assert_eq!(latest, Some(BlockNumber::from(900)));
+
+        // Tracked per account and module.
+        let latest = db
+            .latest_block(&bob, &ScrapingModule::Transfer)
+            .await
+            .unwrap();
+        assert!(latest.is_none());
+
+        let latest = db
+            .latest_block(&alice, &ScrapingModule::RewardsSlashes)
+            .await
+            .unwrap();
+        assert!(latest.is_none());
+
+        // Orphaned entries are ignored.
+        let mut recent = resp.clone();
+        recent.data.transfers.as_mut().unwrap().pop();
+
+        let _ = db
+            .mark_orphaned_transfers(&alice, BlockNumber::from(500), &[recent])
+            .await
+            .unwrap();
+
+        let latest = db
+            .latest_block(&alice, &ScrapingModule::Transfer)
+            .await
+            .unwrap();
+        assert_eq!(latest, Some(BlockNumber::from(800)));
+    }
+
+    #[tokio::test]
+    async fn fetch_rewards_slashes() {
+        let db = db().await;
+        let report = db.reader();
+
+        // Must now have an influence on data.
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        // Gen test data
+        let mut resp: Response<RewardsSlashesPage> = Default::default();
+        resp.data.list = Some(vec![Default::default(); 10]);
+        resp.data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| {
+                t.block_num = BlockNumber::from(idx as u64 * 100);
+                t.event_index = idx.to_string();
+            });
+
+        // New data is inserted
+        let _ = db.store_reward_slash_event(&alice, &resp).await.unwrap();
+
+        // Fetch data
+        let res = report
+            .fetch_rewards_slashes(&[alice], BlockNumber::from(300), BlockNumber::from(800))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            res.iter()
+                .map(|c| c.data.clone().into_owned())
+                .collect::<Vec<RewardSlash>>()
+                .as_slice(),
+            &resp.data.list.unwrap()[3..9]
+        );
+
+        // Fetch data (invalid)
+        let res = report
+            .fetch_rewards_slashes(&[bob], BlockNumber::from(300), BlockNumber::from(800))
+            .await
+            .unwrap();
+
+        assert!(res.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fetch_rewards_slashes_by_timestamp() {
+        let db = db().await;
+        let report = db.reader();
+
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        // Gen test data
+        let mut resp: Response<RewardsSlashesPage> = Default::default();
+        resp.data.list = Some(vec![Default::default(); 10]);
+        resp.data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| {
+                t.block_num = BlockNumber::from(idx as u64 * 100);
+                t.event_index = idx.to_string();
+            });
+
+        // New data is inserted
+        let before = Timestamp::now();
+        let _ = db.store_reward_slash_event(&alice, &resp).await.unwrap();
+        let after = Timestamp::now();
+        let contexts = [alice];
+
+        // Fetch data within the insertion window.
+        let res = report
+            .fetch_rewards_slashes_by_timestamp(&contexts, before, after)
+            .await
+            .unwrap();
+
+        assert_eq!(res.len(), 10);
+        assert!(res
+            .iter()
+            .all(|c| c.timestamp >= before && c.timestamp <= after));
+
+        let count = report
+            .count_rewards_slashes_by_timestamp(&contexts, before, after)
+            .await
+            .unwrap();
+
+        assert_eq!(count, 10);
+
+        // Fetch data (outside of window)
+        let res = report
+            .fetch_rewards_slashes_by_timestamp(
+                &contexts,
+                Timestamp::from(0),
+                Timestamp::from(before.as_secs() - 1),
+            )
+            .await
+            .unwrap();
+
+        assert!(res.is_empty());
+
+        // Fetch data (invalid)
+        let res = report
+            .fetch_rewards_slashes_by_timestamp(&[bob], before, after)
+            .await
+            .unwrap();
+
+        assert!(res.is_empty());
+    }
+
+    #[tokio::test]
+    async fn count_transfers() {
+        let db = db().await;
+        let report = db.reader();
+
+        // Must now have an influence on data.
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        // Gen test data
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Default::default(); 10]);
+        resp.data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| {
+                t.block_timestamp = Timestamp::from(idx as u64 * 100);
+                t.extrinsic_index = idx.to_string().into();
+            });
+
+        // New data is inserted
+        let _ = db.store_transfer_event(&alice, &resp).await.unwrap();
+
+        // Count data
+        let contexts = [alice];
+        let count = report
+            .count_transfers(&contexts, Timestamp::from(300), Timestamp::from(800), None)
+            .await
+            .unwrap();
+
+        let res = report
+            .fetch_transfers(&contexts, Timestamp::from(300), Timestamp::from(800), None)
+            .await
+            .unwrap();
+
+        assert_eq!(count, 6);
+        assert_eq!(count, res.len() as u64);
+
+        // Count data (invalid)
+        let count = report
+            .count_transfers(&[bob], Timestamp::from(300), Timestamp::from(800), None)
+            .await
+            .unwrap();
+
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn count_rewards_slashes() {
+        let db = db().await;
+        let report = db.reader();
+
+        // Must now have an influence on data.
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        // Gen test data
+        let mut resp: Response<RewardsSlashesPage> = Default::default();
+        resp.data.list = Some(vec![Default::default(); 10]);
+        resp.data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| {
+                t.block_num = BlockNumber::from(idx as u64 * 100);
+                t.event_index = idx.to_string();
+            });
+
+        // New data is inserted
+        let _ = db.store_reward_slash_event(&alice, &resp).await.unwrap();
+
+        // Count data
+        let contexts = [alice];
+        let count = report
+            .count_rewards_slashes(&contexts, BlockNumber::from(300), BlockNumber::from(800))
+            .await
+            .unwrap();
+
+        let res = report
+            .fetch_rewards_slashes(&contexts, BlockNumber::from(300), BlockNumber::from(800))
+            .await
+            .unwrap();
+
+        assert_eq!(count, 6);
+        assert_eq!(count, res.len() as u64);
+
+        // Count data (invalid)
+        let count = report
+            .count_rewards_slashes(&[bob], BlockNumber::from(300), BlockNumber::from(800))
+            .await
+            .unwrap();
+
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn count_nominations() {
+        let db = db().await;
+        let report = db.reader();
+
+        // Must now have an influence on data.
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        // Gen test data
+        let mut resp: Response<NominationsPage> = Default::default();
+        resp.data.list = Some(vec![Default::default(); 10]);
+        resp.data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, e)| e.stash_account_display.address = idx.to_string());
+
+        // New data is inserted
+        let _ = db.store_nomination_event(&alice, &resp).await.unwrap();
+
+        // Count data
+        let contexts = [alice];
+        let count = report.count_nominations(&contexts).await.unwrap();
+        let res = report.fetch_nominations(&contexts).await.unwrap();
+
+        assert_eq!(count, 10);
+        assert_eq!(count, res.len() as u64);
+
+        // Count data (invalid)
+        let count = report.count_nominations(&[bob]).await.unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn fetch_transfers_paginated() {
+        let db = db().await;
+        let report = db.reader();
+
+        let alice = Context::alice();
+
+        // Gen test data
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Default::default(); 10]);
+        resp.data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| {
+                t.block_num = BlockNumber::from(idx as u64);
+                t.block_timestamp = Timestamp::from(idx as u64 * 100);
+                t.extrinsic_index = idx.to_string().into();
+            });
+
+        // New data is inserted
+        let _ = db.store_transfer_event(&alice, &resp).await.unwrap();
+
+        let contexts = [alice];
+        let full = report
+            .fetch_transfers(&contexts, Timestamp::from(0), Timestamp::from(1_000), None)
+            .await
+            .unwrap();
+
+        assert_eq!(full.len(), 10);
+
+        // Fetch data in chunks
+        let mut chunks = vec![];
+        for skip in (0..10).step_by(4) {
+            let chunk = report
+                .fetch_transfers_paginated(
+                    &contexts,
+                    Timestamp::from(0),
+                    Timestamp::from(1_000),
+                    Some(Pagination { skip, limit: 4 }),
+                )
+                .await
+                .unwrap();
+
+            chunks.push(chunk.len());
+            assert_eq!(
+                chunk.as_slice(),
+                &full[skip as usize..skip as usize + chunk.len()]
+            );
+        }
+
+        assert_eq!(chunks, vec![4, 4, 2]);
+    }
+
+    #[tokio::test]
+    async fn fetch_rewards_slashes_paginated() {
+        let db = db().await;
+        let report = db.reader();
+
+        let alice = Context::alice();
+
+        // Gen test data
+        let mut resp: Response<RewardsSlashesPage> = Default::default();
+        resp.data.list = Some(vec![Default::default(); 10]);
+        resp.data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| {
+                t.block_num = BlockNumber::from(idx as u64 * 100);
+                t.event_index = idx.to_string();
+            });
+
+        // New data is inserted
+        let _ = db.store_reward_slash_event(&alice, &resp).await.unwrap();
+
+        let contexts = [alice];
+        let full = report
+            .fetch_rewards_slashes(&contexts, BlockNumber::from(0), BlockNumber::from(1_000))
+            .await
+            .unwrap();
+
+        let chunk = report
+            .fetch_rewards_slashes_paginated(
+                &contexts,
+                BlockNumber::from(0),
+                BlockNumber::from(1_000),
+                Some(Pagination { skip: 8, limit: 4 }),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(chunk.as_slice(), &full[8..]);
+    }
+
+    #[tokio::test]
+    async fn fetch_nominations_paginated() {
+        let db = db().await;
+        let report = db.reader();
+
+        let alice = Context::alice();
+
+        // Gen test data
+        let mut resp: Response<NominationsPage> = Default::default();
+        resp.data.list = Some(vec![Default::default(); 10]);
+        resp.data
+            .list
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, e)| e.stash_account_display.address = idx.to_string());
+
+        // New data is inserted
+        let _ = db.store_nomination_event(&alice, &resp).await.unwrap();
+
+        let contexts = [alice];
+        let full = report.fetch_nominations(&contexts).await.unwrap();
+
+        let chunk = report
+            .fetch_nominations_paginated(&contexts, Some(Pagination { skip: 3, limit: 5 }))
+            .await
+            .unwrap();
+
+        assert_eq!(chunk.as_slice(), &full[3..8]);
+    }
+}
@@ -0,0 +1,1675 @@
+use super::{
+    checksum, module_collection, ContextData, DeadLetterEntry, IntegrityReport, NominationSnapshot,
+    Pagination, RawResponse, RecentEntry, RewardSlashFilter, SortDirection, Store, Stored,
+    TransferSort, TransferSortKey, COLL_CONTEXTS, COLL_DEAD_LETTER, COLL_LEASES,
+    COLL_NOMINATIONS_RAW, COLL_NOMINATION_SNAPSHOTS, COLL_NOTIFICATIONS, COLL_RAW_RESPONSES,
+    COLL_REWARD_SLASH_RAW, COLL_STAKING_ACTIONS_RAW, COLL_TRANSFER_RAW, INDEX_CONTEXT_TIMESTAMP,
+    INDEX_TIMESTAMP, TRANSFER_MUTABLE_FIELDS,
+};
+use crate::chain_api::{Nomination, RewardSlash, StakingAction, Transfer};
+use crate::{BlockNumber, Context, ContextId, Network, Result, ScrapingModule, Timestamp};
+use bson::spec::BinarySubtype;
+use bson::{doc, from_bson, from_document, to_bson, Binary, Bson, Document};
+use futures::StreamExt;
+use mongodb::error::{Error as MongoError, ErrorKind, WriteFailure};
+use mongodb::options::{
+    Acknowledgment, DatabaseOptions, FindOneOptions, FindOptions,
+    ReadPreference as MongoReadPreference, ReadPreferenceOptions, ReplaceOptions,
+    SelectionCriteria, UpdateOptions, WriteConcern as MongoWriteConcern,
+};
+use mongodb::{Client, Database as MongoDb};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+const DUPLICATE_KEY_ERROR: i32 = 11000;
+
+/// Convenience trait. Converts a value to BSON.
+trait ToBson {
+    fn to_bson(&self) -> Result<Bson>;
+}
+
+impl<T: Serialize> ToBson for T {
+    fn to_bson(&self) -> Result<Bson> {
+        Ok(to_bson(self)?)
+    }
+}
+
+/// The acknowledgement required for writes to succeed, e.g. `w: majority`
+/// to only succeed once written to the majority of a replica set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteConcern {
+    pub w: WriteAcknowledgment,
+    /// Fails writes which were not acknowledged in time. Such writes are not
+    /// rolled back and might still succeed.
+    pub timeout_secs: Option<u64>,
+    /// Requires writes to be written to the on-disk journal.
+    pub journal: Option<bool>,
+}
+
+/// Either the amount of nodes, `majority` or the name of a custom write
+/// concern of the replica set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WriteAcknowledgment {
+    Nodes(u32),
+    Tag(String),
+}
+
+impl WriteConcern {
+    fn to_mongo(&self) -> Result<MongoWriteConcern> {
+        let w = match &self.w {
+            WriteAcknowledgment::Nodes(0) => {
+                return Err(anyhow!(
+                    "unacknowledged writes (a write concern of zero nodes) are not supported"
+                ))
+            }
+            WriteAcknowledgment::Nodes(nodes) => Acknowledgment::Nodes(*nodes),
+            WriteAcknowledgment::Tag(tag) if tag.is_empty() => {
+                return Err(anyhow!("the write concern must not be empty"))
+            }
+            WriteAcknowledgment::Tag(tag) if tag == "majority" => Acknowledgment::Majority,
+            WriteAcknowledgment::Tag(tag) => Acknowledgment::Custom(tag.clone()),
+        };
+
+        // A timeout of zero would disable the timeout.
+        if self.timeout_secs == Some(0) {
+            return Err(anyhow!(
+                "the timeout of the write concern must be greater than zero"
+            ));
+        }
+
+        let mut concern = MongoWriteConcern::default();
+        concern.w = Some(w);
+        concern.w_timeout = self.timeout_secs.map(Duration::from_secs);
+        concern.journal = self.journal;
+
+        Ok(concern)
+    }
+}
+
+/// The members of a replica set which are read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadPreference {
+    Primary,
+    PrimaryPreferred,
+    Secondary,
+    SecondaryPreferred,
+    Nearest,
+}
+
+impl ReadPreference {
+    fn to_mongo(self) -> MongoReadPreference {
+        let options = ReadPreferenceOptions::default();
+        match self {
+            ReadPreference::Primary => MongoReadPreference::Primary,
+            ReadPreference::PrimaryPreferred => MongoReadPreference::PrimaryPreferred { options },
+            ReadPreference::Secondary => MongoReadPreference::Secondary { options },
+            ReadPreference::SecondaryPreferred => {
+                MongoReadPreference::SecondaryPreferred { options }
+            }
+            ReadPreference::Nearest => MongoReadPreference::Nearest { options },
+        }
+    }
+}
+
+/// Stores the data in MongoDB, one collection per module. The default store.
+#[derive(Clone)]
+pub struct MongoStore {
+    client: Client,
+    db: MongoDb,
+    /// Only applies to the `DatabaseReader`, see `with_read_preference`.
+    read_preference: Option<ReadPreference>,
+    /// Prepended to the names of all collections, see
+    /// `with_collection_prefix`.
+    collection_prefix: Option<String>,
+}
+
+/// Returns whether an upsert inserted a new entry. Existing entries are never
+/// expected to be modified (`$setOnInsert`), but if one is (e.g. by a
+/// concurrent writer) it is only logged, so the fetcher keeps running.
+fn is_inserted(modified_count: u64, upserted_id: &Option<Bson>) -> bool {
+    if modified_count != 0 {
+        warn!(
+            modified_count,
+            "Unexpectedly modified an existing entry while storing, continuing"
+        );
+    }
+
+    upserted_id.is_some()
+}
+
+fn is_duplicate_key(err: &MongoError) -> bool {
+    matches!(
+        &*err.kind,
+        ErrorKind::Write(WriteFailure::WriteError(err)) if err.code == DUPLICATE_KEY_ERROR
+    )
+}
+
+impl MongoStore {
+    /// No connection is established until a query is executed.
+    pub async fn new(uri: &str, db: &str) -> Result<Self> {
+        let client = Client::with_uri_str(uri).await?;
+        Ok(MongoStore {
+            db: client.database(db),
+            client,
+            read_preference: None,
+            collection_prefix: None,
+        })
+    }
+    /// Applies the write concern to all writes, including the ones storing
+    /// entries. The default of the server applies if not set.
+    pub fn with_write_concern(mut self, concern: &WriteConcern) -> Result<Self> {
+        let mut options = DatabaseOptions::default();
+        options.write_concern = Some(concern.to_mongo()?);
+
+        self.db = self.client.database_with_options(self.db.name(), options);
+        Ok(self)
+    }
+    /// Reads from the given members of a replica set in the `DatabaseReader`,
+    /// e.g. to offload report generation from the primary. All other reads,
+    /// such as the ones deciding what to fetch, always read from the primary.
+    pub fn with_read_preference(mut self, preference: ReadPreference) -> Self {
+        self.read_preference = Some(preference);
+        self
+    }
+    /// Names all collections `{prefix}_{name}`, e.g. `staging_raw_transfers`,
+    /// so multiple environments can share a database.
+    pub fn with_collection_prefix(mut self, prefix: &str) -> Self {
+        self.collection_prefix = Some(prefix.to_string());
+        self
+    }
+    /// The name of the given collection, including the prefix.
+    fn collection_name(&self, name: &str) -> String {
+        match &self.collection_prefix {
+            Some(prefix) => format!("{}_{}", prefix, name),
+            None => name.to_string(),
+        }
+    }
+    /// The database read from by the `DatabaseReader`.
+    fn read_db(&self) -> MongoDb {
+        match self.read_preference {
+            Some(preference) => {
+                let mut options = DatabaseOptions::default();
+                options.selection_criteria =
+                    Some(SelectionCriteria::ReadPreference(preference.to_mongo()));
+                self.client.database_with_options(self.db.name(), options)
+            }
+            None => self.db.clone(),
+        }
+    }
+    async fn verify_collection<T>(
+        &self,
+        coll: &str,
+        context: &Context,
+        report: &mut IntegrityReport,
+    ) -> Result<()>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let mut cursor = self
+            .db
+            .collection::<Document>(&self.collection_name(coll))
+            .find(
+                doc! {
+                    "context_id": context.id().to_bson_key(),
+                },
+                None,
+            )
+            .await?;
+
+        while let Some(doc) = cursor.next().await {
+            let doc = doc?;
+            let expected = match doc.get_str("checksum") {
+                Ok(expected) => expected,
+                Err(_) => {
+                    report.unchecked += 1;
+                    continue;
+                }
+            };
+
+            report.checked += 1;
+
+            // Entries which can not be parsed anymore are corrupted too.
+            let matches = doc
+                .get_document("data")
+                .ok()
+                .and_then(|data| from_document::<T>(data.clone()).ok())
+                .and_then(|data| checksum(&data).ok())
+                .is_some_and(|actual| actual == expected);
+
+            if !matches {
+                let id = doc.get("_id").cloned().unwrap_or(Bson::Null);
+                warn!(collection = coll, id = %id, "Checksum mismatch of stored entry");
+                report.mismatched.push(id);
+            }
+        }
+
+        Ok(())
+    }
+    /// Inserts the entry unless an entry matching the filter exists.
+    async fn insert<T>(
+        &self,
+        coll: &str,
+        filter: Document,
+        entry: &ContextData<'_, T>,
+    ) -> Result<Stored>
+    where
+        T: Clone + Serialize,
+    {
+        let res = self
+            .db
+            .collection::<Document>(&self.collection_name(coll))
+            .update_one(
+                filter,
+                doc! {
+                    "$setOnInsert": entry.to_bson()?,
+                },
+                {
+                    let mut opt = UpdateOptions::default();
+                    opt.upsert = Some(true);
+                    Some(opt)
+                },
+            )
+            .await?;
+
+        if is_inserted(res.modified_count, &res.upserted_id) {
+            Ok(Stored::Inserted)
+        } else {
+            Ok(Stored::Existing)
+        }
+    }
+    async fn find_rewards_slashes(
+        &self,
+        filter: Document,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'static, RewardSlash>>> {
+        let coll = self
+            .db
+            .collection::<ContextData<RewardSlash>>(&self.collection_name(COLL_REWARD_SLASH_RAW));
+
+        let mut cursor = coll
+            .find(filter, {
+                let mut ops = FindOptions::default();
+                ops.sort = Some(doc! {
+                    "data.block_num": -1,
+                    "_id": 1,
+                });
+                pagination.apply(&mut ops);
+                Some(ops)
+            })
+            .await?;
+
+        let mut rewards_slashes = vec![];
+        while let Some(doc) = cursor.next().await {
+            rewards_slashes.push(doc?);
+        }
+
+        Ok(rewards_slashes)
+    }
+    /// The non-orphaned entries of the collection stored after `since`, the
+    /// most recently stored first.
+    async fn find_recent<T>(
+        &self,
+        coll: &str,
+        since: Timestamp,
+        limit: u64,
+    ) -> Result<Vec<ContextData<'static, T>>>
+    where
+        T: 'static + Clone + Debug + Serialize + DeserializeOwned + Send + Sync + Unpin,
+    {
+        let coll = self
+            .db
+            .collection::<ContextData<T>>(&self.collection_name(coll));
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "timestamp": {
+                        "$gt": since.to_bson()?
+                    },
+                    "orphaned": {
+                        "$ne": true,
+                    },
+                },
+                {
+                    let mut ops = FindOptions::default();
+                    ops.sort = Some(doc! {
+                        "timestamp": -1,
+                        "_id": -1,
+                    });
+                    ops.limit = Some(limit as i64);
+                    Some(ops)
+                },
+            )
+            .await?;
+
+        let mut entries = vec![];
+        while let Some(doc) = cursor.next().await {
+            entries.push(doc?);
+        }
+
+        Ok(entries)
+    }
+}
+
+/// The update of the `TRANSFER_MUTABLE_FIELDS` (and the checksum) of the
+/// `stored` transfer document to the ones of `entry`, or `None` if nothing
+/// changed. All other fields are kept as stored, so the checksum is computed
+/// over the stored data with the updated fields.
+fn transfer_update(entry: &ContextData<Transfer>, stored: &Document) -> Result<Option<Document>> {
+    let new = match entry.data.to_bson()? {
+        Bson::Document(data) => data,
+        _ => return Err(anyhow!("transfer is not serialized as a document")),
+    };
+    let mut data = stored.get_document("data")?.clone();
+
+    let mut set = Document::new();
+    for field in &TRANSFER_MUTABLE_FIELDS {
+        let value = new.get(*field).cloned().unwrap_or(Bson::Null);
+        if data.get(*field) != Some(&value) {
+            set.insert(format!("data.{}", field), value.clone());
+            data.insert(*field, value);
+        }
+    }
+
+    let mut update = Document::new();
+    // Must match the updated data, a checksum of the previous data would no
+    // longer match.
+    if entry.checksum.is_some() {
+        let checksum = checksum(&from_document::<Transfer>(data)?)?;
+        if stored.get_str("checksum").ok() != Some(checksum.as_str()) {
+            set.insert("checksum", checksum);
+        }
+    } else if stored.contains_key("checksum") {
+        update.insert("$unset", doc! { "checksum": "" });
+    }
+
+    if !set.is_empty() {
+        update.insert("$set", set);
+    }
+
+    Ok(if update.is_empty() {
+        None
+    } else {
+        Some(update)
+    })
+}
+
+#[async_trait]
+impl Store for MongoStore {
+    async fn check_connection(&self) -> Result<()> {
+        use tokio::time::timeout;
+
+        if timeout(
+            Duration::from_secs(10),
+            self.db.list_collections(doc! {}, None),
+        )
+        .await
+        .is_err()
+        {
+            Err(anyhow!("Failed to connect to database..."))
+        } else {
+            Ok(())
+        }
+    }
+    async fn create_indexes(&self) -> Result<()> {
+        for coll in &[
+            COLL_TRANSFER_RAW,
+            COLL_REWARD_SLASH_RAW,
+            COLL_NOMINATIONS_RAW,
+            COLL_STAKING_ACTIONS_RAW,
+            COLL_NOMINATION_SNAPSHOTS,
+        ] {
+            self.db
+                .run_command(
+                    doc! {
+                        "createIndexes": self.collection_name(coll),
+                        "indexes": [
+                            {
+                                "key": {
+                                    "context_id": 1,
+                                    "timestamp": 1,
+                                },
+                                "name": INDEX_CONTEXT_TIMESTAMP,
+                            }
+                        ],
+                    },
+                    None,
+                )
+                .await?;
+        }
+
+        // Used by `fetch_recent`, which queries across all accounts.
+        for coll in &[
+            COLL_TRANSFER_RAW,
+            COLL_REWARD_SLASH_RAW,
+            COLL_NOMINATIONS_RAW,
+        ] {
+            self.db
+                .run_command(
+                    doc! {
+                        "createIndexes": self.collection_name(coll),
+                        "indexes": [
+                            {
+                                "key": {
+                                    "timestamp": -1,
+                                },
+                                "name": INDEX_TIMESTAMP,
+                            }
+                        ],
+                    },
+                    None,
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+    async fn store_transfer(
+        &self,
+        entry: &ContextData<'_, Transfer>,
+        update: bool,
+    ) -> Result<Stored> {
+        let coll = self
+            .db
+            .collection::<Document>(&self.collection_name(COLL_TRANSFER_RAW));
+        // See `Transfer::key`.
+        let mut filter = doc! {
+            "context_id": entry.context_id.to_bson_key(),
+            "data.extrinsic_index": entry.data.extrinsic_index.to_bson()?,
+        };
+        let mut legacy = doc! {
+            "data.from": entry.data.from.to_bson()?,
+            "data.to": entry.data.to.to_bson()?,
+            "data.amount": entry.data.amount.to_bson()?,
+        };
+        match entry.data.event_idx {
+            // Transfers stored before the event index was collected are
+            // still matched by their legacy key, so they are not inserted
+            // again.
+            Some(idx) => {
+                legacy.insert("data.event_idx", doc! { "$exists": false });
+                filter.insert(
+                    "$or",
+                    vec![Bson::from(doc! { "data.event_idx": idx }), legacy.into()],
+                );
+            }
+            None => filter.extend(legacy),
+        }
+
+        let res = coll
+            .update_one(
+                filter.clone(),
+                doc! {
+                    "$setOnInsert": entry.to_bson()?,
+                },
+                {
+                    let mut opt = UpdateOptions::default();
+                    opt.upsert = Some(true);
+                    Some(opt)
+                },
+            )
+            .await?;
+
+        if is_inserted(res.modified_count, &res.upserted_id) {
+            return Ok(Stored::Inserted);
+        }
+        if !update {
+            return Ok(Stored::Existing);
+        }
+
+        let stored = coll
+            .find_one(filter, None)
+            .await?
+            .ok_or_else(|| anyhow!("stored transfer not found"))?;
+
+        match transfer_update(entry, &stored)? {
+            Some(update) => {
+                coll.update_one(doc! { "_id": stored.get_object_id("_id")? }, update, None)
+                    .await?;
+                Ok(Stored::Updated)
+            }
+            None => Ok(Stored::Existing),
+        }
+    }
+    async fn store_reward_slash(&self, entry: &ContextData<'_, RewardSlash>) -> Result<Stored> {
+        self.insert(
+            COLL_REWARD_SLASH_RAW,
+            doc! {
+                "context_id": entry.context_id.to_bson_key(),
+                "data.event_index": entry.data.event_index.to_bson()?,
+            },
+            entry,
+        )
+        .await
+    }
+    async fn store_nomination(&self, entry: &ContextData<'_, Nomination>) -> Result<Stored> {
+        self.insert(
+            COLL_NOMINATIONS_RAW,
+            doc! {
+                "context_id": entry.context_id.to_bson_key(),
+                "data.stash_account_display.address": entry.data.stash_account_display.address.to_bson()?,
+            },
+            entry,
+        )
+        .await
+    }
+    async fn store_staking_action(&self, entry: &ContextData<'_, StakingAction>) -> Result<Stored> {
+        self.insert(
+            COLL_STAKING_ACTIONS_RAW,
+            doc! {
+                "context_id": entry.context_id.to_bson_key(),
+                "data.extrinsic_hash": entry.data.extrinsic_hash.to_bson()?,
+            },
+            entry,
+        )
+        .await
+    }
+    fn is_transient(&self, err: &anyhow::Error) -> bool {
+        err.chain()
+            .filter_map(|cause| cause.downcast_ref::<MongoError>())
+            .any(|err| {
+                err.contains_label("RetryableWriteError")
+                    || matches!(
+                        *err.kind,
+                        ErrorKind::Io(_)
+                            | ErrorKind::ConnectionPoolCleared { .. }
+                            | ErrorKind::ServerSelection { .. }
+                            | ErrorKind::DnsResolve { .. }
+                    )
+            })
+    }
+    async fn is_dead_letter(&self, collection: &str, context: &Context, key: &str) -> Result<bool> {
+        Ok(self
+            .db
+            .collection::<Document>(&self.collection_name(COLL_DEAD_LETTER))
+            .find_one(
+                doc! {
+                    "collection": collection,
+                    "context_id": context.id().to_bson_key(),
+                    "key": key,
+                },
+                None,
+            )
+            .await?
+            .is_some())
+    }
+    async fn store_dead_letter(&self, entry: &DeadLetterEntry<'_>) -> Result<()> {
+        let filter = doc! {
+            "collection": entry.collection,
+            "context_id": entry.context_id.to_bson_key(),
+            "key": entry.key,
+        };
+
+        let mut doc = filter.clone();
+        doc.insert("timestamp", entry.timestamp.to_bson()?);
+        doc.insert("attempts", entry.attempts);
+        doc.insert("error", entry.error.as_str());
+        doc.insert("data", entry.data.as_str());
+
+        self.db
+            .collection::<Document>(&self.collection_name(COLL_DEAD_LETTER))
+            .replace_one(filter, doc, {
+                let mut opt = ReplaceOptions::default();
+                opt.upsert = Some(true);
+                Some(opt)
+            })
+            .await?;
+
+        Ok(())
+    }
+    async fn is_notified(&self, destination: &str, event_id: &str) -> Result<bool> {
+        Ok(self
+            .db
+            .collection::<Document>(&self.collection_name(COLL_NOTIFICATIONS))
+            .find_one(
+                doc! {
+                    "destination": destination,
+                    "event_id": event_id,
+                },
+                None,
+            )
+            .await?
+            .is_some())
+    }
+    async fn store_notified(&self, destination: &str, event_id: &str) -> Result<()> {
+        self.db
+            .collection::<Document>(&self.collection_name(COLL_NOTIFICATIONS))
+            .update_one(
+                doc! {
+                    "destination": destination,
+                    "event_id": event_id,
+                },
+                doc! {
+                    "$setOnInsert": {
+                        "timestamp": Timestamp::now().to_bson()?,
+                    },
+                },
+                {
+                    let mut opt = UpdateOptions::default();
+                    opt.upsert = Some(true);
+                    Some(opt)
+                },
+            )
+            .await?;
+
+        Ok(())
+    }
+    async fn mark_orphaned(
+        &self,
+        module: &ScrapingModule,
+        context: &Context,
+        from: BlockNumber,
+        present: &[String],
+    ) -> Result<u64> {
+        let key = match module {
+            ScrapingModule::Transfer => "data.extrinsic_index",
+            ScrapingModule::RewardsSlashes => "data.event_index",
+            _ => return Err(anyhow!("{:?} entries can not be orphaned", module)),
+        };
+        let coll = self
+            .db
+            .collection::<Document>(&self.collection_name(module_collection(module)));
+
+        let mut orphaned = doc! {
+            "context_id": context.id().to_bson_key(),
+            "data.block_num": {
+                "$gte": from.to_bson()?
+            },
+            "orphaned": {
+                "$ne": true
+            },
+        };
+        orphaned.insert(key, doc! { "$nin": present });
+
+        let res = coll
+            .update_many(orphaned, doc! { "$set": { "orphaned": true } }, None)
+            .await?;
+
+        let mut restored = doc! {
+            "context_id": context.id().to_bson_key(),
+            "data.block_num": {
+                "$gte": from.to_bson()?
+            },
+            "orphaned": true,
+        };
+        restored.insert(key, doc! { "$in": present });
+
+        coll.update_many(restored, doc! { "$unset": { "orphaned": "" } }, None)
+            .await?;
+
+        Ok(res.modified_count)
+    }
+    async fn latest_block(
+        &self,
+        context: &Context,
+        module: &ScrapingModule,
+    ) -> Result<Option<BlockNumber>> {
+        if *module == ScrapingModule::Nominations {
+            return Ok(None);
+        }
+
+        let entry = self
+            .db
+            .collection::<Document>(&self.collection_name(module_collection(module)))
+            .find_one(
+                doc! {
+                    "context_id": context.id().to_bson_key(),
+                    "orphaned": {
+                        "$ne": true
+                    },
+                },
+                {
+                    let mut ops = FindOneOptions::default();
+                    ops.sort = Some(doc! {
+                        "data.block_num": -1,
+                    });
+                    ops.projection = Some(doc! {
+                        "data.block_num": 1,
+                    });
+                    Some(ops)
+                },
+            )
+            .await?;
+
+        match entry {
+            Some(entry) => Ok(Some(from_bson(
+                entry
+                    .get_document("data")?
+                    .get("block_num")
+                    .cloned()
+                    .ok_or_else(|| {
+                        anyhow!("No block number found in stored entry of {:?}", context)
+                    })?,
+            )?)),
+            None => Ok(None),
+        }
+    }
+    async fn acquire_lease(&self, name: &str, holder: &str, ttl: u64) -> Result<bool> {
+        let coll = self
+            .db
+            .collection::<Document>(&self.collection_name(COLL_LEASES));
+        let now = Timestamp::now();
+
+        let res = coll
+            .update_one(
+                doc! {
+                    "_id": name,
+                    "$or": [
+                        { "holder": holder },
+                        { "expires_at": { "$lte": now.to_bson()? } },
+                    ],
+                },
+                doc! {
+                    "$set": {
+                        "holder": holder,
+                        "expires_at": Timestamp::from(now.as_secs() + ttl).to_bson()?,
+                    },
+                },
+                {
+                    let mut opt = UpdateOptions::default();
+                    opt.upsert = Some(true);
+                    Some(opt)
+                },
+            )
+            .await;
+
+        match res {
+            Ok(_) => Ok(true),
+            // The lease exists but is owned by someone else, so the filter
+            // does not match and the upsert conflicts with the existing `_id`.
+            Err(err) if is_duplicate_key(&err) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+    async fn load_contexts(&self) -> Result<Vec<Context>> {
+        let mut cursor = self
+            .db
+            .collection::<Context>(&self.collection_name(COLL_CONTEXTS))
+            .find(None, {
+                let mut ops = FindOptions::default();
+                ops.sort = Some(doc! { "_id": 1 });
+                Some(ops)
+            })
+            .await?;
+
+        let mut contexts = vec![];
+        while let Some(context) = cursor.next().await {
+            contexts.push(context?);
+        }
+
+        Ok(contexts)
+    }
+    async fn upsert_context(&self, context: &Context) -> Result<bool> {
+        let id = context.id().to_string();
+        let mut entry = bson::to_document(context)?;
+        entry.insert("_id", id.as_str());
+
+        let res = self
+            .db
+            .collection::<Document>(&self.collection_name(COLL_CONTEXTS))
+            .replace_one(doc! { "_id": id.as_str() }, entry, {
+                let mut opt = ReplaceOptions::default();
+                opt.upsert = Some(true);
+                Some(opt)
+            })
+            .await?;
+
+        Ok(res.upserted_id.is_some())
+    }
+    async fn verify_integrity(&self, context: &Context) -> Result<IntegrityReport> {
+        let mut report = IntegrityReport::default();
+
+        self.verify_collection::<Transfer>(COLL_TRANSFER_RAW, context, &mut report)
+            .await?;
+        self.verify_collection::<RewardSlash>(COLL_REWARD_SLASH_RAW, context, &mut report)
+            .await?;
+        self.verify_collection::<Nomination>(COLL_NOMINATIONS_RAW, context, &mut report)
+            .await?;
+        self.verify_collection::<StakingAction>(COLL_STAKING_ACTIONS_RAW, context, &mut report)
+            .await?;
+
+        Ok(report)
+    }
+    fn reader(&self) -> Arc<dyn Store> {
+        Arc::new(MongoStore {
+            db: self.read_db(),
+            ..self.clone()
+        })
+    }
+    async fn distinct_contexts(
+        &self,
+        module: &ScrapingModule,
+    ) -> Result<HashSet<(String, Network)>> {
+        self.db
+            .collection::<Document>(&self.collection_name(module_collection(module)))
+            .distinct("context_id", None, None)
+            .await?
+            .into_iter()
+            .map(|id| {
+                let id: ContextId = from_bson(id)?;
+                Ok((id.stash.into_owned(), id.network))
+            })
+            .collect()
+    }
+    async fn fetch_transfers(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+        sort: TransferSort,
+        counterparty: Option<&str>,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'static, Transfer>>> {
+        let coll = self
+            .db
+            .collection::<ContextData<Transfer>>(&self.collection_name(COLL_TRANSFER_RAW));
+
+        let direction = match sort.direction {
+            SortDirection::Ascending => 1,
+            SortDirection::Descending => -1,
+        };
+
+        let mut pipeline = vec![doc! {
+            "$match": transfers_filter(contexts, from, to, counterparty)?,
+        }];
+
+        match sort.key {
+            TransferSortKey::BlockNum => pipeline.push(doc! {
+                "$sort": {
+                    "data.block_num": direction,
+                    "_id": 1,
+                }
+            }),
+            TransferSortKey::Timestamp => pipeline.push(doc! {
+                "$sort": {
+                    "data.block_timestamp": direction,
+                    "_id": 1,
+                }
+            }),
+            TransferSortKey::Amount => {
+                // Amounts are stored as strings, so they must be converted
+                // in order to be sorted numerically.
+                pipeline.push(doc! {
+                    "$addFields": {
+                        "sort_amount": {
+                            "$convert": {
+                                "input": "$data.amount",
+                                "to": "decimal",
+                                "onError": 0,
+                                "onNull": 0,
+                            }
+                        }
+                    }
+                });
+                pipeline.push(doc! {
+                    "$sort": {
+                        "sort_amount": direction,
+                        "_id": 1,
+                    }
+                });
+                pipeline.push(doc! {
+                    "$project": {
+                        "sort_amount": 0,
+                    }
+                });
+            }
+        }
+
+        if let Some(pagination) = pagination {
+            pipeline.push(doc! {
+                "$skip": pagination.skip as i64,
+            });
+            pipeline.push(doc! {
+                "$limit": pagination.limit as i64,
+            });
+        }
+
+        let mut cursor = coll.aggregate(pipeline, None).await?;
+
+        let mut transfers = vec![];
+        while let Some(doc) = cursor.next().await {
+            transfers.push(from_document(doc?)?);
+        }
+
+        Ok(transfers)
+    }
+    async fn count_transfers(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+        counterparty: Option<&str>,
+    ) -> Result<u64> {
+        let coll = self
+            .db
+            .collection::<ContextData<Transfer>>(&self.collection_name(COLL_TRANSFER_RAW));
+
+        Ok(coll
+            .count_documents(transfers_filter(contexts, from, to, counterparty)?, None)
+            .await?)
+    }
+    async fn fetch_rewards_slashes(
+        &self,
+        contexts: &[Context],
+        filter: RewardSlashFilter,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'static, RewardSlash>>> {
+        self.find_rewards_slashes(rewards_slashes_filter(contexts, filter)?, pagination)
+            .await
+    }
+    async fn count_rewards_slashes(
+        &self,
+        contexts: &[Context],
+        filter: RewardSlashFilter,
+    ) -> Result<u64> {
+        let coll = self
+            .db
+            .collection::<ContextData<RewardSlash>>(&self.collection_name(COLL_REWARD_SLASH_RAW));
+
+        Ok(coll
+            .count_documents(rewards_slashes_filter(contexts, filter)?, None)
+            .await?)
+    }
+    async fn fetch_nominations(
+        &self,
+        contexts: &[Context],
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'static, Nomination>>> {
+        let coll = self
+            .db
+            .collection::<ContextData<Nomination>>(&self.collection_name(COLL_NOMINATIONS_RAW));
+
+        let mut cursor = coll
+            .find(nominations_filter(contexts)?, {
+                let mut ops = FindOptions::default();
+                ops.sort = Some(doc! {
+                    "_id": 1,
+                });
+                pagination.apply(&mut ops);
+                Some(ops)
+            })
+            .await?;
+
+        let mut validators = vec![];
+        while let Some(doc) = cursor.next().await {
+            validators.push(doc?);
+        }
+
+        Ok(validators)
+    }
+    async fn count_nominations(&self, contexts: &[Context]) -> Result<u64> {
+        let coll = self
+            .db
+            .collection::<ContextData<Nomination>>(&self.collection_name(COLL_NOMINATIONS_RAW));
+
+        Ok(coll
+            .count_documents(nominations_filter(contexts)?, None)
+            .await?)
+    }
+    async fn fetch_recent(
+        &self,
+        module: &ScrapingModule,
+        since: Timestamp,
+        limit: u64,
+    ) -> Result<Vec<RecentEntry>> {
+        let coll = module_collection(module);
+        Ok(match module {
+            ScrapingModule::Transfer => self
+                .find_recent(coll, since, limit)
+                .await?
+                .into_iter()
+                .map(RecentEntry::Transfer)
+                .collect(),
+            ScrapingModule::RewardsSlashes => self
+                .find_recent(coll, since, limit)
+                .await?
+                .into_iter()
+                .map(RecentEntry::RewardSlash)
+                .collect(),
+            ScrapingModule::Nominations => self
+                .find_recent(coll, since, limit)
+                .await?
+                .into_iter()
+                .map(RecentEntry::Nomination)
+                .collect(),
+            _ => return Err(anyhow!("recent {:?} entries can not be fetched", module)),
+        })
+    }
+    async fn store_nomination_snapshot(
+        &self,
+        entry: &ContextData<'_, NominationSnapshot>,
+    ) -> Result<()> {
+        self.db
+            .collection::<ContextData<NominationSnapshot>>(
+                &self.collection_name(COLL_NOMINATION_SNAPSHOTS),
+            )
+            .insert_one(entry, None)
+            .await?;
+
+        Ok(())
+    }
+    async fn fetch_nomination_snapshots(
+        &self,
+        context: &Context,
+        limit: u64,
+    ) -> Result<Vec<ContextData<'static, NominationSnapshot>>> {
+        let mut cursor = self
+            .db
+            .collection::<ContextData<NominationSnapshot>>(
+                &self.collection_name(COLL_NOMINATION_SNAPSHOTS),
+            )
+            .find(
+                doc! {
+                    "context_id": context.id().to_bson_key(),
+                },
+                {
+                    let mut ops = FindOptions::default();
+                    // Snapshots of the same second are ordered by insertion.
+                    ops.sort = Some(doc! {
+                        "timestamp": -1,
+                        "_id": -1,
+                    });
+                    ops.limit = Some(limit as i64);
+                    Some(ops)
+                },
+            )
+            .await?;
+
+        let mut snapshots = vec![];
+        while let Some(doc) = cursor.next().await {
+            snapshots.push(doc?);
+        }
+
+        Ok(snapshots)
+    }
+    async fn store_raw_response(&self, response: &RawResponse, max_entries: u64) -> Result<()> {
+        let coll = self
+            .db
+            .collection::<Document>(&self.collection_name(COLL_RAW_RESPONSES));
+
+        coll.insert_one(
+            doc! {
+                "timestamp": response.timestamp.to_bson()?,
+                "url": response.url.as_str(),
+                "request": response.request.as_str(),
+                "body": Binary {
+                    subtype: BinarySubtype::Generic,
+                    bytes: response.compressed.clone(),
+                },
+            },
+            None,
+        )
+        .await?;
+
+        // The newest response beyond the cap, everything up to it is removed.
+        let oldest_kept = coll
+            .find_one(None, {
+                let mut ops = FindOneOptions::default();
+                ops.sort = Some(doc! { "_id": -1 });
+                ops.skip = Some(max_entries);
+                ops.projection = Some(doc! { "_id": 1 });
+                Some(ops)
+            })
+            .await?;
+
+        if let Some(doc) = oldest_kept {
+            coll.delete_many(doc! { "_id": { "$lte": doc.get_object_id("_id")? } }, None)
+                .await?;
+        }
+
+        Ok(())
+    }
+    async fn fetch_raw_responses(
+        &self,
+        url: &str,
+        request: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<RawResponse>> {
+        let mut filter = doc! { "url": url };
+        if let Some(request) = request {
+            filter.insert("request", request);
+        }
+
+        let mut cursor = self
+            .db
+            .collection::<Document>(&self.collection_name(COLL_RAW_RESPONSES))
+            .find(filter, {
+                let mut ops = FindOptions::default();
+                ops.sort = Some(doc! { "_id": -1 });
+                ops.limit = Some(limit as i64);
+                Some(ops)
+            })
+            .await?;
+
+        let mut responses = vec![];
+        while let Some(doc) = cursor.next().await {
+            let doc = doc?;
+            responses.push(RawResponse {
+                timestamp: from_bson(doc.get("timestamp").cloned().unwrap_or(Bson::Null))?,
+                url: doc.get_str("url")?.to_string(),
+                request: doc.get_str("request")?.to_string(),
+                compressed: doc.get_binary_generic("body")?.clone(),
+            });
+        }
+
+        Ok(responses)
+    }
+}
+
+/// Convenience trait. Applies the optional pagination to the find options.
+trait ApplyPagination {
+    fn apply(&self, ops: &mut FindOptions);
+}
+
+impl ApplyPagination for Option<Pagination> {
+    fn apply(&self, ops: &mut FindOptions) {
+        if let Some(pagination) = self {
+            ops.skip = Some(pagination.skip);
+            ops.limit = Some(pagination.limit as i64);
+        }
+    }
+}
+
+impl ContextId<'_> {
+    /// The canonical BSON representation of the ID, used for all queries on
+    /// `context_id`. MongoDB only matches embedded documents with the same
+    /// fields in the same order, so this must equal the serialized
+    /// `ContextData::context_id` of stored entries.
+    fn to_bson_key(&self) -> Bson {
+        Bson::Document(doc! {
+            "stash": self.stash.as_str(),
+            "network": self.network.as_str(),
+        })
+    }
+}
+
+// The filters below are shared by the `fetch_*` and `count_*` methods, so
+// both always operate on the same set of documents. Entries flagged as
+// orphaned are excluded.
+
+fn contexts_filter(contexts: &[Context]) -> Bson {
+    Bson::Array(contexts.iter().map(|c| c.id().to_bson_key()).collect())
+}
+
+fn transfers_filter(
+    contexts: &[Context],
+    from: Timestamp,
+    to: Timestamp,
+    counterparty: Option<&str>,
+) -> Result<Document> {
+    let mut filter = doc! {
+        "context_id": {
+            "$in": contexts_filter(contexts),
+        },
+        "orphaned": {
+            "$ne": true,
+        },
+        "$and": [
+            {
+                "data.block_timestamp": {
+                    "$gte": from.to_bson()?
+                }
+            },
+            {
+                "data.block_timestamp": {
+                    "$lte": to.to_bson()?
+                }
+            }
+        ]
+    };
+
+    if let Some(counterparty) = counterparty {
+        filter.insert(
+            "$or",
+            vec![
+                doc! { "data.from": counterparty },
+                doc! { "data.to": counterparty },
+            ],
+        );
+    }
+
+    Ok(filter)
+}
+
+/// Filters by block number, or by the time of insertion, which is covered by
+/// the `(context_id, timestamp)` index.
+fn rewards_slashes_filter(contexts: &[Context], filter: RewardSlashFilter) -> Result<Document> {
+    let (field, from, to) = match filter {
+        RewardSlashFilter::Blocks { from, to } => {
+            ("data.block_num", from.to_bson()?, to.to_bson()?)
+        }
+        RewardSlashFilter::Inserted { from, to } => ("timestamp", from.to_bson()?, to.to_bson()?),
+    };
+
+    let mut lower = Document::new();
+    lower.insert(field, doc! { "$gte": from });
+    let mut upper = Document::new();
+    upper.insert(field, doc! { "$lte": to });
+
+    Ok(doc! {
+        "context_id": {
+            "$in": contexts_filter(contexts),
+        },
+        "orphaned": {
+            "$ne": true,
+        },
+        "$and": [lower, upper],
+    })
+}
+
+fn nominations_filter(contexts: &[Context]) -> Result<Document> {
+    Ok(doc! {
+        "context_id": {
+            "$in": contexts_filter(contexts),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::stash_context;
+    use super::*;
+    use crate::chain_api::{Response, RewardsSlashesPage, TransfersPage};
+    use crate::tests::db;
+    use crate::Database;
+    use std::borrow::Cow;
+
+    #[test]
+    fn modified_entry_is_not_counted() {
+        // A matched and modified entry does not panic and is not counted.
+        assert!(!is_inserted(1, &None));
+        assert!(!is_inserted(0, &None));
+        assert!(is_inserted(0, &Some(Bson::Int32(1))));
+    }
+
+    #[tokio::test]
+    async fn transfer_update_of_mutable_fields() {
+        // No connection is established until a query is executed.
+        let db = Database::new("mongodb://localhost:27017/", "monitor")
+            .await
+            .unwrap()
+            .with_checksums(true);
+        let alice = Context::alice();
+        let transfer = Transfer {
+            amount: "10000000000".into(),
+            extrinsic_index: "1-1".to_string().into(),
+            ..Default::default()
+        };
+        let stored = match db.entry(&alice, &transfer).unwrap().to_bson().unwrap() {
+            Bson::Document(stored) => stored,
+            _ => unreachable!(),
+        };
+
+        // Nothing changed.
+        let entry = db.entry(&alice, &transfer).unwrap();
+        assert!(transfer_update(&entry, &stored).unwrap().is_none());
+
+        // Only the mutable fields are updated, all other fields are kept as
+        // stored and are part of the checksum.
+        let finalized = Transfer {
+            success: true,
+            hash: "0x01".to_string(),
+            ..transfer.clone()
+        };
+        let entry = db.entry(&alice, &finalized).unwrap();
+        let update = transfer_update(&entry, &stored).unwrap().unwrap();
+
+        let set = update.get_document("$set").unwrap();
+        let mut keys: Vec<&String> = set.keys().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["checksum", "data.success"]);
+        let expected = Transfer {
+            success: true,
+            ..transfer.clone()
+        };
+        assert_eq!(
+            set.get_str("checksum").unwrap(),
+            checksum(&expected).unwrap()
+        );
+        assert!(!update.contains_key("$unset"));
+
+        // Without checksums, a previously stored one is removed.
+        let db = db.with_checksums(false);
+        let entry = db.entry(&alice, &transfer).unwrap();
+        let update = transfer_update(&entry, &stored).unwrap().unwrap();
+
+        assert!(!update.contains_key("$set"));
+        assert_eq!(
+            update.get_document("$unset").unwrap(),
+            &doc! { "checksum": "" }
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_write_concern_and_read_preference() {
+        let concern: WriteConcern =
+            serde_yaml::from_str("{ w: majority, timeout_secs: 10 }").unwrap();
+        assert_eq!(concern.w, WriteAcknowledgment::Tag("majority".to_string()));
+        let nodes: WriteConcern = serde_yaml::from_str("w: 2").unwrap();
+        assert_eq!(nodes.w, WriteAcknowledgment::Nodes(2));
+
+        // No connection is established until a query is executed.
+        let store = MongoStore::new("mongodb://localhost:27017/", "monitor")
+            .await
+            .unwrap()
+            .with_write_concern(&concern)
+            .unwrap()
+            .with_read_preference(ReadPreference::SecondaryPreferred);
+
+        let applied = store.db.write_concern().unwrap();
+        assert_eq!(applied.w, Some(Acknowledgment::Majority));
+        assert_eq!(applied.w_timeout, Some(Duration::from_secs(10)));
+        assert_eq!(applied.journal, None);
+
+        // Only the reader is affected by the read preference.
+        assert!(store.db.selection_criteria().is_none());
+        assert!(matches!(
+            store.read_db().selection_criteria(),
+            Some(SelectionCriteria::ReadPreference(
+                MongoReadPreference::SecondaryPreferred { .. }
+            ))
+        ));
+        assert_eq!(store.read_db().name(), "monitor");
+
+        // Invalid write concerns are rejected.
+        let invalid = |w, timeout_secs| WriteConcern {
+            w,
+            timeout_secs,
+            journal: None,
+        };
+        assert!(invalid(WriteAcknowledgment::Nodes(0), None)
+            .to_mongo()
+            .is_err());
+        assert!(invalid(WriteAcknowledgment::Tag(String::new()), None)
+            .to_mongo()
+            .is_err());
+        assert!(invalid(WriteAcknowledgment::Nodes(1), Some(0))
+            .to_mongo()
+            .is_err());
+        assert_eq!(
+            invalid(WriteAcknowledgment::Tag("dc".to_string()), None)
+                .to_mongo()
+                .unwrap()
+                .w,
+            Some(Acknowledgment::Custom("dc".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn dead_letter_unstorable_entry() {
+        let test_db = db().await;
+        let db = test_db.clone();
+        let alice = Context::alice();
+
+        // Block numbers beyond `i64::MAX` can not be represented as BSON.
+        let invalid = Transfer {
+            amount: "10000000000".into(),
+            extrinsic_index: "1-1".to_string().into(),
+            block_num: BlockNumber::from(u64::MAX),
+            ..Default::default()
+        };
+        let valid = Transfer {
+            amount: "10000000000".into(),
+            extrinsic_index: "2-1".to_string().into(),
+            ..Default::default()
+        };
+        assert!(invalid.to_bson().is_err());
+
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![invalid, valid]);
+
+        // Failing entries are retried indefinitely by default.
+        assert!(db.store_transfer_event(&alice, &resp).await.is_err());
+        assert!(db.store_transfer_event(&alice, &resp).await.is_err());
+
+        // Given up on after the second attempt, so the remaining entries are
+        // stored.
+        let db = db.clone().with_dead_letter(2);
+        assert!(db.store_transfer_event(&alice, &resp).await.is_err());
+        assert_eq!(
+            db.store_transfer_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            1
+        );
+
+        let dead_letters: Vec<Document> = test_db
+            .mongo()
+            .db
+            .collection::<Document>(COLL_DEAD_LETTER)
+            .find(None, None)
+            .await
+            .unwrap()
+            .map(|doc| doc.unwrap())
+            .collect()
+            .await;
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(
+            dead_letters[0].get_str("collection").unwrap(),
+            COLL_TRANSFER_RAW
+        );
+        assert!(!dead_letters[0].get_str("error").unwrap().is_empty());
+        assert!(dead_letters[0].get_str("data").unwrap().contains("1-1"));
+
+        // Entries of the dead letter collection are skipped right away.
+        let db = db.with_dead_letter(2);
+        assert_eq!(
+            db.store_transfer_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn prefix_collection_names() {
+        // No connection is established until a query is executed.
+        let store = MongoStore::new("mongodb://localhost:27017/", "monitor")
+            .await
+            .unwrap();
+        assert_eq!(store.collection_name(COLL_TRANSFER_RAW), "raw_transfers");
+
+        let store = store.with_collection_prefix("staging");
+        assert_eq!(
+            store.collection_name(COLL_TRANSFER_RAW),
+            "staging_raw_transfers"
+        );
+        assert_eq!(store.collection_name(COLL_CONTEXTS), "staging_contexts");
+    }
+
+    #[tokio::test]
+    async fn isolate_prefixed_collections() {
+        let test_db = db().await;
+        let alice = Context::alice();
+
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Transfer {
+            extrinsic_index: "1-1".to_string().into(),
+            ..Default::default()
+        }]);
+
+        let staging =
+            Database::with_store(test_db.mongo().clone().with_collection_prefix("staging"));
+        staging.create_indexes().await.unwrap();
+        assert_eq!(
+            staging
+                .store_transfer_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            1
+        );
+
+        let raw = test_db
+            .mongo()
+            .db
+            .collection::<Document>("staging_raw_transfers");
+        assert_eq!(raw.count_documents(None, None).await.unwrap(), 1);
+        let raw = test_db.mongo().db.collection::<Document>(COLL_TRANSFER_RAW);
+        assert_eq!(raw.count_documents(None, None).await.unwrap(), 0);
+
+        // The same transfer is new to the unprefixed environment.
+        assert_eq!(
+            test_db
+                .store_transfer_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            1
+        );
+        assert_eq!(
+            staging
+                .store_transfer_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn verify_integrity() {
+        let db = db().await;
+        let alice = Context::alice();
+
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(vec![Default::default(); 3]);
+        resp.data
+            .transfers
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, t)| t.extrinsic_index = idx.to_string().into());
+
+        // Entries stored before enabling checksums can not be verified.
+        let mut legacy = resp.clone();
+        legacy.data.transfers.as_mut().unwrap().truncate(1);
+        legacy.data.transfers.as_mut().unwrap()[0].extrinsic_index = "legacy".to_string().into();
+        db.store_transfer_event(&alice, &legacy).await.unwrap();
+
+        let store = db.clone().with_checksums(true);
+        store.store_transfer_event(&alice, &resp).await.unwrap();
+
+        let report = store.verify_integrity(&alice).await.unwrap();
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.unchecked, 1);
+        assert!(report.mismatched.is_empty());
+
+        // Tamper with one of the entries.
+        db.mongo()
+            .db
+            .collection::<Document>(COLL_TRANSFER_RAW)
+            .update_one(
+                doc! { "data.extrinsic_index": "1" },
+                doc! { "$set": { "data.amount": "1000000000000" } },
+                None,
+            )
+            .await
+            .unwrap();
+
+        let report = store.verify_integrity(&alice).await.unwrap();
+        assert_eq!(report.mismatched.len(), 1);
+        assert!(store
+            .verify_integrity(&Context::bob())
+            .await
+            .unwrap()
+            .mismatched
+            .is_empty());
+    }
+
+    #[test]
+    fn context_id_matches_stored_key() {
+        let alice = Context::alice();
+        let nomination = Nomination::default();
+        let stored = ContextData {
+            context_id: alice.id(),
+            timestamp: Timestamp::now(),
+            data: Cow::Borrowed(&nomination),
+            labels: Default::default(),
+            checksum: None,
+        }
+        .to_bson()
+        .unwrap();
+
+        assert_eq!(
+            stored.as_document().unwrap().get("context_id").unwrap(),
+            &alice.id().to_bson_key()
+        );
+
+        assert_eq!(
+            alice.id().to_string(),
+            "polkadot:1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP"
+        );
+
+        let context = stash_context(&alice.stash, alice.network);
+        assert_eq!(contexts_filter(&[context]), contexts_filter(&[alice]));
+    }
+
+    #[tokio::test]
+    async fn timestamp_queries_use_index() {
+        let db = db().await;
+        let alice = Context::alice();
+
+        db.create_indexes().await.unwrap();
+        // Idempotent
+        db.create_indexes().await.unwrap();
+
+        // Gen test data of multiple accounts
+        for context in &[alice.clone(), Context::bob(), Context::eve()] {
+            let mut resp: Response<RewardsSlashesPage> = Default::default();
+            resp.data.list = Some(vec![Default::default(); 500]);
+            resp.data
+                .list
+                .as_mut()
+                .unwrap()
+                .iter_mut()
+                .enumerate()
+                .for_each(|(idx, t)| t.event_index = idx.to_string());
+
+            let _ = db.store_reward_slash_event(context, &resp).await.unwrap();
+        }
+
+        let filter = rewards_slashes_filter(
+            &[alice],
+            RewardSlashFilter::Inserted {
+                from: Timestamp::from(0),
+                to: Timestamp::from(i64::MAX as u64),
+            },
+        )
+        .unwrap();
+
+        let explained = db
+            .mongo()
+            .db
+            .run_command(
+                doc! {
+                    "explain": {
+                        "find": COLL_REWARD_SLASH_RAW,
+                        "filter": filter,
+                    },
+                    "verbosity": "queryPlanner",
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        let plan = explained
+            .get_document("queryPlanner")
+            .unwrap()
+            .get_document("winningPlan")
+            .unwrap()
+            .to_string();
+
+        assert!(plan.contains("IXSCAN"));
+        assert!(plan.contains(INDEX_CONTEXT_TIMESTAMP));
+    }
+}
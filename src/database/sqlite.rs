@@ -0,0 +1,1688 @@
+use super::{
+    checksum, module_collection, ContextData, DeadLetterEntry, IntegrityReport, NominationSnapshot,
+    Pagination, RawResponse, RecentEntry, RewardSlashFilter, SortDirection, Store, Stored,
+    TransferSort, TransferSortKey, COLL_CONTEXTS, COLL_DEAD_LETTER, COLL_LEASES,
+    COLL_NOMINATIONS_RAW, COLL_NOMINATION_SNAPSHOTS, COLL_NOTIFICATIONS, COLL_RAW_RESPONSES,
+    COLL_REWARD_SLASH_RAW, COLL_STAKING_ACTIONS_RAW, COLL_TRANSFER_RAW, INDEX_CONTEXT_TIMESTAMP,
+    INDEX_TIMESTAMP, TRANSFER_MUTABLE_FIELDS,
+};
+use crate::chain_api::{Nomination, RewardSlash, StakingAction, Transfer};
+use crate::{BlockNumber, Context, Network, Result, ScrapingModule, Timestamp};
+use bson::Bson;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::{QueryBuilder, Sqlite};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::sync::Arc;
+
+const RAW_TABLES: [&str; 4] = [
+    COLL_TRANSFER_RAW,
+    COLL_REWARD_SLASH_RAW,
+    COLL_NOMINATIONS_RAW,
+    COLL_STAKING_ACTIONS_RAW,
+];
+
+/// Stores the data in a single SQLite file, for deployments which do not
+/// want to operate a MongoDB server. Each entry is stored as JSON, next to
+/// the columns it is queried by.
+#[derive(Clone)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+/// The queried columns of an entry, extracted from its data.
+#[derive(Default)]
+struct Columns {
+    /// Identifies the entry within the account, see `Database::check_stored`.
+    key: String,
+    block_num: Option<i64>,
+    block_timestamp: Option<i64>,
+    sender: Option<String>,
+    recipient: Option<String>,
+    amount: Option<String>,
+    /// The extrinsic index of transfers, the event index of rewards/slashes
+    /// or the extrinsic hash of staking actions, see `Store::mark_orphaned`.
+    extrinsic: Option<String>,
+}
+
+fn to_i64(val: u64) -> Result<i64> {
+    i64::try_from(val).map_err(|_| anyhow!("{} exceeds the range of SQLite integers", val))
+}
+
+fn block_num(block_num: BlockNumber) -> Result<i64> {
+    to_i64(block_num.as_u64())
+}
+
+fn timestamp(timestamp: Timestamp) -> Result<i64> {
+    to_i64(timestamp.as_secs())
+}
+
+/// Matches the entries of any of the accounts. Matches nothing if empty.
+fn push_contexts(qb: &mut QueryBuilder<'_, Sqlite>, contexts: &[Context]) {
+    if contexts.is_empty() {
+        qb.push("0");
+        return;
+    }
+
+    qb.push("(");
+    for (idx, context) in contexts.iter().enumerate() {
+        if idx != 0 {
+            qb.push(" OR ");
+        }
+        qb.push("(stash = ")
+            .push_bind(context.stash.clone())
+            .push(" AND network = ")
+            .push_bind(context.network.as_str().to_string())
+            .push(")");
+    }
+    qb.push(")");
+}
+
+// The filters below are shared by the `fetch_*` and `count_*` methods, so
+// both always operate on the same set of rows. Entries flagged as orphaned
+// are excluded.
+
+fn push_transfers_filter(
+    qb: &mut QueryBuilder<'_, Sqlite>,
+    contexts: &[Context],
+    from: Timestamp,
+    to: Timestamp,
+    counterparty: Option<&str>,
+) -> Result<()> {
+    qb.push(" WHERE ");
+    push_contexts(qb, contexts);
+    qb.push(" AND orphaned = 0 AND block_timestamp >= ")
+        .push_bind(timestamp(from)?)
+        .push(" AND block_timestamp <= ")
+        .push_bind(timestamp(to)?);
+
+    if let Some(counterparty) = counterparty {
+        qb.push(" AND (sender = ")
+            .push_bind(counterparty.to_string())
+            .push(" OR recipient = ")
+            .push_bind(counterparty.to_string())
+            .push(")");
+    }
+
+    Ok(())
+}
+
+fn push_rewards_slashes_filter(
+    qb: &mut QueryBuilder<'_, Sqlite>,
+    contexts: &[Context],
+    filter: RewardSlashFilter,
+) -> Result<()> {
+    let (column, from, to) = match filter {
+        RewardSlashFilter::Blocks { from, to } => ("block_num", block_num(from)?, block_num(to)?),
+        RewardSlashFilter::Inserted { from, to } => ("timestamp", timestamp(from)?, timestamp(to)?),
+    };
+
+    qb.push(" WHERE ");
+    push_contexts(qb, contexts);
+    qb.push(" AND orphaned = 0 AND ")
+        .push(column)
+        .push(" >= ")
+        .push_bind(from)
+        .push(" AND ")
+        .push(column)
+        .push(" <= ")
+        .push_bind(to);
+
+    Ok(())
+}
+
+fn push_pagination(
+    qb: &mut QueryBuilder<'_, Sqlite>,
+    pagination: Option<Pagination>,
+) -> Result<()> {
+    if let Some(pagination) = pagination {
+        qb.push(" LIMIT ")
+            .push_bind(to_i64(pagination.limit)?)
+            .push(" OFFSET ")
+            .push_bind(to_i64(pagination.skip)?);
+    }
+
+    Ok(())
+}
+
+impl SqliteStore {
+    /// Opens the database, e.g. `sqlite://monitor.db` or just the path of the
+    /// file, which is created if it does not exist yet.
+    pub async fn new(uri: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(uri)?.create_if_missing(true);
+
+        // A single connection serializes all writes, just like SQLite does
+        // anyway. It also keeps in-memory databases alive, which only exist
+        // as long as their connection.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await?;
+
+        let store = SqliteStore { pool };
+        store.create_tables().await?;
+
+        Ok(store)
+    }
+    async fn create_tables(&self) -> Result<()> {
+        for table in &RAW_TABLES {
+            sqlx::query(&format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    id INTEGER PRIMARY KEY,
+                    stash TEXT NOT NULL,
+                    network TEXT NOT NULL,
+                    entry_key TEXT NOT NULL,
+                    timestamp INTEGER NOT NULL,
+                    block_num INTEGER,
+                    block_timestamp INTEGER,
+                    sender TEXT,
+                    recipient TEXT,
+                    amount TEXT,
+                    extrinsic TEXT,
+                    orphaned INTEGER NOT NULL DEFAULT 0,
+                    entry TEXT NOT NULL,
+                    UNIQUE (stash, network, entry_key)
+                )",
+                table
+            ))
+            .execute(&self.pool)
+            .await?;
+        }
+
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id INTEGER PRIMARY KEY,
+                stash TEXT NOT NULL,
+                network TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                entry TEXT NOT NULL
+            )",
+            COLL_NOMINATION_SNAPSHOTS
+        ))
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                name TEXT PRIMARY KEY,
+                holder TEXT NOT NULL,
+                expires_at INTEGER NOT NULL
+            )",
+            COLL_LEASES
+        ))
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id TEXT PRIMARY KEY,
+                entry TEXT NOT NULL
+            )",
+            COLL_CONTEXTS
+        ))
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                collection TEXT NOT NULL,
+                stash TEXT NOT NULL,
+                network TEXT NOT NULL,
+                entry_key TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                attempts INTEGER NOT NULL,
+                error TEXT NOT NULL,
+                data TEXT NOT NULL,
+                PRIMARY KEY (collection, stash, network, entry_key)
+            )",
+            COLL_DEAD_LETTER
+        ))
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id INTEGER PRIMARY KEY,
+                timestamp INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                request TEXT NOT NULL,
+                body BLOB NOT NULL
+            )",
+            COLL_RAW_RESPONSES
+        ))
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                destination TEXT NOT NULL,
+                event_id TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                PRIMARY KEY (destination, event_id)
+            )",
+            COLL_NOTIFICATIONS
+        ))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    /// Inserts the entry unless an entry with the same key exists.
+    async fn insert<T>(
+        &self,
+        table: &str,
+        entry: &ContextData<'_, T>,
+        columns: Columns,
+    ) -> Result<Stored>
+    where
+        T: Clone + Serialize,
+    {
+        let res = sqlx::query(&format!(
+            "INSERT INTO {} (stash, network, entry_key, timestamp, block_num, block_timestamp,
+                sender, recipient, amount, extrinsic, entry)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (stash, network, entry_key) DO NOTHING",
+            table
+        ))
+        .bind(entry.context_id.stash.as_str())
+        .bind(entry.context_id.network.as_str())
+        .bind(columns.key)
+        .bind(timestamp(entry.timestamp)?)
+        .bind(columns.block_num)
+        .bind(columns.block_timestamp)
+        .bind(columns.sender)
+        .bind(columns.recipient)
+        .bind(columns.amount)
+        .bind(columns.extrinsic)
+        .bind(serde_json::to_string(entry)?)
+        .execute(&self.pool)
+        .await?;
+
+        if res.rows_affected() != 0 {
+            Ok(Stored::Inserted)
+        } else {
+            Ok(Stored::Existing)
+        }
+    }
+    /// Identifies the transfer within the account, see `Transfer::key`.
+    /// Transfers stored before the event index was collected keep their
+    /// legacy key, so they are not inserted again.
+    async fn transfer_key(&self, entry: &ContextData<'_, Transfer>) -> Result<String> {
+        let transfer = &entry.data;
+        if transfer.event_idx.is_none() {
+            return Ok(transfer.legacy_key());
+        }
+
+        let legacy_key = transfer.legacy_key();
+        let legacy: Option<(i64,)> = sqlx::query_as(&format!(
+            "SELECT id FROM {} WHERE stash = ? AND network = ? AND entry_key = ?",
+            COLL_TRANSFER_RAW
+        ))
+        .bind(entry.context_id.stash.as_str())
+        .bind(entry.context_id.network.as_str())
+        .bind(&legacy_key)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(match legacy {
+            Some(_) => legacy_key,
+            None => transfer.key(),
+        })
+    }
+    /// Sets the mutable fields (and the checksum) of the stored transfer to
+    /// the ones of `entry`. All other fields are kept as stored.
+    async fn update_transfer(
+        &self,
+        entry: &ContextData<'_, Transfer>,
+        key: &str,
+    ) -> Result<Stored> {
+        let stored: Option<(i64, String)> = sqlx::query_as(&format!(
+            "SELECT id, entry FROM {} WHERE stash = ? AND network = ? AND entry_key = ?",
+            COLL_TRANSFER_RAW
+        ))
+        .bind(entry.context_id.stash.as_str())
+        .bind(entry.context_id.network.as_str())
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let (id, stored) = match stored {
+            Some(stored) => stored,
+            None => return Err(anyhow!("stored transfer {} not found", key)),
+        };
+
+        let mut updated: Value = serde_json::from_str(&stored)?;
+        let new = serde_json::to_value(entry)?;
+
+        for field in &TRANSFER_MUTABLE_FIELDS {
+            updated["data"][*field] = new["data"][*field].clone();
+        }
+        // Must match the updated data. All other fields are kept as stored,
+        // so they might differ from the ones of the new entry.
+        let data: Transfer = serde_json::from_value(updated["data"].clone())?;
+        let updated_checksum = match entry.checksum {
+            Some(_) => Some(checksum(&data)?),
+            None => None,
+        };
+        match (updated.as_object_mut(), updated_checksum) {
+            (Some(updated), Some(checksum)) => {
+                updated.insert("checksum".to_string(), Value::String(checksum));
+            }
+            (Some(updated), None) => {
+                updated.remove("checksum");
+            }
+            (None, _) => return Err(anyhow!("stored transfer {} is not an object", key)),
+        }
+
+        if updated == serde_json::from_str::<Value>(&stored)? {
+            return Ok(Stored::Existing);
+        }
+
+        sqlx::query(&format!(
+            "UPDATE {} SET entry = ? WHERE id = ?",
+            COLL_TRANSFER_RAW
+        ))
+        .bind(serde_json::to_string(&updated)?)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Stored::Updated)
+    }
+    async fn verify_table<T>(
+        &self,
+        table: &str,
+        context: &Context,
+        report: &mut IntegrityReport,
+    ) -> Result<()>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let rows: Vec<(i64, String)> = sqlx::query_as(&format!(
+            "SELECT id, entry FROM {} WHERE stash = ? AND network = ? ORDER BY id",
+            table
+        ))
+        .bind(context.stash.as_str())
+        .bind(context.network.as_str())
+        .fetch_all(&self.pool)
+        .await?;
+
+        for (id, entry) in rows {
+            let entry: Value = serde_json::from_str(&entry)?;
+            let expected = match entry.get("checksum").and_then(|c| c.as_str()) {
+                Some(expected) => expected,
+                None => {
+                    report.unchecked += 1;
+                    continue;
+                }
+            };
+
+            report.checked += 1;
+
+            // Entries which can not be parsed anymore are corrupted too.
+            let matches = entry
+                .get("data")
+                .and_then(|data| serde_json::from_value::<T>(data.clone()).ok())
+                .and_then(|data| checksum(&data).ok())
+                .is_some_and(|actual| actual == expected);
+
+            if !matches {
+                warn!(table, id, "Checksum mismatch of stored entry");
+                report.mismatched.push(Bson::Int64(id));
+            }
+        }
+
+        Ok(())
+    }
+    async fn fetch<T>(
+        &self,
+        mut qb: QueryBuilder<'_, Sqlite>,
+    ) -> Result<Vec<ContextData<'static, T>>>
+    where
+        T: 'static + Clone + DeserializeOwned,
+    {
+        qb.build_query_scalar::<String>()
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|entry| Ok(serde_json::from_str(entry)?))
+            .collect()
+    }
+    async fn count(&self, mut qb: QueryBuilder<'_, Sqlite>) -> Result<u64> {
+        let count = qb.build_query_scalar::<i64>().fetch_one(&self.pool).await?;
+
+        Ok(count as u64)
+    }
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    async fn check_connection(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+    async fn create_indexes(&self) -> Result<()> {
+        for table in RAW_TABLES.iter().chain(&[COLL_NOMINATION_SNAPSHOTS]) {
+            sqlx::query(&format!(
+                "CREATE INDEX IF NOT EXISTS {table}_{index} ON {table} (stash, network, timestamp)",
+                table = table,
+                index = INDEX_CONTEXT_TIMESTAMP,
+            ))
+            .execute(&self.pool)
+            .await?;
+        }
+
+        // Used by `fetch_recent`, which queries across all accounts.
+        for table in &[
+            COLL_TRANSFER_RAW,
+            COLL_REWARD_SLASH_RAW,
+            COLL_NOMINATIONS_RAW,
+        ] {
+            sqlx::query(&format!(
+                "CREATE INDEX IF NOT EXISTS {table}_{index} ON {table} (timestamp)",
+                table = table,
+                index = INDEX_TIMESTAMP,
+            ))
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok(())
+    }
+    async fn store_transfer(
+        &self,
+        entry: &ContextData<'_, Transfer>,
+        update: bool,
+    ) -> Result<Stored> {
+        let transfer = &entry.data;
+        let key = self.transfer_key(entry).await?;
+
+        let stored = self
+            .insert(
+                COLL_TRANSFER_RAW,
+                entry,
+                Columns {
+                    key: key.clone(),
+                    block_num: Some(block_num(transfer.block_num)?),
+                    block_timestamp: Some(timestamp(transfer.block_timestamp)?),
+                    sender: Some(transfer.from.clone()),
+                    recipient: Some(transfer.to.clone()),
+                    amount: Some(transfer.amount.to_string()),
+                    extrinsic: Some(transfer.extrinsic_index.to_string()),
+                },
+            )
+            .await?;
+
+        match stored {
+            Stored::Existing if update => self.update_transfer(entry, &key).await,
+            stored => Ok(stored),
+        }
+    }
+    async fn store_reward_slash(&self, entry: &ContextData<'_, RewardSlash>) -> Result<Stored> {
+        self.insert(
+            COLL_REWARD_SLASH_RAW,
+            entry,
+            Columns {
+                key: entry.data.event_index.clone(),
+                block_num: Some(block_num(entry.data.block_num)?),
+                amount: Some(entry.data.amount.to_string()),
+                extrinsic: Some(entry.data.event_index.clone()),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+    async fn store_nomination(&self, entry: &ContextData<'_, Nomination>) -> Result<Stored> {
+        self.insert(
+            COLL_NOMINATIONS_RAW,
+            entry,
+            Columns {
+                key: entry.data.stash_account_display.address.clone(),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+    async fn store_staking_action(&self, entry: &ContextData<'_, StakingAction>) -> Result<Stored> {
+        self.insert(
+            COLL_STAKING_ACTIONS_RAW,
+            entry,
+            Columns {
+                key: entry.data.extrinsic_hash.to_string(),
+                block_num: Some(block_num(entry.data.block_num)?),
+                block_timestamp: Some(timestamp(entry.data.block_timestamp)?),
+                amount: Some(entry.data.amount.to_string()),
+                extrinsic: Some(entry.data.extrinsic_hash.to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+    fn is_transient(&self, err: &anyhow::Error) -> bool {
+        err.chain()
+            .filter_map(|cause| cause.downcast_ref::<sqlx::Error>())
+            .any(|err| match err {
+                sqlx::Error::Io(_)
+                | sqlx::Error::PoolTimedOut
+                | sqlx::Error::PoolClosed
+                | sqlx::Error::WorkerCrashed => true,
+                // `SQLITE_BUSY` or `SQLITE_LOCKED`, the primary result code
+                // is the lowest byte of the extended one.
+                sqlx::Error::Database(err) => err
+                    .code()
+                    .and_then(|code| code.parse::<i32>().ok())
+                    .is_some_and(|code| matches!(code & 0xff, 5 | 6)),
+                _ => false,
+            })
+    }
+    async fn is_dead_letter(&self, collection: &str, context: &Context, key: &str) -> Result<bool> {
+        let found: Option<i64> = sqlx::query_scalar(&format!(
+            "SELECT 1 FROM {}
+            WHERE collection = ? AND stash = ? AND network = ? AND entry_key = ?",
+            COLL_DEAD_LETTER
+        ))
+        .bind(collection)
+        .bind(context.stash.as_str())
+        .bind(context.network.as_str())
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(found.is_some())
+    }
+    async fn store_dead_letter(&self, entry: &DeadLetterEntry<'_>) -> Result<()> {
+        sqlx::query(&format!(
+            "INSERT OR REPLACE INTO {}
+                (collection, stash, network, entry_key, timestamp, attempts, error, data)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            COLL_DEAD_LETTER
+        ))
+        .bind(entry.collection)
+        .bind(entry.context_id.stash.as_str())
+        .bind(entry.context_id.network.as_str())
+        .bind(entry.key)
+        .bind(timestamp(entry.timestamp)?)
+        .bind(entry.attempts)
+        .bind(entry.error.as_str())
+        .bind(entry.data.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn is_notified(&self, destination: &str, event_id: &str) -> Result<bool> {
+        let found: Option<i64> = sqlx::query_scalar(&format!(
+            "SELECT 1 FROM {} WHERE destination = ? AND event_id = ?",
+            COLL_NOTIFICATIONS
+        ))
+        .bind(destination)
+        .bind(event_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(found.is_some())
+    }
+    async fn store_notified(&self, destination: &str, event_id: &str) -> Result<()> {
+        sqlx::query(&format!(
+            "INSERT INTO {} (destination, event_id, timestamp) VALUES (?, ?, ?)
+            ON CONFLICT (destination, event_id) DO NOTHING",
+            COLL_NOTIFICATIONS
+        ))
+        .bind(destination)
+        .bind(event_id)
+        .bind(timestamp(Timestamp::now())?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn mark_orphaned(
+        &self,
+        module: &ScrapingModule,
+        context: &Context,
+        from: BlockNumber,
+        present: &[String],
+    ) -> Result<u64> {
+        match module {
+            ScrapingModule::Transfer | ScrapingModule::RewardsSlashes => {}
+            _ => return Err(anyhow!("{:?} entries can not be orphaned", module)),
+        }
+
+        let mut orphaned = 0;
+        for (flag, operator) in &[(1, "NOT IN"), (0, "IN")] {
+            let mut qb = QueryBuilder::<Sqlite>::new("UPDATE ");
+            qb.push(module_collection(module))
+                .push(" SET orphaned = ")
+                .push_bind(*flag)
+                .push(" WHERE stash = ")
+                .push_bind(context.stash.as_str())
+                .push(" AND network = ")
+                .push_bind(context.network.as_str())
+                .push(" AND block_num >= ")
+                .push_bind(block_num(from)?)
+                .push(" AND orphaned != ")
+                .push_bind(*flag)
+                .push(" AND extrinsic ")
+                .push(operator)
+                .push(" (");
+
+            let mut separated = qb.separated(", ");
+            for extrinsic in present {
+                separated.push_bind(extrinsic.as_str());
+            }
+            qb.push(")");
+
+            let res = qb.build().execute(&self.pool).await?;
+            if *flag == 1 {
+                orphaned = res.rows_affected();
+            }
+        }
+
+        Ok(orphaned)
+    }
+    async fn latest_block(
+        &self,
+        context: &Context,
+        module: &ScrapingModule,
+    ) -> Result<Option<BlockNumber>> {
+        if *module == ScrapingModule::Nominations {
+            return Ok(None);
+        }
+
+        let latest: Option<i64> = sqlx::query_scalar(&format!(
+            "SELECT MAX(block_num) FROM {}
+            WHERE stash = ? AND network = ? AND orphaned = 0",
+            module_collection(module)
+        ))
+        .bind(context.stash.as_str())
+        .bind(context.network.as_str())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(latest.map(|block| BlockNumber::from(block as u64)))
+    }
+    async fn acquire_lease(&self, name: &str, holder: &str, ttl: u64) -> Result<bool> {
+        let now = Timestamp::now();
+
+        let res = sqlx::query(&format!(
+            "INSERT INTO {table} (name, holder, expires_at) VALUES (?, ?, ?)
+            ON CONFLICT (name) DO UPDATE SET
+                holder = excluded.holder,
+                expires_at = excluded.expires_at
+            WHERE {table}.holder = excluded.holder OR {table}.expires_at <= ?",
+            table = COLL_LEASES
+        ))
+        .bind(name)
+        .bind(holder)
+        .bind(timestamp(Timestamp::from(now.as_secs() + ttl))?)
+        .bind(timestamp(now)?)
+        .execute(&self.pool)
+        .await?;
+
+        // Nothing is changed if the lease is owned by someone else.
+        Ok(res.rows_affected() != 0)
+    }
+    async fn load_contexts(&self) -> Result<Vec<Context>> {
+        let entries: Vec<String> =
+            sqlx::query_scalar(&format!("SELECT entry FROM {} ORDER BY id", COLL_CONTEXTS))
+                .fetch_all(&self.pool)
+                .await?;
+
+        entries
+            .iter()
+            .map(|entry| Ok(serde_json::from_str(entry)?))
+            .collect()
+    }
+    async fn upsert_context(&self, context: &Context) -> Result<bool> {
+        let id = context.id().to_string();
+        let entry = serde_json::to_string(context)?;
+
+        let res = sqlx::query(&format!(
+            "INSERT INTO {} (id, entry) VALUES (?, ?) ON CONFLICT (id) DO NOTHING",
+            COLL_CONTEXTS
+        ))
+        .bind(id.as_str())
+        .bind(entry.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        if res.rows_affected() != 0 {
+            return Ok(true);
+        }
+
+        sqlx::query(&format!(
+            "UPDATE {} SET entry = ? WHERE id = ?",
+            COLL_CONTEXTS
+        ))
+        .bind(entry.as_str())
+        .bind(id.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(false)
+    }
+    async fn verify_integrity(&self, context: &Context) -> Result<IntegrityReport> {
+        let mut report = IntegrityReport::default();
+
+        self.verify_table::<Transfer>(COLL_TRANSFER_RAW, context, &mut report)
+            .await?;
+        self.verify_table::<RewardSlash>(COLL_REWARD_SLASH_RAW, context, &mut report)
+            .await?;
+        self.verify_table::<Nomination>(COLL_NOMINATIONS_RAW, context, &mut report)
+            .await?;
+        self.verify_table::<StakingAction>(COLL_STAKING_ACTIONS_RAW, context, &mut report)
+            .await?;
+
+        Ok(report)
+    }
+    fn reader(&self) -> Arc<dyn Store> {
+        Arc::new(self.clone())
+    }
+    async fn distinct_contexts(
+        &self,
+        module: &ScrapingModule,
+    ) -> Result<HashSet<(String, Network)>> {
+        let rows: Vec<(String, String)> = sqlx::query_as(&format!(
+            "SELECT DISTINCT stash, network FROM {}",
+            module_collection(module)
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|(stash, network)| Ok((stash, Network::from_str(&network)?)))
+            .collect()
+    }
+    async fn fetch_transfers(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+        sort: TransferSort,
+        counterparty: Option<&str>,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'static, Transfer>>> {
+        let mut qb = QueryBuilder::new(format!("SELECT entry FROM {}", COLL_TRANSFER_RAW));
+        push_transfers_filter(&mut qb, contexts, from, to, counterparty)?;
+
+        // Amounts are stored as text, so they must be converted in order to
+        // be sorted numerically.
+        let column = match sort.key {
+            TransferSortKey::BlockNum => "block_num",
+            TransferSortKey::Timestamp => "block_timestamp",
+            TransferSortKey::Amount => "CAST(amount AS REAL)",
+        };
+        let direction = match sort.direction {
+            SortDirection::Ascending => "ASC",
+            SortDirection::Descending => "DESC",
+        };
+        qb.push(format!(" ORDER BY {} {}, id ASC", column, direction));
+        push_pagination(&mut qb, pagination)?;
+
+        self.fetch(qb).await
+    }
+    async fn count_transfers(
+        &self,
+        contexts: &[Context],
+        from: Timestamp,
+        to: Timestamp,
+        counterparty: Option<&str>,
+    ) -> Result<u64> {
+        let mut qb = QueryBuilder::new(format!("SELECT COUNT(*) FROM {}", COLL_TRANSFER_RAW));
+        push_transfers_filter(&mut qb, contexts, from, to, counterparty)?;
+
+        self.count(qb).await
+    }
+    async fn fetch_rewards_slashes(
+        &self,
+        contexts: &[Context],
+        filter: RewardSlashFilter,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'static, RewardSlash>>> {
+        let mut qb = QueryBuilder::new(format!("SELECT entry FROM {}", COLL_REWARD_SLASH_RAW));
+        push_rewards_slashes_filter(&mut qb, contexts, filter)?;
+        qb.push(" ORDER BY block_num DESC, id ASC");
+        push_pagination(&mut qb, pagination)?;
+
+        self.fetch(qb).await
+    }
+    async fn count_rewards_slashes(
+        &self,
+        contexts: &[Context],
+        filter: RewardSlashFilter,
+    ) -> Result<u64> {
+        let mut qb = QueryBuilder::new(format!("SELECT COUNT(*) FROM {}", COLL_REWARD_SLASH_RAW));
+        push_rewards_slashes_filter(&mut qb, contexts, filter)?;
+
+        self.count(qb).await
+    }
+    async fn fetch_nominations(
+        &self,
+        contexts: &[Context],
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ContextData<'static, Nomination>>> {
+        let mut qb =
+            QueryBuilder::new(format!("SELECT entry FROM {} WHERE ", COLL_NOMINATIONS_RAW));
+        push_contexts(&mut qb, contexts);
+        qb.push(" ORDER BY id ASC");
+        push_pagination(&mut qb, pagination)?;
+
+        self.fetch(qb).await
+    }
+    async fn count_nominations(&self, contexts: &[Context]) -> Result<u64> {
+        let mut qb = QueryBuilder::new(format!(
+            "SELECT COUNT(*) FROM {} WHERE ",
+            COLL_NOMINATIONS_RAW
+        ));
+        push_contexts(&mut qb, contexts);
+
+        self.count(qb).await
+    }
+    async fn fetch_recent(
+        &self,
+        module: &ScrapingModule,
+        since: Timestamp,
+        limit: u64,
+    ) -> Result<Vec<RecentEntry>> {
+        let recent = |table| {
+            let mut qb = QueryBuilder::new(format!(
+                "SELECT entry FROM {} WHERE orphaned = 0 AND timestamp > ",
+                table
+            ));
+            qb.push_bind(timestamp(since)?)
+                .push(" ORDER BY timestamp DESC, id DESC LIMIT ")
+                .push_bind(to_i64(limit)?);
+            Result::Ok(qb)
+        };
+
+        let table = module_collection(module);
+        Ok(match module {
+            ScrapingModule::Transfer => self
+                .fetch(recent(table)?)
+                .await?
+                .into_iter()
+                .map(RecentEntry::Transfer)
+                .collect(),
+            ScrapingModule::RewardsSlashes => self
+                .fetch(recent(table)?)
+                .await?
+                .into_iter()
+                .map(RecentEntry::RewardSlash)
+                .collect(),
+            ScrapingModule::Nominations => self
+                .fetch(recent(table)?)
+                .await?
+                .into_iter()
+                .map(RecentEntry::Nomination)
+                .collect(),
+            _ => return Err(anyhow!("recent {:?} entries can not be fetched", module)),
+        })
+    }
+    async fn store_nomination_snapshot(
+        &self,
+        entry: &ContextData<'_, NominationSnapshot>,
+    ) -> Result<()> {
+        sqlx::query(&format!(
+            "INSERT INTO {} (stash, network, timestamp, entry) VALUES (?, ?, ?, ?)",
+            COLL_NOMINATION_SNAPSHOTS
+        ))
+        .bind(entry.context_id.stash.as_str())
+        .bind(entry.context_id.network.as_str())
+        .bind(timestamp(entry.timestamp)?)
+        .bind(serde_json::to_string(entry)?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn fetch_nomination_snapshots(
+        &self,
+        context: &Context,
+        limit: u64,
+    ) -> Result<Vec<ContextData<'static, NominationSnapshot>>> {
+        let mut qb = QueryBuilder::new(format!(
+            "SELECT entry FROM {} WHERE ",
+            COLL_NOMINATION_SNAPSHOTS
+        ));
+        push_contexts(&mut qb, std::slice::from_ref(context));
+        qb.push(" ORDER BY id DESC LIMIT ")
+            .push_bind(to_i64(limit)?);
+
+        self.fetch(qb).await
+    }
+    async fn store_raw_response(&self, response: &RawResponse, max_entries: u64) -> Result<()> {
+        sqlx::query(&format!(
+            "INSERT INTO {} (timestamp, url, request, body) VALUES (?, ?, ?, ?)",
+            COLL_RAW_RESPONSES
+        ))
+        .bind(timestamp(response.timestamp)?)
+        .bind(response.url.as_str())
+        .bind(response.request.as_str())
+        .bind(response.compressed.as_slice())
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(&format!(
+            "DELETE FROM {0} WHERE id NOT IN (SELECT id FROM {0} ORDER BY id DESC LIMIT ?)",
+            COLL_RAW_RESPONSES
+        ))
+        .bind(to_i64(max_entries)?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn fetch_raw_responses(
+        &self,
+        url: &str,
+        request: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<RawResponse>> {
+        let mut qb = QueryBuilder::new(format!(
+            "SELECT timestamp, url, request, body FROM {} WHERE url = ",
+            COLL_RAW_RESPONSES
+        ));
+        qb.push_bind(url);
+        if let Some(request) = request {
+            qb.push(" AND request = ").push_bind(request);
+        }
+        qb.push(" ORDER BY id DESC LIMIT ")
+            .push_bind(to_i64(limit)?);
+
+        qb.build_query_as::<(i64, String, String, Vec<u8>)>()
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|(timestamp, url, request, compressed)| {
+                Ok(RawResponse {
+                    timestamp: Timestamp::from(u64::try_from(timestamp)?),
+                    url,
+                    request,
+                    compressed,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_api::{NominationsPage, Response, RewardsSlashesPage, TransfersPage};
+    use crate::tests::entry;
+    use crate::{Database, StoredCount, TransferSort};
+
+    async fn db() -> Database {
+        Database::with_store(SqliteStore::new("sqlite::memory:").await.unwrap())
+    }
+
+    fn transfers(entries: &[(&str, &str, u64)]) -> Response<TransfersPage> {
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(
+            entries
+                .iter()
+                .map(|(index, amount, block)| Transfer {
+                    amount: (*amount).into(),
+                    extrinsic_index: index.to_string().into(),
+                    block_num: BlockNumber::from(*block),
+                    block_timestamp: Timestamp::from(*block),
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        resp
+    }
+
+    #[tokio::test]
+    async fn store_batch_transfers() {
+        let db = db().await;
+        let alice = Context::alice();
+
+        // Two identical transfers of the same batch call, only their events
+        // differ.
+        let mut resp = transfers(&[("100-1", "10", 1), ("100-1", "10", 1)]);
+        for (idx, transfer) in resp.data.transfers.as_mut().unwrap().iter_mut().enumerate() {
+            transfer.event_idx = Some(idx as i64 + 2);
+        }
+        assert_eq!(
+            db.store_transfer_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            2
+        );
+        assert_eq!(
+            db.store_transfer_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            0
+        );
+
+        // Transfers stored without an event index are not inserted again
+        // once it is known.
+        let mut legacy = transfers(&[("200-1", "10", 2)]);
+        assert_eq!(
+            db.store_transfer_event(&alice, &legacy)
+                .await
+                .unwrap()
+                .inserted,
+            1
+        );
+        legacy.data.transfers.as_mut().unwrap()[0].event_idx = Some(5);
+        assert_eq!(
+            db.store_transfer_event(&alice, &legacy)
+                .await
+                .unwrap()
+                .inserted,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn store_and_fetch_transfers() {
+        let db = db().await;
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        db.check_connection().await.unwrap();
+        db.create_indexes().await.unwrap();
+        // Idempotent
+        db.create_indexes().await.unwrap();
+
+        let resp = transfers(&[("1-1", "300", 1), ("2-1", "20", 2), ("3-1", "1000", 3)]);
+        assert_eq!(
+            db.store_transfer_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            3
+        );
+        assert_eq!(
+            db.store_transfer_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            0
+        );
+        assert_eq!(
+            db.store_transfer_event(&bob, &resp).await.unwrap().inserted,
+            3
+        );
+
+        let reader = db.reader();
+        let (from, to) = (Timestamp::from(0), Timestamp::from(i64::MAX as u64));
+        let alice_only = std::slice::from_ref(&alice);
+
+        // The most recent block first.
+        let stored = reader
+            .fetch_transfers(alice_only, from, to, None)
+            .await
+            .unwrap();
+        let indexes: Vec<String> = stored
+            .iter()
+            .map(|t| t.data.extrinsic_index.to_string())
+            .collect();
+        assert_eq!(indexes, vec!["3-1", "2-1", "1-1"]);
+        assert_eq!(stored[0].context_id, alice.id());
+
+        let sorted = reader
+            .fetch_transfers_sorted(
+                alice_only,
+                from,
+                to,
+                TransferSort {
+                    key: TransferSortKey::Amount,
+                    direction: SortDirection::Ascending,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        let amounts: Vec<&str> = sorted.iter().map(|t| t.data.amount.as_str()).collect();
+        assert_eq!(amounts, vec!["20", "300", "1000"]);
+
+        let paginated = reader
+            .fetch_transfers_paginated(alice_only, from, to, Some(Pagination { skip: 1, limit: 1 }))
+            .await
+            .unwrap();
+        assert_eq!(paginated[0].data.extrinsic_index.to_string(), "2-1");
+
+        assert_eq!(
+            reader
+                .count_transfers(&[alice.clone(), bob], from, to, None)
+                .await
+                .unwrap(),
+            6
+        );
+        assert_eq!(
+            reader
+                .count_transfers(alice_only, Timestamp::from(2), to, None)
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            reader.count_transfers(&[], from, to, None).await.unwrap(),
+            0
+        );
+        assert_eq!(
+            reader
+                .distinct_contexts(&ScrapingModule::Transfer)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            db.latest_block(&alice, &ScrapingModule::Transfer)
+                .await
+                .unwrap(),
+            Some(BlockNumber::from(3))
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_transfers_by_counterparty() {
+        let db = db().await;
+        let alice = Context::alice();
+
+        let mut resp = transfers(&[("1-1", "10", 1), ("2-1", "10", 2), ("3-1", "10", 3)]);
+        let stored = resp.data.transfers.as_mut().unwrap();
+        stored[0].from = "carol".to_string();
+        stored[1].to = "carol".to_string();
+        db.store_transfer_event(&alice, &resp).await.unwrap();
+
+        let res = db
+            .reader()
+            .fetch_transfers(
+                &[alice],
+                Timestamp::from(0),
+                Timestamp::from(i64::MAX as u64),
+                Some("carol"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn store_and_fetch_rewards_slashes_and_nominations() {
+        let db = db().await;
+        let alice = Context::alice();
+
+        let mut resp: Response<RewardsSlashesPage> = Default::default();
+        resp.data.list = Some(
+            (1..=3)
+                .map(|block| RewardSlash {
+                    block_num: BlockNumber::from(block),
+                    event_index: block.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        assert_eq!(
+            db.store_reward_slash_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            3
+        );
+        assert_eq!(
+            db.store_reward_slash_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            0
+        );
+
+        let reader = db.reader();
+        let alice_only = std::slice::from_ref(&alice);
+        let stored = reader
+            .fetch_rewards_slashes(alice_only, BlockNumber::from(2), BlockNumber::from(3))
+            .await
+            .unwrap();
+        let blocks: Vec<BlockNumber> = stored.iter().map(|rs| rs.data.block_num).collect();
+        assert_eq!(blocks, vec![BlockNumber::from(3), BlockNumber::from(2)]);
+        assert_eq!(
+            reader
+                .count_rewards_slashes_by_timestamp(
+                    alice_only,
+                    Timestamp::from(0),
+                    Timestamp::now()
+                )
+                .await
+                .unwrap(),
+            3
+        );
+
+        let mut resp: Response<NominationsPage> = Default::default();
+        resp.data.list = Some(vec![Default::default(); 2]);
+        resp.data.list.as_mut().unwrap()[1]
+            .stash_account_display
+            .address = "validator".to_string();
+        assert_eq!(
+            db.store_nomination_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            2
+        );
+        assert_eq!(
+            db.store_nomination_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            0
+        );
+
+        assert_eq!(reader.fetch_nominations(alice_only).await.unwrap().len(), 2);
+        assert_eq!(reader.count_nominations(alice_only).await.unwrap(), 2);
+        assert_eq!(
+            db.latest_block(&alice, &ScrapingModule::Nominations)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn store_rewards_slashes_without_extrinsic_hash() {
+        let db = db().await;
+        let alice = Context::alice();
+
+        // The v2 endpoints only return the event index.
+        let mut resp: Response<RewardsSlashesPage> = Default::default();
+        resp.data.list = Some(
+            ["100-2", "100-3"]
+                .iter()
+                .map(|event_index| RewardSlash {
+                    block_num: BlockNumber::from(100),
+                    event_index: event_index.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        assert_eq!(
+            db.store_reward_slash_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            2
+        );
+        assert_eq!(
+            db.store_reward_slash_event(&alice, &resp)
+                .await
+                .unwrap()
+                .inserted,
+            0
+        );
+
+        let stored = db
+            .reader()
+            .fetch_rewards_slashes(
+                std::slice::from_ref(&alice),
+                BlockNumber::from(0),
+                BlockNumber::from(100),
+            )
+            .await
+            .unwrap();
+        let mut indexes: Vec<&str> = stored.iter().map(|r| r.data.event_index.as_str()).collect();
+        indexes.sort_unstable();
+        assert_eq!(indexes, vec!["100-2", "100-3"]);
+
+        // Only the missing event is orphaned.
+        let mut current = resp.clone();
+        current.data.list.as_mut().unwrap().truncate(1);
+        assert_eq!(
+            db.mark_orphaned_rewards_slashes(&alice, BlockNumber::from(100), &[current])
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn mark_orphaned_transfers() {
+        let db = db().await;
+        let alice = Context::alice();
+
+        let resp = transfers(&[("1-1", "10", 1), ("2-1", "10", 2), ("3-1", "10", 3)]);
+        db.store_transfer_event(&alice, &resp).await.unwrap();
+
+        // The transfer of block 3 disappeared.
+        let current = transfers(&[("2-1", "10", 2)]);
+        assert_eq!(
+            db.mark_orphaned_transfers(&alice, BlockNumber::from(2), &[current])
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            db.latest_block(&alice, &ScrapingModule::Transfer)
+                .await
+                .unwrap(),
+            Some(BlockNumber::from(2))
+        );
+
+        // And reappeared.
+        assert_eq!(
+            db.mark_orphaned_transfers(&alice, BlockNumber::from(2), &[resp])
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            db.latest_block(&alice, &ScrapingModule::Transfer)
+                .await
+                .unwrap(),
+            Some(BlockNumber::from(3))
+        );
+    }
+
+    #[tokio::test]
+    async fn update_stored_transfer() {
+        let db = db().await.with_checksums(true);
+        let alice = Context::alice();
+
+        let resp = transfers(&[("1-1", "10", 1)]);
+        db.store_transfer_event(&alice, &resp).await.unwrap();
+
+        // Only the mutable fields of the stored transfer are updated.
+        let mut finalized = resp.clone();
+        let transfer = &mut finalized.data.transfers.as_mut().unwrap()[0];
+        transfer.success = true;
+        transfer.hash = "0x01".to_string();
+
+        let fetch = || async {
+            db.reader()
+                .fetch_transfers(
+                    std::slice::from_ref(&alice),
+                    Timestamp::from(0),
+                    Timestamp::from(i64::MAX as u64),
+                    None,
+                )
+                .await
+                .unwrap()
+        };
+
+        // Stored transfers are not changed by default.
+        db.store_transfer_event(&alice, &finalized).await.unwrap();
+        assert!(!fetch().await[0].data.success);
+
+        let updating = db.clone().with_transfer_updates(true);
+        assert_eq!(
+            updating
+                .store_transfer_event(&alice, &finalized)
+                .await
+                .unwrap()
+                .inserted,
+            0
+        );
+        let stored = fetch().await;
+        assert_eq!(stored.len(), 1);
+        assert!(stored[0].data.success);
+        assert!(stored[0].data.hash.is_empty());
+
+        // The checksum matches the updated data.
+        let report = db.verify_integrity(&alice).await.unwrap();
+        assert_eq!(report.checked, 1);
+        assert!(report.mismatched.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dead_letter_unstorable_entry() {
+        let db = db().await.with_dead_letter(1);
+        let alice = Context::alice();
+
+        // Block numbers beyond `i64::MAX` can not be stored in SQLite.
+        let resp = transfers(&[("1-1", "10", u64::MAX), ("2-1", "10", 2)]);
+        assert_eq!(
+            db.store_transfer_event(&alice, &resp).await.unwrap(),
+            StoredCount {
+                inserted: 1,
+                skipped: 1
+            }
+        );
+
+        // Entries of the dead letter table are skipped right away.
+        assert_eq!(
+            db.store_transfer_event(&alice, &resp).await.unwrap(),
+            StoredCount {
+                inserted: 0,
+                skipped: 1
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn transient_errors() {
+        let store = SqliteStore::new("sqlite::memory:").await.unwrap();
+
+        assert!(store.is_transient(&sqlx::Error::PoolTimedOut.into()));
+        assert!(store.is_transient(&anyhow::Error::from(sqlx::Error::PoolClosed).context("store")));
+        // The entry itself can not be stored.
+        assert!(!store.is_transient(&i64::try_from(u64::MAX).unwrap_err().into()));
+    }
+
+    #[tokio::test]
+    async fn leases_and_contexts() {
+        let db = db().await;
+
+        assert!(db.acquire_lease("fetcher", "a", 60).await.unwrap());
+        assert!(db.acquire_lease("fetcher", "a", 60).await.unwrap());
+        assert!(!db.acquire_lease("fetcher", "b", 60).await.unwrap());
+        // Expired leases can be taken over.
+        assert!(db.acquire_lease("other", "a", 0).await.unwrap());
+        assert!(db.acquire_lease("other", "b", 60).await.unwrap());
+
+        let alice = Context::alice();
+        let mut kusama = Context::bob();
+        kusama.network = Network::Kusama;
+
+        assert!(db.upsert_context(&alice).await.unwrap());
+        assert!(db.upsert_context(&kusama).await.unwrap());
+
+        let mut updated = alice.clone();
+        updated.description = "updated".to_string();
+        assert!(!db.upsert_context(&updated).await.unwrap());
+
+        assert_eq!(db.load_contexts().await.unwrap(), vec![kusama, updated]);
+    }
+
+    #[tokio::test]
+    async fn store_nomination_snapshots_on_change() {
+        let db = db().await;
+        let alice = Context::alice();
+        let nominations = |validators: &[&str]| {
+            let mut resp: Response<NominationsPage> = Default::default();
+            resp.data.list = Some(
+                validators
+                    .iter()
+                    .map(|address| {
+                        let mut nomination = Nomination::default();
+                        nomination.stash_account_display.address = address.to_string();
+                        nomination
+                    })
+                    .collect(),
+            );
+            resp
+        };
+
+        db.store_nomination_event(&alice, &nominations(&["a", "b"]))
+            .await
+            .unwrap();
+        // Unchanged nominations do not add a snapshot, even if reordered.
+        db.store_nomination_event(&alice, &nominations(&["b", "a"]))
+            .await
+            .unwrap();
+
+        let reader = db.reader();
+        let snapshots = reader.fetch_nomination_snapshots(&alice, 2).await.unwrap();
+        assert_eq!(snapshots.len(), 1);
+
+        db.store_nomination_event(&alice, &nominations(&["b", "c"]))
+            .await
+            .unwrap();
+
+        let snapshots = reader.fetch_nomination_snapshots(&alice, 2).await.unwrap();
+        let addresses: Vec<HashSet<&str>> = snapshots.iter().map(|s| s.data.addresses()).collect();
+        assert_eq!(
+            addresses,
+            vec![
+                vec!["b", "c"].into_iter().collect(),
+                vec!["a", "b"].into_iter().collect(),
+            ]
+        );
+        assert!(reader
+            .fetch_nomination_snapshots(&Context::bob(), 2)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // A response without a list means all validators were removed.
+        let stored = db
+            .store_nomination_event(&alice, &Response::default())
+            .await
+            .unwrap();
+        assert_eq!(stored, StoredCount::default());
+
+        let snapshots = reader.fetch_nomination_snapshots(&alice, 2).await.unwrap();
+        assert!(snapshots[0].data.validators.is_empty());
+        assert_eq!(snapshots[1].data.addresses().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn fetch_recent_entries() {
+        let store = SqliteStore::new("sqlite::memory:").await.unwrap();
+        store.create_indexes().await.unwrap();
+        let (alice, bob) = (Context::alice(), Context::bob());
+
+        fn stored_at<T: Clone>(context: &Context, stored: u64, data: T) -> ContextData<'_, T> {
+            ContextData {
+                timestamp: Timestamp::from(stored),
+                ..entry(context, data)
+            }
+        }
+
+        // Stored at 100, 200 and 300 respectively, spread across accounts.
+        let resp = transfers(&[("1-1", "10", 1), ("2-1", "10", 2), ("3-1", "10", 3)]);
+        for (transfer, (context, stored)) in resp.data.transfers.unwrap().into_iter().zip(vec![
+            (&alice, 100),
+            (&bob, 300),
+            (&alice, 200),
+        ]) {
+            store
+                .store_transfer(&stored_at(context, stored, transfer), false)
+                .await
+                .unwrap();
+        }
+        for (block, stored) in &[(1, 300), (2, 100)] {
+            let reward_slash = RewardSlash {
+                block_num: BlockNumber::from(*block),
+                event_index: block.to_string(),
+                ..Default::default()
+            };
+            store
+                .store_reward_slash(&stored_at(&alice, *stored, reward_slash))
+                .await
+                .unwrap();
+        }
+        for (context, stored) in &[(&alice, 100), (&bob, 250)] {
+            store
+                .store_nomination(&stored_at(context, *stored, Nomination::default()))
+                .await
+                .unwrap();
+        }
+
+        let recent = |module, since| {
+            let store = store.clone();
+            async move {
+                store
+                    .fetch_recent(&module, Timestamp::from(since), 10)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        // Only entries stored after the given time, the latest first.
+        let transfers: Vec<(String, u64)> = recent(ScrapingModule::Transfer, 100)
+            .await
+            .into_iter()
+            .map(|entry| match entry {
+                RecentEntry::Transfer(entry) => (
+                    entry.data.extrinsic_index.to_string(),
+                    entry.timestamp.as_secs(),
+                ),
+                other => panic!("unexpected entry {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            transfers,
+            vec![("2-1".to_string(), 300), ("3-1".to_string(), 200)]
+        );
+        assert!(recent(ScrapingModule::Transfer, 300).await.is_empty());
+
+        // At most the given amount of the latest entries.
+        let latest = store
+            .fetch_recent(&ScrapingModule::Transfer, Timestamp::from(0), 2)
+            .await
+            .unwrap();
+        let stored: Vec<u64> = latest.iter().map(|e| e.timestamp().as_secs()).collect();
+        assert_eq!(stored, vec![300, 200]);
+
+        let rewards_slashes = recent(ScrapingModule::RewardsSlashes, 0).await;
+        assert_eq!(rewards_slashes.len(), 2);
+        assert!(
+            matches!(&rewards_slashes[0], RecentEntry::RewardSlash(entry)
+            if entry.data.block_num == BlockNumber::from(1))
+        );
+        assert_eq!(rewards_slashes[1].timestamp(), Timestamp::from(100));
+
+        let nominations = recent(ScrapingModule::Nominations, 100).await;
+        assert_eq!(nominations.len(), 1);
+        assert!(matches!(nominations[0], RecentEntry::Nomination(_)));
+        assert_eq!(nominations[0].context_id(), &bob.id());
+
+        // Orphaned entries are excluded.
+        store
+            .mark_orphaned(&ScrapingModule::Transfer, &bob, BlockNumber::from(0), &[])
+            .await
+            .unwrap();
+        assert_eq!(recent(ScrapingModule::Transfer, 100).await.len(), 1);
+
+        assert!(store
+            .fetch_recent(&ScrapingModule::StakingActions, Timestamp::from(0), 10)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn verify_integrity() {
+        let store = SqliteStore::new("sqlite::memory:").await.unwrap();
+        let db = Database::with_store(store.clone()).with_checksums(true);
+        let alice = Context::alice();
+
+        let resp = transfers(&[("1-1", "10", 1), ("2-1", "10", 2)]);
+        db.store_transfer_event(&alice, &resp).await.unwrap();
+
+        // Tamper with one of the entries.
+        sqlx::query(&format!(
+            "UPDATE {} SET entry = json_set(entry, '$.data.amount', '1000')
+            WHERE entry_key LIKE '1-1/%'",
+            COLL_TRANSFER_RAW
+        ))
+        .execute(&store.pool)
+        .await
+        .unwrap();
+
+        let report = db.verify_integrity(&alice).await.unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.mismatched, vec![Bson::Int64(1)]);
+    }
+}
@@ -0,0 +1,334 @@
+use super::{mark_sent, pending_events, Notification, NotificationEvent, Publisher};
+use crate::rate_limit::RateLimiter;
+use crate::{Database, Result};
+use reqwest::Client;
+use sha2::{Digest, Sha256};
+use tokio::time::Duration;
+
+const PUBLISHER_REQUEST_TIMEOUT: u64 = 1;
+
+// Limits of the Discord webhook API, see
+// https://discord.com/developers/docs/resources/channel#embed-object-embed-limits
+const MAX_EMBEDS_PER_MESSAGE: usize = 10;
+const MAX_FIELDS_PER_EMBED: usize = 25;
+const MAX_CHARS_PER_MESSAGE: usize = 6000;
+const MAX_CONTENT_LEN: usize = 2000;
+const MAX_TITLE_LEN: usize = 256;
+const MAX_FIELD_NAME_LEN: usize = 256;
+const MAX_FIELD_VALUE_LEN: usize = 1024;
+
+/// Sends notable report events to a Discord channel via a webhook. Events
+/// are grouped into one embed per account and batched into as few messages as
+/// Discord's limits allow. Only events with an amount at or above the
+/// configured threshold are sent, each of them at most once per webhook.
+pub struct DiscordPublisher {
+    client: Client,
+    min_amount: f64,
+    db: Database,
+    rate_limiter: RateLimiter,
+}
+
+impl DiscordPublisher {
+    /// The sent events are tracked in the database, so they are not sent
+    /// again after a restart.
+    pub fn new(min_amount: f64, db: Database) -> Self {
+        DiscordPublisher {
+            client: Client::new(),
+            min_amount,
+            db,
+            rate_limiter: RateLimiter::every(Duration::from_secs(PUBLISHER_REQUEST_TIMEOUT)),
+        }
+    }
+    /// Limits the rate of messages, one every `PUBLISHER_REQUEST_TIMEOUT`
+    /// seconds by default.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+}
+
+/// Identifies the webhook in the database without storing its URL, which
+/// contains the secret token.
+fn destination(webhook_url: &str) -> String {
+    format!(
+        "discord/{}",
+        hex::encode(Sha256::digest(webhook_url.as_bytes()))
+    )
+}
+
+#[async_trait]
+impl Publisher for DiscordPublisher {
+    type Data = Notification;
+    type Info = DiscordUploadInfo;
+
+    async fn upload_data(&self, info: Self::Info, data: Self::Data) -> Result<()> {
+        let destination = destination(&info.webhook_url);
+
+        let events = pending_events(&self.db, &destination, self.min_amount, &data).await?;
+        if events.is_empty() {
+            debug!("No new events to notify about in the Discord channel");
+            return Ok(());
+        }
+
+        for message in messages(&data.title, embeds(&data.title, &events)) {
+            self.rate_limiter.until_ready().await;
+
+            self.client
+                .post(&info.webhook_url)
+                .json(&message)
+                .send()
+                .await?
+                .error_for_status()?;
+
+            // Only mark events as sent once the message was accepted.
+            let ids: Vec<&str> = message
+                .embeds
+                .iter()
+                .flat_map(|embed| embed.event_ids.iter().map(String::as_str))
+                .collect();
+            mark_sent(&self.db, &destination, &ids).await?;
+        }
+
+        Ok(())
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
+
+/// Groups the events into one embed per account, splitting them across
+/// multiple embeds if an account has more events than fit into one.
+fn embeds(title: &str, events: &[&NotificationEvent]) -> Vec<DiscordEmbed> {
+    let mut accounts: Vec<(&str, Vec<&NotificationEvent>)> = vec![];
+    for event in events {
+        match accounts
+            .iter_mut()
+            .find(|(account, _)| *account == event.account)
+        {
+            Some((_, account_events)) => account_events.push(event),
+            None => accounts.push((&event.account, vec![event])),
+        }
+    }
+
+    let mut embeds = vec![];
+    for (account, account_events) in accounts {
+        let mut embed = DiscordEmbed::new(account);
+
+        for event in account_events {
+            let field = DiscordEmbedField {
+                name: truncate(title, MAX_FIELD_NAME_LEN),
+                value: truncate(&event.summary, MAX_FIELD_VALUE_LEN),
+            };
+
+            if embed.fields.len() == MAX_FIELDS_PER_EMBED
+                || embed.len() + field.len() > MAX_CHARS_PER_MESSAGE
+            {
+                embeds.push(std::mem::replace(&mut embed, DiscordEmbed::new(account)));
+            }
+
+            embed.fields.push(field);
+            embed.event_ids.push(event.id.clone());
+        }
+
+        embeds.push(embed);
+    }
+
+    embeds
+}
+
+/// Batches the embeds into messages, respecting the amount of embeds and the
+/// total amount of characters a single message may contain. The title is sent
+/// as the content of each message.
+fn messages(title: &str, embeds: Vec<DiscordEmbed>) -> Vec<DiscordMessage> {
+    let mut messages: Vec<DiscordMessage> = vec![];
+
+    for embed in embeds {
+        match messages.last_mut() {
+            Some(message)
+                if message.embeds.len() < MAX_EMBEDS_PER_MESSAGE
+                    && message.len() + embed.len() <= MAX_CHARS_PER_MESSAGE =>
+            {
+                message.embeds.push(embed)
+            }
+            _ => messages.push(DiscordMessage {
+                content: truncate(title, MAX_CONTENT_LEN),
+                embeds: vec![embed],
+            }),
+        }
+    }
+
+    messages
+}
+
+#[derive(Debug, Serialize)]
+struct DiscordMessage {
+    content: String,
+    embeds: Vec<DiscordEmbed>,
+}
+
+impl DiscordMessage {
+    /// The amount of characters of the embeds, the content has its own limit.
+    fn len(&self) -> usize {
+        self.embeds.iter().map(|embed| embed.len()).sum()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DiscordEmbed {
+    title: String,
+    fields: Vec<DiscordEmbedField>,
+    /// The IDs of the events contained in the fields.
+    #[serde(skip)]
+    event_ids: Vec<String>,
+}
+
+impl DiscordEmbed {
+    fn new(title: &str) -> Self {
+        DiscordEmbed {
+            title: truncate(title, MAX_TITLE_LEN),
+            fields: vec![],
+            event_ids: vec![],
+        }
+    }
+    /// The amount of characters counted towards the message limit.
+    fn len(&self) -> usize {
+        self.title.chars().count() + self.fields.iter().map(|field| field.len()).sum::<usize>()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DiscordEmbedField {
+    name: String,
+    value: String,
+}
+
+impl DiscordEmbedField {
+    fn len(&self) -> usize {
+        self.name.chars().count() + self.value.chars().count()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiscordUploadInfo {
+    pub webhook_url: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_api::TokenAmount;
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn event(id: usize, account: &str, amount: u128) -> NotificationEvent {
+        NotificationEvent {
+            id: id.to_string(),
+            account: account.to_string(),
+            // Whole tokens.
+            amount: TokenAmount::new(amount, 0),
+            summary: format!("Event {}", id),
+        }
+    }
+
+    #[test]
+    fn batch_events_within_limits() {
+        let events: Vec<NotificationEvent> = (0..30)
+            .map(|id| event(id, "Alice", 1))
+            .chain((30..31).map(|id| event(id, "Bob", 1)))
+            .collect();
+        let events: Vec<&NotificationEvent> = events.iter().collect();
+
+        // One embed per account, split once the field limit is reached.
+        let batched = embeds("Transfers", &events);
+        assert_eq!(batched.len(), 3);
+        assert_eq!(batched[0].title, "Alice");
+        assert_eq!(batched[0].fields.len(), MAX_FIELDS_PER_EMBED);
+        assert_eq!(batched[1].title, "Alice");
+        assert_eq!(batched[1].fields.len(), 5);
+        assert_eq!(batched[2].title, "Bob");
+        assert_eq!(batched[2].fields[0].value, "Event 30");
+        assert_eq!(messages("Transfers", batched).len(), 1);
+
+        // Long summaries are split by the character limit.
+        let mut long = event(0, "Alice", 1);
+        long.summary = "x".repeat(2_000);
+        let events = vec![&long; 12];
+
+        let batched = embeds("Transfers", &events);
+        assert!(batched
+            .iter()
+            .all(|embed| embed.len() <= MAX_CHARS_PER_MESSAGE));
+        assert_eq!(batched[0].fields[0].value.len(), MAX_FIELD_VALUE_LEN);
+
+        let batched = messages("Transfers", batched);
+        assert_eq!(batched.len(), 3);
+        assert!(batched
+            .iter()
+            .all(|message| message.len() <= MAX_CHARS_PER_MESSAGE));
+    }
+
+    #[test]
+    fn limit_message_content() {
+        let event = event(0, "Alice", 1);
+
+        let title = "x".repeat(3_000);
+        let batched = messages(&title, embeds(&title, &[&event]));
+        assert_eq!(batched[0].content.chars().count(), MAX_CONTENT_LEN);
+    }
+
+    #[test]
+    fn hide_webhook_url() {
+        let url = "https://discord.com/api/webhooks/1/secret";
+        assert!(destination(url).starts_with("discord/"));
+        assert!(!destination(url).contains("secret"));
+        assert_ne!(
+            destination(url),
+            destination("https://discord.com/api/webhooks/2/secret")
+        );
+    }
+
+    #[tokio::test]
+    async fn upload_to_mock_webhook() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "content": "Transfers",
+                "embeds": [{
+                    "title": "Alice",
+                    "fields": [{ "name": "Transfers", "value": "Event 1" }],
+                }],
+            })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let db = crate::tests::memory_db().await;
+        let publisher = DiscordPublisher::new(100.0, db.clone());
+        let info = DiscordUploadInfo {
+            webhook_url: server.uri(),
+        };
+        let notification = Notification {
+            title: "Transfers".to_string(),
+            events: vec![event(0, "Alice", 50), event(1, "Alice", 100)],
+        };
+
+        publisher
+            .upload_data(info.clone(), notification.clone())
+            .await
+            .unwrap();
+
+        // Events which were already sent are skipped, even by a new publisher
+        // with the same database.
+        publisher
+            .upload_data(info.clone(), notification.clone())
+            .await
+            .unwrap();
+        DiscordPublisher::new(100.0, db)
+            .upload_data(info, notification)
+            .await
+            .unwrap();
+    }
+}
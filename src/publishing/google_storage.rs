@@ -0,0 +1,538 @@
+use super::Publisher;
+use crate::rate_limit::RateLimiter;
+use crate::Result;
+use reqwest::header::{CONTENT_RANGE, CONTENT_TYPE, LOCATION, RANGE};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use tokio::time::{sleep, Duration};
+use yup_oauth2::authenticator::DefaultAuthenticator;
+use yup_oauth2::{read_service_account_key, ServiceAccountAuthenticator};
+
+const PUBLISHER_REQUEST_TIMEOUT: u64 = 1;
+const UPLOAD_TIMEOUT: u64 = 360;
+const DEFAULT_BASE_URL: &str = "https://storage.googleapis.com";
+
+/// Reports are uploaded to Cloud Storage buckets, which requires no access to
+/// Google Drive itself.
+const DEFAULT_SCOPES: [&str; 1] = ["https://www.googleapis.com/auth/devstorage.read_write"];
+
+/// The amount of retries of a failed request, waiting `RETRY_DELAY` seconds
+/// before the first retry and doubling the delay with each further one.
+const MAX_RETRIES: u32 = 5;
+const RETRY_DELAY: u64 = 1;
+
+/// Bodies above this size are uploaded in chunks via a resumable upload.
+const RESUMABLE_UPLOAD_THRESHOLD: usize = 5 * 1024 * 1024;
+/// Must be a multiple of 256 KiB, as required by Cloud Storage.
+const UPLOAD_CHUNK_SIZE: usize = 32 * 256 * 1024;
+
+/// The status code of a resumable upload which has not been completed yet.
+const RESUME_INCOMPLETE: u16 = 308;
+
+pub struct GoogleStorage {
+    client: Client,
+    auth: Option<DefaultAuthenticator>,
+    scopes: Vec<String>,
+    base_url: String,
+    retry_delay: Duration,
+    resumable_threshold: usize,
+    chunk_size: usize,
+    rate_limiter: RateLimiter,
+}
+
+impl GoogleStorage {
+    pub async fn new(path: &str) -> Result<Self> {
+        Self::with_scopes(path, &DEFAULT_SCOPES).await
+    }
+    /// Authenticates with the given OAuth scopes instead of `DEFAULT_SCOPES`.
+    pub async fn with_scopes<S: AsRef<str>>(path: &str, scopes: &[S]) -> Result<Self> {
+        let key = read_service_account_key(path).await?;
+        let auth = ServiceAccountAuthenticator::builder(key).build().await?;
+
+        let mut storage = Self::with_base_url(DEFAULT_BASE_URL)?;
+        storage.scopes = scopes.iter().map(|s| s.as_ref().to_string()).collect();
+        storage.auth = Some(auth);
+
+        if storage.token(false).await?.is_empty() {
+            return Err(anyhow!("returned Google auth token is invalid"));
+        }
+
+        Ok(storage)
+    }
+    /// Creates an unauthenticated client, which is only useful for testing
+    /// with a mock server.
+    fn with_base_url(base_url: &str) -> Result<Self> {
+        Ok(GoogleStorage {
+            client: Client::builder()
+                .timeout(Duration::from_secs(UPLOAD_TIMEOUT))
+                .build()?,
+            auth: None,
+            scopes: vec![],
+            base_url: base_url.trim_end_matches('/').to_string(),
+            retry_delay: Duration::from_secs(RETRY_DELAY),
+            resumable_threshold: RESUMABLE_UPLOAD_THRESHOLD,
+            chunk_size: UPLOAD_CHUNK_SIZE,
+            rate_limiter: RateLimiter::every(Duration::from_secs(PUBLISHER_REQUEST_TIMEOUT)),
+        })
+    }
+    /// Limits the rate of uploads, one every `PUBLISHER_REQUEST_TIMEOUT`
+    /// seconds by default.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+    /// Returns the access token, which is cached by the authenticator and only
+    /// fetched again once expired, unless `force_refresh` is set.
+    async fn token(&self, force_refresh: bool) -> Result<String> {
+        let auth = match &self.auth {
+            Some(auth) => auth,
+            None => return Ok(String::new()),
+        };
+
+        let token = if force_refresh {
+            auth.force_refreshed_token(&self.scopes).await?
+        } else {
+            auth.token(&self.scopes).await?
+        };
+
+        Ok(token.as_str().to_string())
+    }
+    /// Sends the request created by `request`, retrying on connection errors
+    /// and on server errors with an exponential backoff. If the token is
+    /// rejected, e.g. because it expired during a long run, it is refreshed
+    /// once.
+    async fn send<F>(&self, request: F) -> Result<Response>
+    where
+        F: Fn(&Client) -> RequestBuilder,
+    {
+        let mut retries = 0;
+        let mut refreshed = false;
+
+        loop {
+            let token = self.token(false).await?;
+            let err = match request(&self.client).bearer_auth(&token).send().await {
+                Ok(resp) if resp.status() == StatusCode::UNAUTHORIZED && !refreshed => {
+                    warn!("Google auth token was rejected, refreshing");
+                    self.token(true).await?;
+                    refreshed = true;
+                    continue;
+                }
+                Ok(resp)
+                    if resp.status().is_server_error()
+                        || resp.status() == StatusCode::TOO_MANY_REQUESTS =>
+                {
+                    anyhow!(
+                        "upload failed with status {}: {}",
+                        resp.status(),
+                        resp.text().await.unwrap_or_default()
+                    )
+                }
+                Ok(resp) => return Ok(resp),
+                Err(err) => err.into(),
+            };
+
+            if retries == MAX_RETRIES {
+                return Err(err);
+            }
+
+            let delay = self.retry_delay * 2_u32.pow(retries);
+            retries += 1;
+
+            warn!(
+                error = ?err,
+                "Failed to upload to Google Cloud Storage, retrying in {:?} ({}/{})",
+                delay,
+                retries,
+                MAX_RETRIES
+            );
+            sleep(delay).await;
+        }
+    }
+    async fn upload(&self, bucket: &str, data: &GoogleStoragePayload) -> Result<()> {
+        let url = format!("{}/upload/storage/v1/b/{}/o", self.base_url, bucket);
+
+        self.send(|client| {
+            client
+                .post(&url)
+                .query(&upload_query(data, "media"))
+                .header(CONTENT_TYPE, &data.mime_type)
+                .body(data.body.clone())
+        })
+        .await?
+        .error_for_status()?;
+
+        Ok(())
+    }
+    /// Uploads the body in chunks, so a failed request only requires the
+    /// affected chunk to be sent again.
+    async fn upload_resumable(&self, bucket: &str, data: &GoogleStoragePayload) -> Result<()> {
+        let url = format!("{}/upload/storage/v1/b/{}/o", self.base_url, bucket);
+        let total = data.body.len();
+
+        let session = self
+            .send(|client| {
+                client
+                    .post(&url)
+                    .query(&upload_query(data, "resumable"))
+                    .header("X-Upload-Content-Type", &data.mime_type)
+                    .header("X-Upload-Content-Length", total)
+            })
+            .await?
+            .error_for_status()?
+            .headers()
+            .get(LOCATION)
+            .ok_or_else(|| anyhow!("no resumable upload session was returned"))?
+            .to_str()?
+            .to_string();
+
+        let mut start = 0;
+        // Chunks which were sent without any progress, see `MAX_RETRIES`.
+        let mut retries = 0;
+        while start < total {
+            let end = (start + self.chunk_size).min(total);
+            let chunk = &data.body[start..end];
+
+            let resp = self
+                .send(|client| {
+                    client
+                        .put(&session)
+                        .header(
+                            CONTENT_RANGE,
+                            format!("bytes {}-{}/{}", start, end - 1, total),
+                        )
+                        .body(chunk.to_vec())
+                })
+                .await?;
+
+            if resp.status().as_u16() != RESUME_INCOMPLETE {
+                resp.error_for_status()?;
+                return Ok(());
+            }
+
+            // Continue after the last byte persisted by Cloud Storage, which
+            // is not necessarily the end of the chunk.
+            let persisted = match resp.headers().get(RANGE) {
+                Some(range) => persisted_bytes(range.to_str()?)?,
+                None => 0,
+            };
+            if persisted > start {
+                start = persisted;
+                retries = 0;
+                continue;
+            }
+
+            // Nothing of the chunk was persisted, so it is sent again.
+            if retries == MAX_RETRIES {
+                return Err(anyhow!(
+                    "resumable upload made no progress at byte {} of {}",
+                    start,
+                    total
+                ));
+            }
+
+            let delay = self.retry_delay * 2_u32.pow(retries);
+            retries += 1;
+
+            warn!(
+                "Resumable upload made no progress at byte {} of {}, retrying in {:?} ({}/{})",
+                start, total, delay, retries, MAX_RETRIES
+            );
+            sleep(delay).await;
+            start = persisted;
+        }
+
+        Err(anyhow!(
+            "resumable upload was not completed after all chunks were sent"
+        ))
+    }
+}
+
+fn upload_query<'a>(
+    data: &'a GoogleStoragePayload,
+    upload_type: &'a str,
+) -> Vec<(&'a str, &'a str)> {
+    let mut query = vec![("name", data.name.as_str()), ("uploadType", upload_type)];
+    if data.is_public {
+        query.push(("predefinedAcl", "publicRead"));
+    }
+
+    query
+}
+
+/// Parses the `Range` header of a resumable upload, e.g. `bytes=0-1023`, into
+/// the amount of persisted bytes.
+fn persisted_bytes(range: &str) -> Result<usize> {
+    let last = range
+        .strip_prefix("bytes=0-")
+        .ok_or_else(|| anyhow!("invalid range of resumable upload: {}", range))?;
+
+    Ok(last.parse::<usize>()? + 1)
+}
+
+#[async_trait]
+impl Publisher for GoogleStorage {
+    type Data = GoogleStoragePayload;
+    type Info = GoogleStorageUploadInfo;
+
+    async fn upload_data(&self, info: Self::Info, mut data: Self::Data) -> Result<()> {
+        self.rate_limiter.until_ready().await;
+
+        data.name = info.object_name(&data);
+
+        if data.body.len() > self.resumable_threshold {
+            self.upload_resumable(&info.bucket_name, &data).await
+        } else {
+            self.upload(&info.bucket_name, &data).await
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoogleStoragePayload {
+    pub name: String,
+    pub mime_type: String,
+    pub body: Vec<u8>,
+    pub is_public: bool,
+    /// The report module which generated the report, e.g. `transfers`.
+    pub module: String,
+}
+
+// TODO: Rename, reference "config"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoogleStorageUploadInfo {
+    pub bucket_name: String,
+    /// Prepended to the name of each report, e.g. `reports/{module}/`.
+    /// `{module}` is replaced with the report module.
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
+impl GoogleStorageUploadInfo {
+    /// The key of the report within the bucket, i.e. its name with the
+    /// prefix prepended.
+    pub fn object_name(&self, data: &GoogleStoragePayload) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}{}", prefix.replace("{module}", &data.module), data.name),
+            None => data.name.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::Instant;
+    use wiremock::matchers::{header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn storage(server: &MockServer) -> GoogleStorage {
+        let mut storage = GoogleStorage::with_base_url(&server.uri()).unwrap();
+        storage.retry_delay = Duration::ZERO;
+        storage
+    }
+
+    fn payload(body: &[u8]) -> GoogleStoragePayload {
+        GoogleStoragePayload {
+            name: "report_transfer.csv".to_string(),
+            mime_type: "text/csv".to_string(),
+            body: body.to_vec(),
+            is_public: false,
+            module: "transfers".to_string(),
+        }
+    }
+
+    fn info() -> GoogleStorageUploadInfo {
+        GoogleStorageUploadInfo {
+            bucket_name: "bucket".to_string(),
+            prefix: None,
+        }
+    }
+
+    #[test]
+    fn parse_persisted_bytes() {
+        assert_eq!(persisted_bytes("bytes=0-1023").unwrap(), 1024);
+        assert!(persisted_bytes("bytes=10-20").is_err());
+        assert!(persisted_bytes("invalid").is_err());
+    }
+
+    #[tokio::test]
+    async fn retry_failed_upload() {
+        let server = MockServer::start().await;
+
+        // A server error and a rejected token are both recovered from.
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/upload/storage/v1/b/bucket/o"))
+            .and(query_param("uploadType", "media"))
+            .and(query_param("name", "report_transfer.csv"))
+            .and(header("Content-Type", "text/csv"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        storage(&server)
+            .upload_data(info(), payload(b"Network\npolkadot\n"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn give_up_after_max_retries() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(u64::from(MAX_RETRIES) + 1)
+            .mount(&server)
+            .await;
+
+        assert!(storage(&server)
+            .upload_data(info(), payload(b"Network\npolkadot\n"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn space_uploads_apart() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let timeout = Duration::from_millis(200);
+        let storage = storage(&server).with_rate_limiter(RateLimiter::every(timeout));
+
+        let start = Instant::now();
+        storage
+            .upload_data(info(), payload(b"Network\npolkadot\n"))
+            .await
+            .unwrap();
+        assert!(start.elapsed() < timeout);
+
+        storage
+            .upload_data(info(), payload(b"Network\npolkadot\n"))
+            .await
+            .unwrap();
+        assert!(start.elapsed() >= timeout);
+    }
+
+    #[tokio::test]
+    async fn upload_with_prefix() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/upload/storage/v1/b/bucket/o"))
+            .and(query_param("name", "reports/transfers/report_transfer.csv"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let prefixed = GoogleStorageUploadInfo {
+            prefix: Some("reports/{module}/".to_string()),
+            ..info()
+        };
+        storage(&server)
+            .upload_data(prefixed, payload(b"Network\npolkadot\n"))
+            .await
+            .unwrap();
+
+        // At the root of the bucket by default.
+        assert_eq!(info().object_name(&payload(b"")), "report_transfer.csv");
+    }
+
+    #[tokio::test]
+    async fn chunked_upload() {
+        let server = MockServer::start().await;
+        let session = format!("{}/session", server.uri());
+
+        Mock::given(method("POST"))
+            .and(path("/upload/storage/v1/b/bucket/o"))
+            .and(query_param("uploadType", "resumable"))
+            .and(header("X-Upload-Content-Type", "text/csv"))
+            .respond_with(ResponseTemplate::new(200).insert_header("Location", session.as_str()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/session"))
+            .and(header("Content-Range", "bytes 0-3/10"))
+            .respond_with(
+                ResponseTemplate::new(RESUME_INCOMPLETE).insert_header("Range", "bytes=0-3"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        // Only part of the second chunk is persisted.
+        Mock::given(method("PUT"))
+            .and(path("/session"))
+            .and(header("Content-Range", "bytes 4-7/10"))
+            .respond_with(
+                ResponseTemplate::new(RESUME_INCOMPLETE).insert_header("Range", "bytes=0-5"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/session"))
+            .and(header("Content-Range", "bytes 6-9/10"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut storage = storage(&server);
+        storage.resumable_threshold = 4;
+        storage.chunk_size = 4;
+
+        storage
+            .upload_data(info(), payload(b"0123456789"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn give_up_chunked_upload_without_progress() {
+        let server = MockServer::start().await;
+        let session = format!("{}/session", server.uri());
+
+        Mock::given(method("POST"))
+            .and(path("/upload/storage/v1/b/bucket/o"))
+            .and(query_param("uploadType", "resumable"))
+            .respond_with(ResponseTemplate::new(200).insert_header("Location", session.as_str()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // Nothing is ever persisted, i.e. no `Range` header is returned.
+        Mock::given(method("PUT"))
+            .and(path("/session"))
+            .and(header("Content-Range", "bytes 0-3/10"))
+            .respond_with(ResponseTemplate::new(RESUME_INCOMPLETE))
+            .expect(MAX_RETRIES as u64 + 1)
+            .mount(&server)
+            .await;
+
+        let mut storage = storage(&server);
+        storage.resumable_threshold = 4;
+        storage.chunk_size = 4;
+
+        assert!(storage
+            .upload_data(info(), payload(b"0123456789"))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use super::{mark_sent, pending_events, Notification, NotificationEvent, Publisher};
+use crate::rate_limit::RateLimiter;
+use crate::{Database, Result, Timestamp};
+use reqwest::{Client, Url};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::time::Duration;
+
+const PUBLISHER_REQUEST_TIMEOUT: u64 = 1;
+
+// Matrix events may be at most 64 KiB, including the event envelope, so the
+// body of a single message is kept well below that.
+const MAX_BODY_LEN: usize = 32_768;
+// Keeps a single event line (or title) within the body limit.
+const MAX_LINE_LEN: usize = 1024;
+
+/// Sends a summary of notable report events to a Matrix room. Only events
+/// with an amount at or above the configured threshold are sent, each of
+/// them at most once per room. Events which do not fit into a single message
+/// are split across multiple messages.
+pub struct MatrixPublisher {
+    client: Client,
+    homeserver: Url,
+    access_token: String,
+    min_amount: f64,
+    db: Database,
+    txn_counter: AtomicU64,
+    rate_limiter: RateLimiter,
+}
+
+impl MatrixPublisher {
+    /// The sent events are tracked in the database, so they are not sent
+    /// again after a restart.
+    pub fn new(
+        homeserver: &str,
+        access_token: &str,
+        min_amount: f64,
+        db: Database,
+    ) -> Result<Self> {
+        Ok(MatrixPublisher {
+            client: Client::new(),
+            homeserver: Url::parse(homeserver)?,
+            access_token: access_token.to_string(),
+            min_amount,
+            db,
+            txn_counter: AtomicU64::new(0),
+            rate_limiter: RateLimiter::every(Duration::from_secs(PUBLISHER_REQUEST_TIMEOUT)),
+        })
+    }
+    /// Limits the rate of messages, one every `PUBLISHER_REQUEST_TIMEOUT`
+    /// seconds by default.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+    fn header(&self, title: &str, count: usize) -> String {
+        format!(
+            "{}: {} new event(s) of at least {}\n",
+            truncate(title, MAX_LINE_LEN),
+            count,
+            self.min_amount
+        )
+    }
+    fn line(event: &NotificationEvent) -> String {
+        format!("- {}\n", truncate(&event.summary, MAX_LINE_LEN))
+    }
+    fn format_message(&self, title: &str, events: &[&NotificationEvent]) -> String {
+        let mut message = self.header(title, events.len());
+        for event in events {
+            message.push_str(&Self::line(event));
+        }
+
+        message
+    }
+    /// Splits the events into batches whose message body stays within
+    /// `MAX_BODY_LEN` bytes.
+    fn batches<'a, 'b>(
+        &self,
+        title: &str,
+        events: &'b [&'a NotificationEvent],
+    ) -> Vec<&'b [&'a NotificationEvent]> {
+        // The header of the full list is at least as long as the header of
+        // any batch.
+        let header_len = self.header(title, events.len()).len();
+
+        let mut batches = vec![];
+        let mut start = 0;
+        let mut len = header_len;
+        for (idx, event) in events.iter().enumerate() {
+            let line_len = Self::line(event).len();
+            if idx > start && len + line_len > MAX_BODY_LEN {
+                batches.push(&events[start..idx]);
+                start = idx;
+                len = header_len;
+            }
+
+            len += line_len;
+        }
+
+        if start < events.len() {
+            batches.push(&events[start..]);
+        }
+
+        batches
+    }
+    fn message_url(&self, room_id: &str) -> Result<Url> {
+        // The transaction ID must be unique per access token, so repeated
+        // requests are not treated as retransmissions.
+        let txn_id = format!(
+            "{}-{}",
+            Timestamp::now(),
+            self.txn_counter.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let mut url = self.homeserver.clone();
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("invalid Matrix homeserver URL"))?
+            .pop_if_empty()
+            .extend(&["_matrix", "client", "v3", "rooms"])
+            .push(room_id)
+            .extend(&["send", "m.room.message"])
+            .push(&txn_id);
+
+        Ok(url)
+    }
+}
+
+#[async_trait]
+impl Publisher for MatrixPublisher {
+    type Data = Notification;
+    type Info = MatrixUploadInfo;
+
+    async fn upload_data(&self, info: Self::Info, data: Self::Data) -> Result<()> {
+        let destination = format!("matrix/{}", info.room_id);
+
+        let events = pending_events(&self.db, &destination, self.min_amount, &data).await?;
+        if events.is_empty() {
+            debug!("No new events to notify about in the Matrix room");
+            return Ok(());
+        }
+
+        for batch in self.batches(&data.title, &events) {
+            self.rate_limiter.until_ready().await;
+
+            self.client
+                .put(self.message_url(&info.room_id)?)
+                .bearer_auth(&self.access_token)
+                .json(&MatrixMessage {
+                    msgtype: "m.text",
+                    body: self.format_message(&data.title, batch),
+                })
+                .send()
+                .await?
+                .error_for_status()?;
+
+            // Only mark events as sent once the message was accepted.
+            let ids: Vec<&str> = batch.iter().map(|event| event.id.as_str()).collect();
+            mark_sent(&self.db, &destination, &ids).await?;
+        }
+
+        Ok(())
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
+
+#[derive(Serialize)]
+struct MatrixMessage {
+    msgtype: &'static str,
+    body: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatrixUploadInfo {
+    pub room_id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_api::TokenAmount;
+    use crate::tests::memory_db;
+    use wiremock::matchers::{method, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn event(id: &str, amount: u128) -> NotificationEvent {
+        NotificationEvent {
+            id: id.to_string(),
+            account: "Alice".to_string(),
+            // Whole tokens.
+            amount: TokenAmount::new(amount, 0),
+            summary: format!("Event {}", id),
+        }
+    }
+
+    #[tokio::test]
+    async fn pending_events_threshold_and_dedup() {
+        let db = memory_db().await;
+        let publisher =
+            MatrixPublisher::new("https://matrix.org", "token", 100.0, db.clone()).unwrap();
+        let notification = Notification {
+            title: "Transfers".to_string(),
+            events: vec![event("a", 50), event("b", 100), event("c", 1_000)],
+        };
+
+        let pending = pending_events(&db, "matrix/room", 100.0, &notification)
+            .await
+            .unwrap();
+        assert_eq!(
+            pending,
+            vec![&notification.events[1], &notification.events[2]]
+        );
+        assert_eq!(
+            publisher.format_message(&notification.title, &pending),
+            "Transfers: 2 new event(s) of at least 100\n- Event b\n- Event c\n"
+        );
+
+        // Events which were already sent are skipped, but only for the same
+        // destination.
+        db.mark_notified("matrix/room", "b").await.unwrap();
+        let pending = pending_events(&db, "matrix/room", 100.0, &notification)
+            .await
+            .unwrap();
+        assert_eq!(pending, vec![&notification.events[2]]);
+
+        let pending = pending_events(&db, "matrix/other", 100.0, &notification)
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn split_long_messages() {
+        let publisher =
+            MatrixPublisher::new("https://matrix.org", "token", 0.0, memory_db().await).unwrap();
+
+        let mut events: Vec<NotificationEvent> =
+            (0..100).map(|id| event(&id.to_string(), 1)).collect();
+        for event in &mut events {
+            event.summary = "x".repeat(2_000);
+        }
+        let events: Vec<&NotificationEvent> = events.iter().collect();
+
+        let batches = publisher.batches("Transfers", &events);
+        assert!(batches.len() > 1);
+        assert_eq!(batches.iter().map(|batch| batch.len()).sum::<usize>(), 100);
+        for batch in batches {
+            let body = publisher.format_message("Transfers", batch);
+            assert!(body.len() <= MAX_BODY_LEN);
+        }
+
+        // A single event always fits.
+        assert_eq!(publisher.batches("Transfers", &events[..1]).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn skip_sent_events_after_restart() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("PUT"))
+            .and(path_regex(
+                "^/_matrix/client/v3/rooms/.*/send/m.room.message/",
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let db = memory_db().await;
+        let info = MatrixUploadInfo {
+            room_id: "!room:matrix.org".to_string(),
+        };
+        let notification = Notification {
+            title: "Transfers".to_string(),
+            events: vec![event("a", 100)],
+        };
+
+        MatrixPublisher::new(&server.uri(), "token", 0.0, db.clone())
+            .unwrap()
+            .upload_data(info.clone(), notification.clone())
+            .await
+            .unwrap();
+
+        // A new publisher with the same database does not send it again.
+        MatrixPublisher::new(&server.uri(), "token", 0.0, db)
+            .unwrap()
+            .upload_data(info, notification)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn message_url() {
+        let publisher =
+            MatrixPublisher::new("https://matrix.org/", "token", 0.0, memory_db().await).unwrap();
+        let url = publisher.message_url("!room:matrix.org").unwrap();
+
+        assert!(url.as_str().starts_with(
+            "https://matrix.org/_matrix/client/v3/rooms/!room:matrix.org/send/m.room.message/"
+        ));
+    }
+}
@@ -1,7 +1,14 @@
-use crate::Result;
-mod google_drive;
+use crate::chain_api::TokenAmount;
+use crate::{Database, Result};
+mod discord;
+mod google_storage;
+mod matrix;
+mod stdout;
 
-pub use self::google_drive::{GoogleDrive, GoogleDriveUploadInfo, GoogleStoragePayload};
+pub use self::discord::{DiscordPublisher, DiscordUploadInfo};
+pub use self::google_storage::{GoogleStorage, GoogleStoragePayload, GoogleStorageUploadInfo};
+pub use self::matrix::{MatrixPublisher, MatrixUploadInfo};
+pub use self::stdout::StdoutPublisher;
 
 #[async_trait]
 pub trait Publisher {
@@ -11,3 +18,51 @@ pub trait Publisher {
 
     async fn upload_data(&self, info: Self::Info, data: Self::Data) -> Result<()>;
 }
+
+/// A summary of a report, used by publishers which notify about notable events
+/// rather than uploading the full report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub title: String,
+    pub events: Vec<NotificationEvent>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotificationEvent {
+    /// Uniquely identifies the event, so it's only notified about once.
+    pub id: String,
+    /// The description of the monitored account the event belongs to.
+    pub account: String,
+    /// The amount in whole tokens, compared against notification thresholds.
+    pub amount: TokenAmount,
+    pub summary: String,
+}
+
+/// Returns the events which are worth notifying the destination about,
+/// meaning those at or above the threshold which have not been sent to it
+/// before. Sent events are tracked in the database, so they are not sent again
+/// after a restart.
+async fn pending_events<'a>(
+    db: &Database,
+    destination: &str,
+    min_amount: f64,
+    data: &'a Notification,
+) -> Result<Vec<&'a NotificationEvent>> {
+    let mut pending = vec![];
+    for event in &data.events {
+        if event.amount.at_least(min_amount) && !db.is_notified(destination, &event.id).await? {
+            pending.push(event);
+        }
+    }
+
+    Ok(pending)
+}
+
+/// Records the events as sent to the destination, see `pending_events`.
+async fn mark_sent(db: &Database, destination: &str, ids: &[&str]) -> Result<()> {
+    for id in ids {
+        db.mark_notified(destination, id).await?;
+    }
+
+    Ok(())
+}
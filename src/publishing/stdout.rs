@@ -0,0 +1,105 @@
+use super::{GoogleStoragePayload, Publisher};
+use crate::Result;
+use std::io::{self, Write};
+
+/// Writes the reports verbatim to stdout, e.g. to pipe them into other tools.
+/// The bytes are not converted, so binary reports are written unchanged.
+/// Optionally, each report is preceded by a header with its name on stderr,
+/// which keeps stdout limited to the report contents.
+pub struct StdoutPublisher {
+    header: bool,
+}
+
+impl StdoutPublisher {
+    pub fn new() -> Self {
+        StdoutPublisher { header: false }
+    }
+    /// Writes a header with the name, type and size of each report to stderr.
+    pub fn with_header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+}
+
+impl Default for StdoutPublisher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn header(data: &GoogleStoragePayload) -> String {
+    format!(
+        "==> {} ({}, {} bytes) <==\n",
+        data.name,
+        data.mime_type,
+        data.body.len()
+    )
+}
+
+/// Writes the report, and its header if given. Both writers are flushed, so
+/// reports of concurrent modules are not interleaved with each other.
+fn write_report<W: Write, H: Write>(
+    out: &mut W,
+    header_out: Option<&mut H>,
+    data: &GoogleStoragePayload,
+) -> Result<()> {
+    if let Some(header_out) = header_out {
+        header_out.write_all(header(data).as_bytes())?;
+        header_out.flush()?;
+    }
+
+    out.write_all(&data.body)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl Publisher for StdoutPublisher {
+    type Data = GoogleStoragePayload;
+    type Info = ();
+
+    async fn upload_data(&self, _info: Self::Info, data: Self::Data) -> Result<()> {
+        let header = self.header;
+
+        // Writing blocks if the reading end of the pipe is slow.
+        tokio::task::spawn_blocking(move || {
+            // Locked for the whole report.
+            let mut out = io::stdout().lock();
+            let mut header_out = io::stderr().lock();
+            write_report(&mut out, header.then_some(&mut header_out), &data)
+        })
+        .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_report_verbatim() {
+        // The start of a gzip file, which is not valid UTF-8.
+        let data = GoogleStoragePayload {
+            name: "report_transfer.csv.gz".to_string(),
+            mime_type: "application/gzip".to_string(),
+            body: vec![0x1f, 0x8b, 0x08, 0x00, 0xff, 0x00, 0x0a, 0x0d],
+            is_public: false,
+            module: "transfers".to_string(),
+        };
+
+        let mut out = vec![];
+        let mut header_out = vec![];
+        write_report(&mut out, Some(&mut header_out), &data).unwrap();
+
+        assert_eq!(out, data.body);
+        assert_eq!(
+            String::from_utf8(header_out).unwrap(),
+            "==> report_transfer.csv.gz (application/gzip, 8 bytes) <==\n"
+        );
+
+        let mut out = vec![];
+        write_report::<_, Vec<u8>>(&mut out, None, &data).unwrap();
+        assert_eq!(out, data.body);
+    }
+}
@@ -0,0 +1,184 @@
+use crate::Result;
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Duration, Instant};
+
+/// The rate at which requests are sent to an external service, such as
+/// Subscan or a publisher.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// The sustained amount of requests per second, e.g. `0.5` for one
+    /// request every two seconds.
+    pub requests_per_second: f64,
+    /// How many requests may be sent right after each other once the limit
+    /// was not exhausted for a while. One by default, which spaces all
+    /// requests evenly.
+    #[serde(default = "default_burst")]
+    pub burst: u32,
+}
+
+fn default_burst() -> u32 {
+    1
+}
+
+/// A token bucket limiting the rate of requests. The bucket holds up to
+/// `burst` tokens and is refilled with one token per interval. Each request
+/// takes a token, waiting for one to be refilled if the bucket is empty.
+///
+/// Implemented as the generic cell rate algorithm, which only tracks when the
+/// bucket is full again rather than the amount of tokens.
+pub struct RateLimiter {
+    interval: Duration,
+    /// How far the bucket may be drained ahead of time, i.e. the time it takes
+    /// to refill all but one token.
+    tolerance: Duration,
+    /// When the bucket would be full again if no further requests were sent.
+    /// Requests are delayed while it's further ahead than `tolerance`.
+    full_at: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: &RateLimit) -> Result<Self> {
+        if !(limit.requests_per_second.is_finite() && limit.requests_per_second > 0.0) {
+            return Err(anyhow!(
+                "the requests per second of a rate limit must be greater than zero, found {}",
+                limit.requests_per_second
+            ));
+        }
+        if limit.burst == 0 {
+            return Err(anyhow!(
+                "the burst of a rate limit must be greater than zero"
+            ));
+        }
+
+        // Tiny rates result in intervals which can not be represented, or
+        // which would overflow the deadlines of `until_ready`.
+        let too_low = || {
+            anyhow!(
+                "the requests per second of a rate limit are too low, found {}",
+                limit.requests_per_second
+            )
+        };
+        let interval =
+            Duration::try_from_secs_f64(1.0 / limit.requests_per_second).map_err(|_| too_low())?;
+        let tolerance = interval.checked_mul(limit.burst - 1).ok_or_else(too_low)?;
+        interval
+            .checked_add(tolerance)
+            .and_then(|delay| Instant::now().checked_add(delay))
+            .ok_or_else(too_low)?;
+
+        Ok(RateLimiter {
+            interval,
+            tolerance,
+            full_at: Mutex::new(None),
+        })
+    }
+    /// Allows one request per interval, without bursts.
+    pub fn every(interval: Duration) -> Self {
+        RateLimiter {
+            interval,
+            tolerance: Duration::ZERO,
+            full_at: Mutex::new(None),
+        }
+    }
+    /// A new limiter with the same rate and burst, but a bucket of its own,
+    /// e.g. for a separate API key.
+    pub fn same_rate(&self) -> Self {
+        RateLimiter {
+            interval: self.interval,
+            tolerance: self.tolerance,
+            full_at: Mutex::new(None),
+        }
+    }
+    /// Waits until a token is available and takes it. The lock is held while
+    /// waiting, so concurrent callers are served one after another.
+    pub async fn until_ready(&self) {
+        let mut full_at = self.full_at.lock().await;
+
+        let now = Instant::now();
+        let at = full_at.unwrap_or(now).max(now);
+        if let Some(ready) = at.checked_sub(self.tolerance) {
+            sleep_until(ready).await;
+        }
+
+        *full_at = Some(at.max(Instant::now()) + self.interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn space_requests_evenly() {
+        let limiter = RateLimiter::new(&RateLimit {
+            requests_per_second: 5.0,
+            burst: 1,
+        })
+        .unwrap();
+
+        let start = Instant::now();
+        limiter.until_ready().await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        limiter.until_ready().await;
+        limiter.until_ready().await;
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn allow_bursts() {
+        let limiter = Arc::new(
+            RateLimiter::new(&RateLimit {
+                requests_per_second: 5.0,
+                burst: 3,
+            })
+            .unwrap(),
+        );
+
+        // The full bucket is drained right away, even by concurrent callers.
+        let start = Instant::now();
+        let handles: Vec<_> = (0..3)
+            .map(|_| {
+                let limiter = Arc::clone(&limiter);
+                tokio::spawn(async move { limiter.until_ready().await })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        // Further requests wait for the bucket to be refilled.
+        limiter.until_ready().await;
+        assert!(start.elapsed() >= Duration::from_millis(200));
+
+        // Once refilled, bursts are possible again.
+        sleep_until(Instant::now() + Duration::from_millis(600)).await;
+        let start = Instant::now();
+        limiter.until_ready().await;
+        limiter.until_ready().await;
+        limiter.until_ready().await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn reject_invalid_limits() {
+        let limit = |requests_per_second, burst| RateLimit {
+            requests_per_second,
+            burst,
+        };
+
+        assert!(RateLimiter::new(&limit(0.0, 1)).is_err());
+        assert!(RateLimiter::new(&limit(-1.0, 1)).is_err());
+        assert!(RateLimiter::new(&limit(f64::NAN, 1)).is_err());
+        assert!(RateLimiter::new(&limit(1.0, 0)).is_err());
+        assert!(RateLimiter::new(&limit(0.1, 1)).is_ok());
+        // Neither the interval nor the burst tolerance can be represented.
+        assert!(RateLimiter::new(&limit(1e-300, 1)).is_err());
+        assert!(RateLimiter::new(&limit(1e-10, u32::MAX)).is_err());
+
+        let limit: RateLimit = serde_yaml::from_str("requests_per_second: 2").unwrap();
+        assert_eq!(limit.burst, 1);
+    }
+}
@@ -0,0 +1,344 @@
+use super::{
+    index_contexts, nominations, rewards_slashes, select_contexts, transfers, GenerateReport,
+    ReportPeriod,
+};
+use crate::chain_api::{Nomination, RewardSlash, Transfer};
+use crate::database::{ContextData, DatabaseReader};
+use crate::publishing::{GoogleStoragePayload, Publisher};
+use crate::{BlockNumber, Context, Labels, Result, Timestamp};
+use rust_xlsxwriter::Workbook;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const XLSX_MIME_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+/// The columns of all worksheets which are written as numbers, so they can be
+/// sorted and summed up in spreadsheet tools.
+const NUMERIC_COLUMNS: [&str; 6] = [
+    "Block Number",
+    "Block Timestamp",
+    "Amount",
+    "Fee",
+    "Value",
+    "Fees",
+];
+
+pub struct CombinedReport {
+    period: ReportPeriod,
+    body: Vec<u8>,
+}
+
+pub struct CombinedData<'a> {
+    transfers: Vec<ContextData<'a, Transfer>>,
+    rewards_slashes: Vec<ContextData<'a, RewardSlash>>,
+    nominations: Vec<ContextData<'a, Nomination>>,
+}
+
+pub struct CombinedReportGenerator<'a> {
+    reader: DatabaseReader,
+    contexts: Arc<RwLock<Vec<Context>>>,
+    labels: Labels,
+    _p: PhantomData<&'a ()>,
+}
+
+impl<'a> CombinedReportGenerator<'a> {
+    pub fn new(db: DatabaseReader, contexts: Arc<RwLock<Vec<Context>>>) -> Self {
+        CombinedReportGenerator {
+            reader: db,
+            contexts,
+            labels: Labels::new(),
+            _p: PhantomData,
+        }
+    }
+    /// Only reports on the accounts with all of the given labels.
+    pub fn with_labels(mut self, labels: Labels) -> Self {
+        self.labels = labels;
+        self
+    }
+}
+
+#[async_trait]
+impl<'a, T> GenerateReport<T> for CombinedReportGenerator<'a>
+where
+    T: 'static + Send + Sync + Publisher,
+    <T as Publisher>::Data: Send + Sync + From<CombinedReport>,
+    <T as Publisher>::Info: Send + Sync,
+{
+    type Data = CombinedData<'a>;
+    type Report = CombinedReport;
+
+    fn name() -> &'static str {
+        "CombinedReportGenerator"
+    }
+    async fn fetch_data(&self) -> Result<Option<Self::Data>> {
+        let contexts = self.contexts.read().await;
+        let contexts = select_contexts(&contexts, &self.labels);
+        let contexts = contexts.as_ref();
+
+        // Simply fetch everything as of now.
+        let (from_time, to_time) = (Timestamp::from(0), Timestamp::from(i64::MAX as u64));
+        let (from_block, to_block) = (BlockNumber::from(0), BlockNumber::from(i64::MAX as u64));
+
+        let data = CombinedData {
+            transfers: self
+                .reader
+                .fetch_transfers(contexts, from_time, to_time, None)
+                .await?,
+            rewards_slashes: self
+                .reader
+                .fetch_rewards_slashes(contexts, from_block, to_block)
+                .await?,
+            nominations: self.reader.fetch_nominations(contexts).await?,
+        };
+        if data.transfers.is_empty()
+            && data.rewards_slashes.is_empty()
+            && data.nominations.is_empty()
+        {
+            return Ok(None);
+        }
+
+        debug!(
+            "{}: Fetched {} transfers, {} rewards/slashes and {} nominations from database",
+            <Self as GenerateReport<T>>::name(),
+            data.transfers.len(),
+            data.rewards_slashes.len(),
+            data.nominations.len(),
+        );
+
+        Ok(Some(data))
+    }
+    async fn generate(&self, data: &Self::Data) -> Result<Vec<Self::Report>> {
+        debug!(
+            "{}: Generating combined report",
+            <Self as GenerateReport<T>>::name(),
+        );
+
+        let contexts = self.contexts.read().await;
+        let contexts = index_contexts(contexts.as_slice());
+
+        // A single invalid entry does not fail the whole workbook. The fees
+        // are only summed up over the transfers which are listed.
+        let transfers = valid_entries("transfer", &data.transfers, |data| {
+            transfers::rows(&contexts, data)
+        });
+
+        // One worksheet per data type, plus the fees paid per account.
+        let sheets = [
+            (
+                "Transfers",
+                &transfers::HEADER[..],
+                transfers::rows(&contexts, &transfers)?,
+            ),
+            (
+                "Rewards and Slashes",
+                &rewards_slashes::HEADER[..],
+                valid_rows("reward/slash", &data.rewards_slashes, |data| {
+                    rewards_slashes::rows(&contexts, data)
+                }),
+            ),
+            (
+                "Nominations",
+                &nominations::HEADER[..],
+                valid_rows("nomination", &data.nominations, |data| {
+                    nominations::rows(&contexts, data)
+                }),
+            ),
+            (
+                "Fees",
+                &transfers::FEES_HEADER[..],
+                transfers::fee_totals(&contexts, &transfers)?,
+            ),
+        ];
+
+        let mut workbook = Workbook::new();
+        for (name, header, rows) in &sheets {
+            let worksheet = workbook.add_worksheet();
+            worksheet.set_name(*name)?;
+
+            for (col, title) in header.iter().enumerate() {
+                worksheet.write_string(0, col as u16, *title)?;
+            }
+
+            for (row, values) in rows.iter().enumerate() {
+                let row = row as u32 + 1;
+                for (col, value) in values.iter().enumerate() {
+                    let col = col as u16;
+                    match value.parse::<f64>() {
+                        Ok(number) if NUMERIC_COLUMNS.contains(&header[col as usize]) => {
+                            worksheet.write_number(row, col, number)?;
+                        }
+                        _ => {
+                            worksheet.write_string(row, col, value)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(vec![CombinedReport {
+            // All entries are fetched as of now.
+            period: ReportPeriod::Full,
+            body: workbook.save_to_buffer()?,
+        }])
+    }
+    async fn publish(
+        &self,
+        publisher: Arc<T>,
+        info: <T as Publisher>::Info,
+        report: Self::Report,
+    ) -> Result<()> {
+        publisher
+            .upload_data(info, <T as Publisher>::Data::from(report))
+            .await?;
+
+        info!("Uploaded new report");
+
+        Ok(())
+    }
+}
+
+/// The entries for which `rows` succeeds. Invalid ones are logged and skipped.
+fn valid_entries<'a, T, F>(
+    kind: &str,
+    data: &[ContextData<'a, T>],
+    rows: F,
+) -> Vec<ContextData<'a, T>>
+where
+    T: Clone,
+    F: Fn(&[ContextData<'a, T>]) -> Result<Vec<Vec<String>>>,
+{
+    data.iter()
+        .filter(|entry| match rows(std::slice::from_ref(entry)) {
+            Ok(_) => true,
+            Err(err) => {
+                warn!("Skipping invalid {} in combined report: {:?}", kind, err);
+                false
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// The rows of all entries for which `rows` succeeds. Invalid ones are logged
+/// and skipped.
+fn valid_rows<'a, T, F>(kind: &str, data: &[ContextData<'a, T>], rows: F) -> Vec<Vec<String>>
+where
+    T: Clone,
+    F: Fn(&[ContextData<'a, T>]) -> Result<Vec<Vec<String>>>,
+{
+    data.iter()
+        .flat_map(|entry| match rows(std::slice::from_ref(entry)) {
+            Ok(rows) => rows,
+            Err(err) => {
+                warn!("Skipping invalid {} in combined report: {:?}", kind, err);
+                vec![]
+            }
+        })
+        .collect()
+}
+
+impl From<CombinedReport> for GoogleStoragePayload {
+    fn from(val: CombinedReport) -> Self {
+        GoogleStoragePayload {
+            name: val.period.file_name("report_combined", "xlsx"),
+            mime_type: XLSX_MIME_TYPE.to_string(),
+            body: val.body,
+            is_public: false,
+            module: "combined".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publishing::GoogleStorage;
+    use crate::tests::{entry, memory_db};
+
+    #[tokio::test]
+    async fn generate_combined_report() {
+        let alice = Context::alice();
+
+        let data = CombinedData {
+            transfers: vec![entry(
+                &alice,
+                Transfer {
+                    amount: "10000000000".into(),
+                    ..Default::default()
+                },
+            )],
+            rewards_slashes: vec![],
+            nominations: vec![entry(&alice, Default::default())],
+        };
+
+        let reader = memory_db().await.reader();
+        let generator =
+            CombinedReportGenerator::new(reader, Arc::new(RwLock::new(vec![alice.clone()])));
+
+        let reports =
+            <CombinedReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+
+        assert_eq!(reports.len(), 1);
+
+        // XLSX files are ZIP archives.
+        assert!(reports[0].body.starts_with(b"PK"));
+    }
+
+    #[tokio::test]
+    async fn skip_invalid_entries() {
+        use std::io::Read;
+
+        let alice = Context::alice();
+        let bob = Context::bob();
+        let transfer = |amount: &str| {
+            entry(
+                &alice,
+                Transfer {
+                    amount: amount.into(),
+                    block_num: 10.into(),
+                    ..Default::default()
+                },
+            )
+        };
+
+        let data = CombinedData {
+            transfers: vec![transfer("not a number"), transfer("10000000000")],
+            rewards_slashes: vec![],
+            nominations: vec![entry(&bob, Default::default())],
+        };
+
+        let reader = memory_db().await.reader();
+        let generator =
+            CombinedReportGenerator::new(reader, Arc::new(RwLock::new(vec![alice.clone()])));
+
+        let reports =
+            <CombinedReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+
+        let mut archive =
+            zip::ZipArchive::new(std::io::Cursor::new(reports[0].body.as_slice())).unwrap();
+        let mut sheet = |idx: usize| {
+            let mut xml = String::new();
+            archive
+                .by_name(&format!("xl/worksheets/sheet{}.xml", idx))
+                .unwrap()
+                .read_to_string(&mut xml)
+                .unwrap();
+            xml
+        };
+
+        // Only the valid transfer is listed, with numeric cells for the
+        // block number and the amount.
+        let transfers = sheet(1);
+        assert_eq!(transfers.matches("<row ").count(), 2);
+        assert!(transfers.contains(r#"<c r="B2"><v>10</v></c>"#));
+        assert!(transfers.contains(r#"<c r="G2"><v>1</v></c>"#));
+
+        // The nomination of an unknown account is skipped.
+        assert_eq!(sheet(3).matches("<row ").count(), 1);
+    }
+}
@@ -1,14 +1,138 @@
+use crate::chain_api::{Balance, TokenAmount};
 use crate::publishing::Publisher;
-use crate::Result;
+use crate::{BlockNumber, Context, ContextId, Labels, Network, Result, Timestamp};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
 use std::sync::Arc;
 
+mod combined;
+mod nomination_deltas;
 mod nominations;
 mod rewards_slashes;
 mod transfers;
 
-pub use nominations::{NominationReport, NominationReportGenerator};
+pub use combined::CombinedReportGenerator;
+pub use nomination_deltas::NominationDeltaReportGenerator;
+pub use nominations::NominationReportGenerator;
 pub use rewards_slashes::RewardSlashReportGenerator;
-pub use transfers::{TransferReport, TransferReportGenerator};
+pub(crate) use transfers::transfer_event;
+pub use transfers::TransferColumn;
+#[cfg(test)]
+pub use transfers::TransferReport;
+pub use transfers::TransferReportGenerator;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Limits the entries of a report to a window of blocks or time. Reports
+/// cover all entries by default.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportRange {
+    /// The entries within the given blocks, both inclusive.
+    Blocks {
+        from: Option<BlockNumber>,
+        to: Option<BlockNumber>,
+    },
+    /// The entries of the given amount of most recent days.
+    LastDays(u64),
+}
+
+impl ReportRange {
+    /// The window of blocks, if limited by blocks.
+    fn blocks(&self) -> Option<(BlockNumber, BlockNumber)> {
+        match self {
+            ReportRange::Blocks { from, to } => Some((
+                from.unwrap_or_default(),
+                to.unwrap_or_else(|| BlockNumber::from(i64::MAX as u64)),
+            )),
+            ReportRange::LastDays(_) => None,
+        }
+    }
+    /// The window of time, if limited by time.
+    fn time(&self, now: Timestamp) -> Option<(Timestamp, Timestamp)> {
+        match self {
+            ReportRange::Blocks { .. } => None,
+            ReportRange::LastDays(days) => Some((
+                Timestamp::from(now.as_secs().saturating_sub(days * SECS_PER_DAY)),
+                Timestamp::from(i64::MAX as u64),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ReportRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportRange::Blocks { from, to } => {
+                match from {
+                    Some(from) => write!(f, "blocks {}", from)?,
+                    None => write!(f, "blocks from the first")?,
+                }
+                match to {
+                    Some(to) => write!(f, " to {}", to),
+                    None => write!(f, " to the latest"),
+                }
+            }
+            ReportRange::LastDays(days) => write!(f, "last {} days", days),
+        }
+    }
+}
+
+/// The size of the buckets of `ReportBuckets`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BucketSize {
+    Daily,
+    Weekly,
+    /// Calendar months.
+    Monthly,
+}
+
+/// Splits a report into one file per bucket of time (UTC), e.g. one per
+/// month of a year.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ReportBuckets {
+    /// The first day of the first bucket. Monthly buckets start at the first
+    /// day of its month.
+    pub start: NaiveDate,
+    /// The day after the last bucket, e.g. `2025-01-01` for the buckets of
+    /// 2024. Up to and including the current bucket if not set.
+    pub end: Option<NaiveDate>,
+    pub size: BucketSize,
+}
+
+impl ReportBuckets {
+    /// The periods of all buckets, in chronological order. The last bucket
+    /// is cut off at `end`, if set.
+    fn periods(&self, today: NaiveDate) -> Vec<ReportPeriod> {
+        let mut from = match self.size {
+            BucketSize::Monthly => self.start.with_day(1).unwrap(),
+            _ => self.start,
+        };
+        let end = self.end.unwrap_or_else(|| today.succ_opt().unwrap());
+
+        let mut periods = vec![];
+        while from < end {
+            let to = match self.size {
+                BucketSize::Daily => from.succ_opt().unwrap(),
+                BucketSize::Weekly => from + Duration::days(7),
+                BucketSize::Monthly => match from.month() {
+                    12 => NaiveDate::from_ymd_opt(from.year() + 1, 1, 1).unwrap(),
+                    month => NaiveDate::from_ymd_opt(from.year(), month + 1, 1).unwrap(),
+                },
+            }
+            .min(end);
+
+            periods.push(ReportPeriod::Bucket { from, to });
+            from = to;
+        }
+
+        periods
+    }
+}
 
 // TODO: Is this type constraint required here?
 #[async_trait]
@@ -26,3 +150,468 @@ pub trait GenerateReport<T: Publisher> {
         report: Self::Report,
     ) -> Result<()>;
 }
+
+/// The period covered by a report. The file name of a published report is
+/// derived from it, so publishing the same report again overwrites the
+/// previous upload instead of creating a duplicate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportPeriod {
+    /// All entries stored in the database.
+    Full,
+    /// The entries from the start of `from` until (excluding) `to`.
+    Bucket { from: NaiveDate, to: NaiveDate },
+}
+
+impl ReportPeriod {
+    fn file_name(&self, module: &str, extension: &str) -> String {
+        match self {
+            ReportPeriod::Full => format!("{}.{}", module, extension),
+            // Named after the first and the last day, both inclusive.
+            ReportPeriod::Bucket { from, to } => {
+                format!(
+                    "{}_{}_{}.{}",
+                    module,
+                    from,
+                    to.pred_opt().unwrap(),
+                    extension
+                )
+            }
+        }
+    }
+    /// The window of time of the period, both inclusive.
+    fn time(&self) -> (Timestamp, Timestamp) {
+        match self {
+            ReportPeriod::Full => (Timestamp::from(0), Timestamp::from(i64::MAX as u64)),
+            ReportPeriod::Bucket { from, to } => (
+                Timestamp::from(from.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() as u64),
+                Timestamp::from(to.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() as u64 - 1),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ReportPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportPeriod::Full => write!(f, "all entries"),
+            ReportPeriod::Bucket { from, to } => {
+                write!(f, "{} to {}", from, to.pred_opt().unwrap())
+            }
+        }
+    }
+}
+
+/// Describes a CSV report in a block of comment lines preceding the column
+/// header, e.g. for audits. Disabled by default, since strict CSV parsers
+/// reject the extra lines.
+#[derive(Debug, Clone, PartialEq)]
+struct ReportMetadata {
+    generated: DateTime<Utc>,
+    /// The amount of accounts the report covers.
+    accounts: usize,
+    /// The period covered by the report, e.g. `all entries`.
+    period: String,
+}
+
+impl ReportMetadata {
+    fn new(accounts: usize, period: String) -> Self {
+        ReportMetadata {
+            generated: Utc::now(),
+            accounts,
+            period,
+        }
+    }
+    fn header(&self) -> String {
+        format!(
+            "# Generated: {}\n# Monitor version: {}\n# Accounts: {}\n# Period: {}\n",
+            self.generated.to_rfc3339(),
+            env!("CARGO_PKG_VERSION"),
+            self.accounts,
+            self.period
+        )
+    }
+    /// Prepends the comment lines to the given CSV document.
+    fn prepend_to(&self, report: String) -> String {
+        self.header() + &report
+    }
+}
+
+/// The contexts indexed by their stash address and network, so entries
+/// fetched from the database can be matched with their context without
+/// iterating the full list. The same address on different networks belongs to
+/// different contexts.
+struct ContextIndex<'c>(HashMap<(&'c str, Network), &'c Context>);
+
+impl<'c> ContextIndex<'c> {
+    fn get(&self, id: &ContextId) -> Result<&'c Context> {
+        self.0
+            .get(&(id.stash.as_str(), id.network))
+            .copied()
+            .ok_or_else(|| anyhow!("No context found while generating reports"))
+    }
+}
+
+fn index_contexts(contexts: &[Context]) -> ContextIndex<'_> {
+    ContextIndex(
+        contexts
+            .iter()
+            .map(|c| ((c.stash.as_str(), c.network), c))
+            .collect(),
+    )
+}
+
+/// The accounts with all of the given labels, or all accounts if no labels are
+/// given.
+fn select_contexts<'c>(contexts: &'c [Context], labels: &Labels) -> Cow<'c, [Context]> {
+    if labels.is_empty() {
+        return Cow::Borrowed(contexts);
+    }
+
+    Cow::Owned(
+        contexts
+            .iter()
+            .filter(|context| context.has_labels(labels))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Converts a Planck amount, as returned by Subscan, into whole tokens of the
+/// given network. Fails if the amount is missing or malformed.
+fn to_token_amount(network: Network, amount: &Balance) -> Result<TokenAmount> {
+    let planck = amount
+        .planck()
+        .ok_or_else(|| anyhow!("invalid amount: {:?}", amount.as_str()))?;
+
+    Ok(TokenAmount::new(planck, network.token_decimals()))
+}
+
+/// The scale of decimal amounts, the highest token decimals of all networks,
+/// so the amounts of every network are represented exactly.
+const AMOUNT_SCALE: u32 = 12;
+
+/// Converts a Planck amount into whole tokens of the given network, as an
+/// exact decimal with `AMOUNT_SCALE` digits after the decimal point. Fails if
+/// the amount is missing, malformed or out of range.
+fn to_decimal_amount(network: Network, amount: &Balance) -> Result<i128> {
+    let planck = amount
+        .planck()
+        .ok_or_else(|| anyhow!("invalid amount: {:?}", amount.as_str()))?;
+
+    i128::try_from(planck)
+        .ok()
+        .and_then(|planck| planck.checked_mul(10_i128.pow(AMOUNT_SCALE - network.token_decimals())))
+        .ok_or_else(|| anyhow!("amount out of range: {}", amount))
+}
+
+/// Uploaded as plain CSV files, rather than being converted to documents.
+const CSV_MIME_TYPE: &str = "text/csv";
+
+const PARQUET_MIME_TYPE: &str = "application/vnd.apache.parquet";
+
+/// The file format of a report. Only the transfers report supports formats
+/// other than CSV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    #[default]
+    Csv,
+    /// Typed columns, e.g. for data lakes. Comment lines of `ReportMetadata`
+    /// are not supported.
+    Parquet,
+}
+
+impl ReportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "csv",
+            ReportFormat::Parquet => "parquet",
+        }
+    }
+    fn mime_type(&self) -> &'static str {
+        match self {
+            ReportFormat::Csv => CSV_MIME_TYPE,
+            ReportFormat::Parquet => PARQUET_MIME_TYPE,
+        }
+    }
+}
+
+/// The delimiter of CSV reports, unless configured otherwise.
+pub const DEFAULT_CSV_DELIMITER: u8 = b',';
+
+/// Characters which make spreadsheet applications interpret a cell as a
+/// formula.
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// Neutralizes fields which would otherwise be evaluated as formulas when the
+/// report is opened in a spreadsheet application (CSV injection), by prefixing
+/// them with a single quote. Numbers, such as negative amounts, are kept as is.
+fn sanitize_field(field: &str) -> Cow<'_, str> {
+    if field.starts_with(&FORMULA_PREFIXES[..]) && field.parse::<f64>().is_err() {
+        Cow::Owned(format!("'{}", field))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Joins the header and rows into a CSV document. Fields are quoted as
+/// required by RFC 4180, e.g. if they contain the delimiter or quotes, and
+/// sanitized against CSV injection.
+fn to_csv(header: &[&str], rows: &[Vec<String>], delimiter: u8) -> Result<String> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(vec![]);
+
+    writer.write_record(header)?;
+    for row in rows {
+        writer.write_record(row.iter().map(|field| sanitize_field(field).into_owned()))?;
+    }
+
+    let report = writer.into_inner().map_err(|err| err.into_error())?;
+    Ok(String::from_utf8(report)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn token_amount_conversion() {
+        let amount = |network, planck: &str| {
+            to_token_amount(network, &planck.into()).map(|amount| amount.to_string())
+        };
+
+        assert_eq!(amount(Network::Polkadot, "10000000000").unwrap(), "1");
+        assert_eq!(amount(Network::Polkadot, "25000000000").unwrap(), "2.5");
+        assert_eq!(amount(Network::Polkadot, "1").unwrap(), "0.0000000001");
+        assert_eq!(amount(Network::Kusama, "1000000000000").unwrap(), "1");
+        assert_eq!(amount(Network::Kusama, "3500000000000").unwrap(), "3.5");
+        assert_eq!(amount(Network::Kusama, "0").unwrap(), "0");
+        assert!(amount(Network::Polkadot, "invalid").is_err());
+        assert!(amount(Network::Polkadot, "").is_err());
+        // Beyond the range of `u64`.
+        assert_eq!(
+            amount(Network::Polkadot, "100000000000000000000").unwrap(),
+            "10000000000"
+        );
+        // Beyond the precision of `f64`.
+        assert_eq!(
+            amount(Network::Kusama, "123456789012345678").unwrap(),
+            "123456.789012345678"
+        );
+
+        // Exact decimals with the scale of all networks.
+        assert_eq!(
+            to_decimal_amount(Network::Polkadot, &"25000000000".into()).unwrap(),
+            2_500_000_000_000
+        );
+        assert_eq!(
+            to_decimal_amount(Network::Kusama, &"3500000000000".into()).unwrap(),
+            3_500_000_000_000
+        );
+        assert!(to_decimal_amount(Network::Polkadot, &"".into()).is_err());
+        let max = u128::MAX.to_string();
+        assert!(to_decimal_amount(Network::Polkadot, &max.as_str().into()).is_err());
+    }
+
+    #[test]
+    fn csv_quoting_and_delimiter() {
+        let rows = vec![vec![
+            "polkadot".to_string(),
+            "Alice, the \"main\" stash".to_string(),
+        ]];
+
+        assert_eq!(
+            to_csv(&["Network", "Description"], &rows, b',').unwrap(),
+            "Network,Description\npolkadot,\"Alice, the \"\"main\"\" stash\"\n"
+        );
+        assert_eq!(
+            to_csv(&["Network", "Description"], &rows, b';').unwrap(),
+            "Network;Description\npolkadot;\"Alice, the \"\"main\"\" stash\"\n"
+        );
+    }
+
+    #[test]
+    fn csv_injection_is_neutralized() {
+        let rows = vec![vec![
+            "=HYPERLINK(\"http://example.com\", \"Click\")".to_string(),
+            "@SUM(A1:A2)".to_string(),
+            "-1.5".to_string(),
+            "Alice".to_string(),
+        ]];
+
+        assert_eq!(
+            to_csv(
+                &["Display Name", "Description", "Amount", "Name"],
+                &rows,
+                b','
+            )
+            .unwrap(),
+            "Display Name,Description,Amount,Name\n\
+             \"'=HYPERLINK(\"\"http://example.com\"\", \"\"Click\"\")\",'@SUM(A1:A2),-1.5,Alice\n"
+        );
+    }
+
+    #[test]
+    fn bucket_periods() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let bounds = |periods: Vec<ReportPeriod>| -> Vec<(NaiveDate, NaiveDate)> {
+            periods
+                .into_iter()
+                .map(|period| match period {
+                    ReportPeriod::Bucket { from, to } => (from, to),
+                    ReportPeriod::Full => panic!("unexpected full period"),
+                })
+                .collect()
+        };
+
+        // A whole year of months, starting mid-month.
+        let buckets = ReportBuckets {
+            start: date(2024, 1, 15),
+            end: Some(date(2025, 1, 1)),
+            size: BucketSize::Monthly,
+        };
+        let periods = bounds(buckets.periods(date(2030, 1, 1)));
+        assert_eq!(periods.len(), 12);
+        assert_eq!(periods[0], (date(2024, 1, 1), date(2024, 2, 1)));
+        assert_eq!(periods[1], (date(2024, 2, 1), date(2024, 3, 1)));
+        assert_eq!(periods[11], (date(2024, 12, 1), date(2025, 1, 1)));
+
+        // Up to and including the current bucket, which is cut off at today.
+        let buckets = ReportBuckets {
+            end: None,
+            ..buckets
+        };
+        let periods = bounds(buckets.periods(date(2024, 3, 10)));
+        assert_eq!(periods.len(), 3);
+        assert_eq!(periods[2], (date(2024, 3, 1), date(2024, 3, 11)));
+
+        let buckets = ReportBuckets {
+            start: date(2024, 1, 1),
+            end: Some(date(2024, 1, 10)),
+            size: BucketSize::Weekly,
+        };
+        assert_eq!(
+            bounds(buckets.periods(date(2030, 1, 1))),
+            vec![
+                (date(2024, 1, 1), date(2024, 1, 8)),
+                (date(2024, 1, 8), date(2024, 1, 10))
+            ]
+        );
+
+        let buckets = ReportBuckets {
+            size: BucketSize::Daily,
+            ..buckets
+        };
+        assert_eq!(buckets.periods(date(2030, 1, 1)).len(), 9);
+
+        // Starting in the future.
+        let buckets = ReportBuckets {
+            start: date(2024, 2, 1),
+            end: None,
+            size: BucketSize::Daily,
+        };
+        assert!(buckets.periods(date(2024, 1, 1)).is_empty());
+
+        // Both bounds of the window are inclusive.
+        let january = ReportPeriod::Bucket {
+            from: date(2024, 1, 1),
+            to: date(2024, 2, 1),
+        };
+        assert_eq!(
+            january.time(),
+            (Timestamp::from(1704067200), Timestamp::from(1706745599))
+        );
+        assert_eq!(
+            january.file_name("report_transfer", "csv"),
+            "report_transfer_2024-01-01_2024-01-31.csv"
+        );
+    }
+
+    #[test]
+    fn report_range_windows() {
+        let now = Timestamp::from(10 * SECS_PER_DAY);
+
+        let range = ReportRange::Blocks {
+            from: Some(BlockNumber::from(100)),
+            to: None,
+        };
+        assert_eq!(
+            range.blocks(),
+            Some((BlockNumber::from(100), BlockNumber::from(i64::MAX as u64)))
+        );
+        assert_eq!(range.time(now), None);
+
+        let range = ReportRange::LastDays(3);
+        assert_eq!(range.blocks(), None);
+        assert_eq!(
+            range.time(now),
+            Some((
+                Timestamp::from(7 * SECS_PER_DAY),
+                Timestamp::from(i64::MAX as u64)
+            ))
+        );
+        assert_eq!(
+            ReportRange::LastDays(30).time(now).unwrap().0,
+            Timestamp::from(0)
+        );
+
+        let range: ReportRange = serde_yaml::from_str("blocks:\n  from: 100\n  to: 200").unwrap();
+        assert_eq!(
+            range,
+            ReportRange::Blocks {
+                from: Some(BlockNumber::from(100)),
+                to: Some(BlockNumber::from(200)),
+            }
+        );
+        let range: ReportRange = serde_yaml::from_str("last_days: 30").unwrap();
+        assert_eq!(range, ReportRange::LastDays(30));
+    }
+
+    #[test]
+    fn report_period_file_name() {
+        assert_eq!(
+            ReportPeriod::Full.file_name("report_transfer", "csv"),
+            "report_transfer.csv"
+        );
+    }
+
+    #[test]
+    fn metadata_header() {
+        let metadata = ReportMetadata {
+            generated: Utc.with_ymd_and_hms(2024, 2, 1, 8, 30, 0).unwrap(),
+            accounts: 3,
+            period: ReportPeriod::Bucket {
+                from: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                to: NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            }
+            .to_string(),
+        };
+
+        let report = metadata.prepend_to("Network,Address\npolkadot,1a2b\n".to_string());
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "# Generated: 2024-02-01T08:30:00+00:00",
+                &format!("# Monitor version: {}", env!("CARGO_PKG_VERSION")),
+                "# Accounts: 3",
+                "# Period: 2024-01-01 to 2024-01-31",
+                "Network,Address",
+                "polkadot,1a2b",
+            ]
+        );
+
+        assert_eq!(ReportPeriod::Full.to_string(), "all entries");
+        assert_eq!(
+            ReportRange::Blocks {
+                from: Some(BlockNumber::from(100)),
+                to: None,
+            }
+            .to_string(),
+            "blocks 100 to the latest"
+        );
+        assert_eq!(ReportRange::LastDays(30).to_string(), "last 30 days");
+    }
+}
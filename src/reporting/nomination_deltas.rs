@@ -0,0 +1,336 @@
+use super::nominations::validator_name;
+use super::{
+    index_contexts, select_contexts, to_csv, ContextIndex, GenerateReport, ReportMetadata,
+    ReportPeriod, CSV_MIME_TYPE, DEFAULT_CSV_DELIMITER,
+};
+use crate::chain_api::Nomination;
+use crate::database::{ContextData, DatabaseReader, NominationSnapshot};
+use crate::publishing::{GoogleStoragePayload, Publisher};
+use crate::{Context, Labels, Result};
+use chrono::{TimeZone, Utc};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const HEADER: [&str; 7] = [
+    "Detected",
+    "Network",
+    "Address",
+    "Description",
+    "Validator",
+    "Display Name",
+    "Direction",
+];
+
+/// Whether an account started or stopped nominating a validator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Added,
+    Removed,
+}
+
+impl Direction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Added => "added",
+            Direction::Removed => "removed",
+        }
+    }
+}
+
+pub struct NominationDeltaReport {
+    period: ReportPeriod,
+    body: String,
+}
+
+/// The validators which were added in `latest` compared to `previous`,
+/// followed by the ones which were removed.
+fn deltas<'s>(
+    previous: &'s NominationSnapshot,
+    latest: &'s NominationSnapshot,
+) -> Vec<(Direction, &'s Nomination)> {
+    let (before, after) = (previous.addresses(), latest.addresses());
+
+    let added = latest
+        .validators
+        .iter()
+        .filter(|v| !before.contains(v.stash_account_display.address.as_str()))
+        .map(|v| (Direction::Added, v));
+    let removed = previous
+        .validators
+        .iter()
+        .filter(|v| !after.contains(v.stash_account_display.address.as_str()))
+        .map(|v| (Direction::Removed, v));
+
+    added.chain(removed).collect()
+}
+
+/// Creates a report row for each added or removed validator, matching the
+/// columns of `HEADER`. Detected at the time of the latest snapshot.
+fn rows(
+    contexts: &ContextIndex,
+    data: &[(
+        ContextData<NominationSnapshot>,
+        ContextData<NominationSnapshot>,
+    )],
+) -> Result<Vec<Vec<String>>> {
+    let mut rows = vec![];
+
+    for (previous, latest) in data {
+        let context = contexts.get(&latest.context_id)?;
+        let detected = Utc
+            .timestamp_opt(latest.timestamp.as_secs() as i64, 0)
+            .unwrap()
+            .to_rfc3339();
+
+        for (direction, validator) in deltas(&previous.data, &latest.data) {
+            rows.push(vec![
+                detected.clone(),
+                context.network.as_str().to_string(),
+                context.stash.clone(),
+                context.description.clone(),
+                validator.stash_account_display.address.clone(),
+                validator_name(validator),
+                direction.as_str().to_string(),
+            ]);
+        }
+    }
+
+    Ok(rows)
+}
+
+/// Reports only the validators which accounts most recently started or
+/// stopped nominating, by comparing the latest two snapshots of the
+/// nominations of each account. Accounts with a single snapshot have no
+/// changes yet.
+pub struct NominationDeltaReportGenerator<'a> {
+    reader: DatabaseReader,
+    contexts: Arc<RwLock<Vec<Context>>>,
+    labels: Labels,
+    delimiter: u8,
+    metadata: bool,
+    _p: PhantomData<&'a ()>,
+}
+
+impl<'a> NominationDeltaReportGenerator<'a> {
+    pub fn new(db: DatabaseReader, contexts: Arc<RwLock<Vec<Context>>>) -> Self {
+        NominationDeltaReportGenerator {
+            reader: db,
+            contexts,
+            labels: Labels::new(),
+            delimiter: DEFAULT_CSV_DELIMITER,
+            metadata: false,
+            _p: PhantomData,
+        }
+    }
+    /// Only reports on the accounts with all of the given labels.
+    pub fn with_labels(mut self, labels: Labels) -> Self {
+        self.labels = labels;
+        self
+    }
+    /// Sets the delimiter of the CSV report.
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+    /// Prepends a block of comment lines describing the report, see
+    /// `ReportMetadata`.
+    pub fn with_metadata(mut self, metadata: bool) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+#[async_trait]
+impl<'a, T> GenerateReport<T> for NominationDeltaReportGenerator<'a>
+where
+    T: 'static + Send + Sync + Publisher,
+    <T as Publisher>::Data: Send + Sync + From<NominationDeltaReport>,
+    <T as Publisher>::Info: Send + Sync,
+{
+    /// The previous and the latest snapshot of each account.
+    type Data = Vec<(
+        ContextData<'a, NominationSnapshot>,
+        ContextData<'a, NominationSnapshot>,
+    )>;
+    type Report = NominationDeltaReport;
+
+    fn name() -> &'static str {
+        "NominationDeltaReportGenerator"
+    }
+    async fn fetch_data(&self) -> Result<Option<Self::Data>> {
+        let contexts = self.contexts.read().await;
+        let contexts = select_contexts(&contexts, &self.labels);
+
+        let mut data = vec![];
+        for context in contexts.iter() {
+            let mut snapshots = self.reader.fetch_nomination_snapshots(context, 2).await?;
+            if snapshots.len() == 2 {
+                let previous = snapshots.pop().unwrap();
+                let latest = snapshots.pop().unwrap();
+                data.push((previous, latest));
+            }
+        }
+
+        if data.is_empty() {
+            return Ok(None);
+        }
+
+        debug!(
+            "{}: Fetched the snapshots of {} accounts from database",
+            <Self as GenerateReport<T>>::name(),
+            data.len()
+        );
+
+        Ok(Some(data))
+    }
+    async fn generate(&self, data: &Self::Data) -> Result<Vec<Self::Report>> {
+        let contexts = self.contexts.read().await;
+        let accounts = select_contexts(&contexts, &self.labels).len();
+        let contexts = index_contexts(contexts.as_slice());
+
+        let rows = rows(&contexts, data)?;
+        if rows.is_empty() {
+            return Ok(vec![]);
+        }
+
+        debug!(
+            "{}: Generating reports of {} changed nominations",
+            <Self as GenerateReport<T>>::name(),
+            rows.len()
+        );
+
+        // Only the latest changes are reported.
+        let period = ReportPeriod::Full;
+        let mut report = to_csv(&HEADER, &rows, self.delimiter)?;
+        if self.metadata {
+            report = ReportMetadata::new(accounts, period.to_string()).prepend_to(report);
+        }
+
+        Ok(vec![NominationDeltaReport {
+            period,
+            body: report,
+        }])
+    }
+    async fn publish(
+        &self,
+        publisher: Arc<T>,
+        info: <T as Publisher>::Info,
+        report: Self::Report,
+    ) -> Result<()> {
+        publisher
+            .upload_data(info, <T as Publisher>::Data::from(report))
+            .await?;
+
+        info!("Uploaded new report");
+
+        Ok(())
+    }
+}
+
+impl From<NominationDeltaReport> for GoogleStoragePayload {
+    fn from(val: NominationDeltaReport) -> Self {
+        GoogleStoragePayload {
+            name: val.period.file_name("nomination_deltas", "csv"),
+            mime_type: CSV_MIME_TYPE.to_string(),
+            body: val.body.into_bytes(),
+            is_public: false,
+            module: "nomination_deltas".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_api::StashAccountDisplay;
+    use crate::publishing::GoogleStorage;
+    use crate::tests::{entry, memory_db};
+    use crate::Timestamp;
+
+    fn snapshot<'a>(
+        context: &'a Context,
+        timestamp: u64,
+        validators: &[&str],
+    ) -> ContextData<'a, NominationSnapshot> {
+        ContextData {
+            timestamp: Timestamp::from(timestamp),
+            ..entry(
+                context,
+                NominationSnapshot {
+                    validators: validators
+                        .iter()
+                        .map(|name| Nomination {
+                            stash_account_display: StashAccountDisplay {
+                                address: format!("{}_address", name),
+                                display: name.to_string(),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        })
+                        .collect(),
+                },
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn generate_added_and_removed_validators() {
+        let alice = Context::alice();
+        let bob = Context::bob();
+
+        let data = vec![
+            (
+                snapshot(&alice, 100, &["Validator A", "Validator B"]),
+                snapshot(&alice, 200, &["Validator B", "Validator C"]),
+            ),
+            // Reordered, but unchanged.
+            (
+                snapshot(&bob, 100, &["Validator A", "Validator B"]),
+                snapshot(&bob, 200, &["Validator B", "Validator A"]),
+            ),
+        ];
+
+        let reader = memory_db().await.reader();
+        let generator = NominationDeltaReportGenerator::new(
+            reader,
+            Arc::new(RwLock::new(vec![alice.clone(), bob.clone()])),
+        );
+
+        let reports = <NominationDeltaReportGenerator as GenerateReport<GoogleStorage>>::generate(
+            &generator, &data,
+        )
+        .await
+        .unwrap();
+        assert_eq!(reports.len(), 1);
+
+        let rows: Vec<Vec<&str>> = reports[0]
+            .body
+            .lines()
+            .skip(1)
+            .map(|row| row.split(',').collect())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        for row in &rows {
+            assert_eq!(row[0], Utc.timestamp_opt(200, 0).unwrap().to_rfc3339());
+            assert_eq!(row[2], alice.stash);
+        }
+        assert_eq!(
+            rows[0][4..],
+            ["Validator C_address", "Validator C", "added"]
+        );
+        assert_eq!(
+            rows[1][4..],
+            ["Validator A_address", "Validator A", "removed"]
+        );
+
+        // Nothing to report if no nominations changed.
+        let reports = <NominationDeltaReportGenerator as GenerateReport<GoogleStorage>>::generate(
+            &generator,
+            &data[1..].to_vec(),
+        )
+        .await
+        .unwrap();
+        assert!(reports.is_empty());
+    }
+}
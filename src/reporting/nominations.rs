@@ -1,18 +1,92 @@
-use super::GenerateReport;
+use super::{
+    index_contexts, select_contexts, to_csv, ContextIndex, GenerateReport, ReportMetadata,
+    ReportPeriod, CSV_MIME_TYPE, DEFAULT_CSV_DELIMITER,
+};
 use crate::chain_api::Nomination;
 use crate::database::{ContextData, DatabaseReader};
 use crate::publishing::{GoogleStoragePayload, Publisher};
-use crate::{Context, Result};
-use chrono::{SecondsFormat, TimeZone, Utc};
+use crate::{Context, Labels, Result};
+use chrono::{TimeZone, Utc};
 use std::marker::PhantomData;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-pub struct NominationReport(String);
+pub(super) const HEADER: [&str; 6] = [
+    "Detected",
+    "Network",
+    "Address",
+    "Description",
+    "Validator",
+    "Display Name",
+];
+
+// The amount of characters kept at each end of truncated addresses.
+const ADDRESS_AFFIX_LEN: usize = 6;
+
+pub struct NominationReport {
+    period: ReportPeriod,
+    body: String,
+}
+
+/// The name of the nominated validator. Falls back to the node name if the
+/// validator has no on-chain identity, and to a truncated address if neither
+/// is set.
+pub(super) fn validator_name(nomination: &Nomination) -> String {
+    let display = &nomination.stash_account_display.display;
+    if !display.is_empty() {
+        return display.clone();
+    }
+
+    if !nomination.node_name.is_empty() {
+        return nomination.node_name.clone();
+    }
+
+    let address = &nomination.stash_account_display.address;
+    if address.chars().count() <= ADDRESS_AFFIX_LEN * 2 {
+        return address.clone();
+    }
+
+    let prefix: String = address.chars().take(ADDRESS_AFFIX_LEN).collect();
+    let suffix: String = address
+        .chars()
+        .skip(address.chars().count() - ADDRESS_AFFIX_LEN)
+        .collect();
+
+    format!("{}...{}", prefix, suffix)
+}
+
+/// Creates a report row for each nomination, matching the columns of `HEADER`.
+pub(super) fn rows(
+    contexts: &ContextIndex,
+    data: &[ContextData<Nomination>],
+) -> Result<Vec<Vec<String>>> {
+    let mut rows = vec![];
+
+    for entry in data {
+        let context = contexts.get(&entry.context_id)?;
+
+        let data = entry.data.as_ref();
+        rows.push(vec![
+            Utc.timestamp_opt(entry.timestamp.as_secs() as i64, 0)
+                .unwrap()
+                .to_rfc3339(),
+            context.network.as_str().to_string(),
+            context.stash.clone(),
+            context.description.clone(),
+            data.stash_account_display.address.clone(),
+            validator_name(data),
+        ]);
+    }
+
+    Ok(rows)
+}
 
 pub struct NominationReportGenerator<'a> {
     reader: DatabaseReader,
     contexts: Arc<RwLock<Vec<Context>>>,
+    labels: Labels,
+    delimiter: u8,
+    metadata: bool,
     _p: PhantomData<&'a ()>,
 }
 
@@ -20,10 +94,29 @@ impl<'a> NominationReportGenerator<'a> {
     pub fn new(db: DatabaseReader, contexts: Arc<RwLock<Vec<Context>>>) -> Self {
         NominationReportGenerator {
             reader: db,
-            contexts: contexts,
+            contexts,
+            labels: Labels::new(),
+            delimiter: DEFAULT_CSV_DELIMITER,
+            metadata: false,
             _p: PhantomData,
         }
     }
+    /// Only reports on the accounts with all of the given labels.
+    pub fn with_labels(mut self, labels: Labels) -> Self {
+        self.labels = labels;
+        self
+    }
+    /// Sets the delimiter of the CSV report.
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+    /// Prepends a block of comment lines describing the report, see
+    /// `ReportMetadata`.
+    pub fn with_metadata(mut self, metadata: bool) -> Self {
+        self.metadata = metadata;
+        self
+    }
 }
 
 #[async_trait]
@@ -41,22 +134,23 @@ where
     }
     async fn fetch_data(&self) -> Result<Option<Self::Data>> {
         let contexts = self.contexts.read().await;
+        let contexts = select_contexts(&contexts, &self.labels);
+
         let data = self
             .reader
             // Simply fetch everything as of now.
-            .fetch_nominations(contexts.as_slice())
+            .fetch_nominations(&contexts)
             .await?;
-
         if data.is_empty() {
             return Ok(None);
-        } else {
-            debug!(
-                "{}: Fetched {} entries from database",
-                <Self as GenerateReport<T>>::name(),
-                data.len()
-            );
         }
 
+        debug!(
+            "{}: Fetched {} entries from database",
+            <Self as GenerateReport<T>>::name(),
+            data.len()
+        );
+
         Ok(Some(data))
     }
     async fn generate(&self, data: &Self::Data) -> Result<Vec<Self::Report>> {
@@ -70,32 +164,20 @@ where
             data.len()
         );
 
+        // All entries are fetched as of now.
+        let period = ReportPeriod::Full;
         let contexts = self.contexts.read().await;
-
-        let mut report =
-            String::from("Detected,Network,Address,Description,Validator,Display Name\n");
-
-        for entry in data {
-            // TODO: Improve performance here.
-            let context = contexts
-                .iter()
-                .find(|c| c.stash == entry.context_id.stash.clone().into_owned())
-                .ok_or(anyhow!("No context found while generating reports"))?;
-
-            let data = entry.data.as_ref();
-            report.push_str(&format!(
-                "{},{},{},{},{},{}\n",
-                Utc.timestamp(entry.timestamp.as_secs() as i64, 0)
-                    .to_rfc3339(),
-                context.network.as_str(),
-                context.stash,
-                context.description,
-                data.stash_account_display.address,
-                data.stash_account_display.display,
-            ))
+        let accounts = select_contexts(&contexts, &self.labels).len();
+        let contexts = index_contexts(contexts.as_slice());
+        let mut report = to_csv(&HEADER, &rows(&contexts, data)?, self.delimiter)?;
+        if self.metadata {
+            report = ReportMetadata::new(accounts, period.to_string()).prepend_to(report);
         }
 
-        Ok(vec![NominationReport(report)])
+        Ok(vec![NominationReport {
+            period,
+            body: report,
+        }])
     }
     async fn publish(
         &self,
@@ -115,13 +197,54 @@ where
 
 impl From<NominationReport> for GoogleStoragePayload {
     fn from(val: NominationReport) -> Self {
-        let _date = chrono::offset::Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
-
         GoogleStoragePayload {
-            name: format!("nominations.csv"),
-            mime_type: "application/vnd.google-apps.document".to_string(),
-            body: val.0.into_bytes(),
+            name: val.period.file_name("nominations", "csv"),
+            mime_type: CSV_MIME_TYPE.to_string(),
+            body: val.body.into_bytes(),
             is_public: false,
+            module: "nominations".to_string(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_api::StashAccountDisplay;
+    use crate::tests::entry;
+
+    #[test]
+    fn validator_name_fallback() {
+        let alice = Context::alice();
+        let contexts = vec![alice.clone()];
+        let contexts = index_contexts(&contexts);
+
+        let nomination = |display: &str, node_name: &str| {
+            entry(
+                &alice,
+                Nomination {
+                    stash_account_display: StashAccountDisplay {
+                        address: "1zugcag7cJVBtVRnFxv5Qftn7xKAnR6YJ9x4x3XLgGgmNnS".to_string(),
+                        display: display.to_string(),
+                        ..Default::default()
+                    },
+                    node_name: node_name.to_string(),
+                    ..Default::default()
+                },
+            )
+        };
+
+        let data = vec![
+            nomination("Validator", "node"),
+            nomination("", "node"),
+            nomination("", ""),
+        ];
+        let names: Vec<String> = rows(&contexts, &data)
+            .unwrap()
+            .into_iter()
+            .map(|row| row[5].clone())
+            .collect();
+
+        assert_eq!(names, vec!["Validator", "node", "1zugca...GgmNnS"]);
+    }
+}
@@ -1,19 +1,150 @@
-use super::GenerateReport;
-use crate::chain_api::RewardSlash;
+use super::{
+    index_contexts, select_contexts, to_csv, to_token_amount, ContextIndex, GenerateReport,
+    ReportMetadata, ReportPeriod, ReportRange, CSV_MIME_TYPE, DEFAULT_CSV_DELIMITER,
+};
+use crate::chain_api::{RewardSlash, TokenAmount};
 use crate::database::{ContextData, DatabaseReader};
-use crate::publishing::GoogleStoragePayload;
-use crate::publishing::Publisher;
-use crate::{BlockNumber, Context, Network, Result};
-use chrono::SecondsFormat;
+use crate::publishing::{GoogleStoragePayload, Notification, NotificationEvent, Publisher};
+use crate::{BlockNumber, Context, Labels, Result, Timestamp};
+use chrono::NaiveDate;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-pub struct RewardSlashReport(String);
+pub(super) const HEADER: [&str; 7] = [
+    "Network",
+    "Block Number",
+    "Address",
+    "Description",
+    "Event",
+    "Value",
+    "Symbol",
+];
+
+pub struct RewardSlashReport {
+    period: ReportPeriod,
+    body: String,
+    events: Vec<NotificationEvent>,
+}
+
+/// The amount of the reward/slash in whole tokens. Subscan returns it as
+/// `amount`, which is also encoded as the `Balance` parameter of the event in
+/// `params`. The latter is used if the former is missing. Returns `None` if
+/// neither contains a valid amount.
+fn reward_amount(context: &Context, data: &RewardSlash) -> Option<TokenAmount> {
+    let amount = if data.amount.is_empty() {
+        data.balance_param()?
+    } else {
+        data.amount.clone()
+    };
+
+    to_token_amount(context.network, &amount).ok()
+}
+
+/// Like `reward_amount`, but logs entries without a valid amount, which are
+/// then skipped rather than failing the whole report.
+fn valid_amount(context: &Context, data: &RewardSlash) -> Option<TokenAmount> {
+    let amount = reward_amount(context, data);
+    if amount.is_none() {
+        warn!(
+            event_index = %data.event_index,
+            amount = %data.amount,
+            params = %data.params,
+            "Skipping reward/slash with an invalid amount"
+        );
+    }
+
+    amount
+}
+
+/// Creates a report row for each reward/slash, matching the columns of
+/// `HEADER`. Entries with an amount of zero or an invalid amount are skipped.
+pub(super) fn rows(
+    contexts: &ContextIndex,
+    data: &[ContextData<RewardSlash>],
+) -> Result<Vec<Vec<String>>> {
+    let mut rows = vec![];
+
+    for entry in data {
+        let context = contexts.get(&entry.context_id)?;
+
+        let data = entry.data.as_ref();
+        let amount = match valid_amount(context, data) {
+            Some(amount) => amount,
+            None => continue,
+        };
+
+        if amount.is_zero() {
+            debug!("Skipping reward of 0 for {:?}", context);
+            continue;
+        }
+
+        rows.push(vec![
+            context.network.as_str().to_string(),
+            data.block_num.to_string(),
+            context.stash.clone(),
+            context.description.clone(),
+            data.event_id.clone(),
+            amount.to_string(),
+            context.network.token_symbol().to_string(),
+        ]);
+    }
+
+    Ok(rows)
+}
+
+/// Creates a notification event for each reward/slash. Entries with an amount
+/// of zero or an invalid amount are skipped.
+fn events(
+    contexts: &ContextIndex,
+    data: &[ContextData<RewardSlash>],
+) -> Result<Vec<NotificationEvent>> {
+    let mut events = vec![];
+
+    for entry in data {
+        let context = contexts.get(&entry.context_id)?;
+
+        let data = entry.data.as_ref();
+        let amount = match valid_amount(context, data) {
+            Some(amount) => amount,
+            None => continue,
+        };
+        if amount.is_zero() {
+            continue;
+        }
+
+        events.push(NotificationEvent {
+            id: format!(
+                "{}-{}-{}",
+                context.network.as_str(),
+                context.stash,
+                data.event_index
+            ),
+            account: context.description.clone(),
+            amount,
+            summary: format!(
+                "{} of {} {} for {} ({}), block {}",
+                data.event_id,
+                amount,
+                context.network.token_symbol(),
+                context.stash,
+                context.description,
+                data.block_num
+            ),
+        });
+    }
+
+    Ok(events)
+}
 
 pub struct RewardSlashReportGenerator<'a> {
     reader: DatabaseReader,
     contexts: Arc<RwLock<Vec<Context>>>,
+    range: Option<ReportRange>,
+    window: Option<ReportPeriod>,
+    labels: Labels,
+    delimiter: u8,
+    metadata: bool,
     _p: PhantomData<&'a ()>,
 }
 
@@ -21,10 +152,72 @@ impl<'a> RewardSlashReportGenerator<'a> {
     pub fn new(db: DatabaseReader, contexts: Arc<RwLock<Vec<Context>>>) -> Self {
         RewardSlashReportGenerator {
             reader: db,
-            contexts: contexts,
+            contexts,
+            range: None,
+            window: None,
+            labels: Labels::new(),
+            delimiter: DEFAULT_CSV_DELIMITER,
+            metadata: false,
             _p: PhantomData,
         }
     }
+    /// Only reports the entries within the given range, rather than all of
+    /// them.
+    pub fn with_range(mut self, range: Option<ReportRange>) -> Self {
+        self.range = range;
+        self
+    }
+    /// Only reports the entries from the start of `from` until (excluding)
+    /// `to`, named after the window. Takes precedence over the range.
+    pub fn with_window(mut self, from: NaiveDate, to: NaiveDate) -> Self {
+        self.window = Some(ReportPeriod::Bucket { from, to });
+        self
+    }
+    /// Only reports on the accounts with all of the given labels.
+    pub fn with_labels(mut self, labels: Labels) -> Self {
+        self.labels = labels;
+        self
+    }
+    /// Sets the delimiter of the CSV report.
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+    /// Prepends a block of comment lines describing the report, see
+    /// `ReportMetadata`.
+    pub fn with_metadata(mut self, metadata: bool) -> Self {
+        self.metadata = metadata;
+        self
+    }
+    /// Fetches the entries within the configured range.
+    async fn fetch(&self, contexts: &[Context]) -> Result<Vec<ContextData<'a, RewardSlash>>> {
+        if let Some((from, to)) = self.blocks() {
+            return self.reader.fetch_rewards_slashes(contexts, from, to).await;
+        }
+
+        let (from, to) = self.time_window();
+        self.reader
+            .fetch_rewards_slashes_by_timestamp(contexts, from, to)
+            .await
+    }
+    /// The configured range of blocks, unless a window of time is set.
+    fn blocks(&self) -> Option<(BlockNumber, BlockNumber)> {
+        if self.window.is_some() {
+            return None;
+        }
+
+        self.range.and_then(|range| range.blocks())
+    }
+    /// The configured window of time, or all time.
+    fn time_window(&self) -> (Timestamp, Timestamp) {
+        if let Some(window) = self.window {
+            return window.time();
+        }
+
+        self.range
+            .and_then(|range| range.time(Timestamp::now()))
+            .unwrap_or((Timestamp::from(0), Timestamp::from(i64::MAX as u64)))
+    }
 }
 
 #[async_trait]
@@ -42,26 +235,19 @@ where
     }
     async fn fetch_data(&self) -> Result<Option<Self::Data>> {
         let contexts = self.contexts.read().await;
-        let data = self
-            .reader
-            // Simply fetch everything as of now.
-            .fetch_rewards_slashes(
-                contexts.as_slice(),
-                BlockNumber::from(0),
-                BlockNumber::from(i64::MAX as u64),
-            )
-            .await?;
+        let contexts = select_contexts(&contexts, &self.labels);
 
+        let data = self.fetch(&contexts).await?;
         if data.is_empty() {
             return Ok(None);
-        } else {
-            debug!(
-                "{}: Fetched {} entries from database",
-                <Self as GenerateReport<T>>::name(),
-                data.len()
-            );
         }
 
+        debug!(
+            "{}: Fetched {} entries from database",
+            <Self as GenerateReport<T>>::name(),
+            data.len()
+        );
+
         Ok(Some(data))
     }
     async fn generate(&self, data: &Self::Data) -> Result<Vec<Self::Report>> {
@@ -75,40 +261,25 @@ where
             data.len()
         );
 
+        // All entries are fetched as of now, unless limited to a window.
+        let period = self.window.unwrap_or(ReportPeriod::Full);
         let contexts = self.contexts.read().await;
-        let mut report = String::from("Network,Block Number,Address,Description,Event,Value\n");
-
-        for entry in data {
-            // TODO: Improve performance here.
-            let context = contexts
-                .iter()
-                .find(|c| c.stash == entry.context_id.stash.clone().into_owned())
-                .ok_or(anyhow!("No context found while generating reports"))?;
-
-            let data = entry.data.as_ref();
-            let amount = data.amount.parse::<f64>()?;
-            let amount = match context.network {
-                Network::Kusama => amount / 1_000_000_000_000.0,
-                Network::Polkadot => amount / 10_000_000_000.0,
+        let accounts = select_contexts(&contexts, &self.labels).len();
+        let contexts = index_contexts(contexts.as_slice());
+        let mut report = to_csv(&HEADER, &rows(&contexts, data)?, self.delimiter)?;
+        if self.metadata {
+            let covered = match (self.window, self.range) {
+                (None, Some(range)) => range.to_string(),
+                _ => period.to_string(),
             };
-
-            if amount == 0.0 {
-                debug!("Skipping reward of 0 for {:?}", context);
-                continue;
-            }
-
-            report.push_str(&format!(
-                "{},{},{},{},{},{}\n",
-                context.network.as_str(),
-                data.block_num,
-                context.stash,
-                context.description,
-                data.event_id,
-                amount,
-            ));
+            report = ReportMetadata::new(accounts, covered).prepend_to(report);
         }
 
-        Ok(vec![RewardSlashReport(report)])
+        Ok(vec![RewardSlashReport {
+            period,
+            body: report,
+            events: events(&contexts, data)?,
+        }])
     }
     async fn publish(
         &self,
@@ -128,13 +299,153 @@ where
 
 impl From<RewardSlashReport> for GoogleStoragePayload {
     fn from(val: RewardSlashReport) -> Self {
-        let _date = chrono::offset::Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
-
         GoogleStoragePayload {
-            name: format!("rewards_slashes.csv"),
-            mime_type: "application/vnd.google-apps.document".to_string(),
-            body: val.0.into_bytes(),
+            name: val.period.file_name("rewards_slashes", "csv"),
+            mime_type: CSV_MIME_TYPE.to_string(),
+            body: val.body.into_bytes(),
             is_public: false,
+            module: "rewards_slashes".to_string(),
         }
     }
 }
+
+impl From<RewardSlashReport> for Notification {
+    fn from(val: RewardSlashReport) -> Self {
+        Notification {
+            title: "Rewards and slashes".to_string(),
+            events: val.events,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_api::{Response, RewardsSlashesPage};
+    use crate::publishing::GoogleStorage;
+    use crate::tests::{db, entry};
+    use crate::BlockNumber;
+
+    fn reward<'a>(
+        context: &'a Context,
+        amount: &str,
+        params: &str,
+    ) -> ContextData<'a, RewardSlash> {
+        entry(
+            context,
+            RewardSlash {
+                amount: amount.into(),
+                params: params.to_string(),
+                event_id: "Reward".to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn skip_invalid_amounts() {
+        let alice = Context::alice();
+        let contexts = vec![alice.clone()];
+        let contexts = index_contexts(&contexts);
+
+        let params =
+            r#"[{"type":"AccountId","value":"1a2YiGNu"},{"type":"Balance","value":"25000000000"}]"#;
+        let data = vec![
+            reward(&alice, "10000000000", ""),
+            // The amount is taken from the event parameters if missing.
+            reward(&alice, "", params),
+            reward(
+                &alice,
+                "",
+                r#"[{"type":"BalanceOf<T>","value":5000000000}]"#,
+            ),
+            // Malformed entries are skipped.
+            reward(&alice, "invalid", params),
+            reward(&alice, "NaN", ""),
+            reward(&alice, "", "not json"),
+            reward(&alice, "", r#"[{"type":"AccountId","value":"1a2YiGNu"}]"#),
+            reward(&alice, "", r#"[{"type":"Balance","value":null}]"#),
+        ];
+
+        let amounts: Vec<String> = rows(&contexts, &data)
+            .unwrap()
+            .into_iter()
+            .map(|row| row[5].clone())
+            .collect();
+        assert_eq!(amounts, vec!["1", "2.5", "0.5"]);
+
+        let amounts: Vec<String> = events(&contexts, &data)
+            .unwrap()
+            .into_iter()
+            .map(|event| event.amount.to_string())
+            .collect();
+        assert_eq!(amounts, vec!["1", "2.5", "0.5"]);
+    }
+
+    #[tokio::test]
+    async fn fetch_within_range() {
+        let db = db().await;
+        let alice = Context::alice();
+
+        let mut resp: Response<RewardsSlashesPage> = Default::default();
+        resp.data.list = Some(
+            (0..10)
+                .map(|idx| RewardSlash {
+                    amount: "10000000000".into(),
+                    block_num: BlockNumber::from(idx * 100),
+                    event_index: idx.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+        );
+
+        db.store_reward_slash_event(&alice, &resp).await.unwrap();
+
+        let contexts = Arc::new(RwLock::new(vec![alice]));
+        let generator = RewardSlashReportGenerator::new(db.reader(), Arc::clone(&contexts))
+            .with_range(Some(ReportRange::Blocks {
+                from: Some(BlockNumber::from(300)),
+                to: Some(BlockNumber::from(600)),
+            }));
+
+        let data =
+            <RewardSlashReportGenerator as GenerateReport<GoogleStorage>>::fetch_data(&generator)
+                .await
+                .unwrap()
+                .unwrap();
+
+        let mut blocks: Vec<BlockNumber> = data.iter().map(|entry| entry.data.block_num).collect();
+        blocks.sort();
+        assert_eq!(
+            blocks,
+            (3..=6)
+                .map(|idx| BlockNumber::from(idx * 100))
+                .collect::<Vec<_>>()
+        );
+
+        // Nothing to report outside of the stored blocks.
+        let generator = RewardSlashReportGenerator::new(db.reader(), Arc::clone(&contexts))
+            .with_range(Some(ReportRange::Blocks {
+                from: Some(BlockNumber::from(1_000)),
+                to: None,
+            }));
+
+        assert!(
+            <RewardSlashReportGenerator as GenerateReport<GoogleStorage>>::fetch_data(&generator)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // All entries were stored just now.
+        let generator = RewardSlashReportGenerator::new(db.reader(), contexts)
+            .with_range(Some(ReportRange::LastDays(1)));
+
+        let data =
+            <RewardSlashReportGenerator as GenerateReport<GoogleStorage>>::fetch_data(&generator)
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(data.len(), 10);
+    }
+}
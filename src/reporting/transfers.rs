@@ -1,20 +1,431 @@
-use super::GenerateReport;
-use crate::chain_api::Transfer;
-use crate::database::{ContextData, DatabaseReader};
-use crate::publishing::GoogleStoragePayload;
-use crate::publishing::Publisher;
-use crate::{Context, Result, Timestamp};
-use chrono::SecondsFormat;
+use super::{
+    index_contexts, select_contexts, to_csv, to_decimal_amount, to_token_amount, ContextIndex,
+    GenerateReport, ReportBuckets, ReportFormat, ReportMetadata, ReportPeriod, AMOUNT_SCALE,
+    DEFAULT_CSV_DELIMITER,
+};
+use crate::chain_api::{Parent, TokenAmount, Transfer};
+use crate::database::{ContextData, DatabaseReader, TransferSort};
+use crate::publishing::{GoogleStoragePayload, Notification, NotificationEvent, Publisher};
+use crate::{Context, ExtrinsicType, Labels, Result};
+use arrow_array::{
+    ArrayRef, BooleanArray, Decimal128Array, Int64Array, RecordBatch, StringArray,
+    TimestampMillisecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::{NaiveDate, Utc};
+use parquet::arrow::ArrowWriter;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// The columns of the report, unless selected otherwise.
+pub(super) const HEADER: [&str; 11] = [
+    "Network",
+    "Block Number",
+    "Block Timestamp",
+    "From",
+    "Description",
+    "To",
+    "Amount",
+    "Symbol",
+    "Fee",
+    "Extrinsic Index",
+    "Success",
+];
+
+/// All available columns, including the ones which are only included if
+/// selected. Starts with the columns of `HEADER`.
+const COLUMNS: [&str; 14] = [
+    "Network",
+    "Block Number",
+    "Block Timestamp",
+    "From",
+    "Description",
+    "To",
+    "Amount",
+    "Symbol",
+    "Fee",
+    "Extrinsic Index",
+    "Success",
+    "From Display",
+    "To Display",
+    "Labels",
+];
+
+/// A column of the transfers report. Declared in the order of `COLUMNS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferColumn {
+    Network,
+    BlockNumber,
+    BlockTimestamp,
+    From,
+    Description,
+    To,
+    Amount,
+    Symbol,
+    Fee,
+    ExtrinsicIndex,
+    Success,
+    /// The on-chain identity of the sender. For sub-accounts, the identity of
+    /// the parent followed by the name of the sub-account.
+    FromDisplay,
+    /// Like `FromDisplay`, for the recipient.
+    ToDisplay,
+    /// The labels of the account at the time the transfer was stored, e.g.
+    /// `category=validator;team=treasury`. Empty for transfers stored before
+    /// labels were recorded.
+    Labels,
+}
+
+impl TransferColumn {
+    /// The position of the column in `COLUMNS` and the rows of `all_rows`.
+    fn index(self) -> usize {
+        self as usize
+    }
+    /// The type of the column in Parquet reports. Amounts are exact decimals
+    /// in whole tokens.
+    fn data_type(self) -> DataType {
+        match self {
+            TransferColumn::BlockNumber => DataType::Int64,
+            TransferColumn::BlockTimestamp => {
+                DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))
+            }
+            TransferColumn::Amount | TransferColumn::Fee => {
+                DataType::Decimal128(DECIMAL_PRECISION, AMOUNT_SCALE as i8)
+            }
+            TransferColumn::Success => DataType::Boolean,
+            _ => DataType::Utf8,
+        }
+    }
+}
+
+/// The maximum precision of 128-bit decimals.
+const DECIMAL_PRECISION: u8 = 38;
+
+/// All columns of the report, in the order of `COLUMNS`.
+const ALL_COLUMNS: [TransferColumn; 14] = [
+    TransferColumn::Network,
+    TransferColumn::BlockNumber,
+    TransferColumn::BlockTimestamp,
+    TransferColumn::From,
+    TransferColumn::Description,
+    TransferColumn::To,
+    TransferColumn::Amount,
+    TransferColumn::Symbol,
+    TransferColumn::Fee,
+    TransferColumn::ExtrinsicIndex,
+    TransferColumn::Success,
+    TransferColumn::FromDisplay,
+    TransferColumn::ToDisplay,
+    TransferColumn::Labels,
+];
+
+/// Serializes the given columns of the transfers to a Parquet file, with the
+/// types of `TransferColumn::data_type`. Columns are named like in the CSV
+/// report.
+fn to_parquet(
+    contexts: &ContextIndex,
+    data: &[ContextData<Transfer>],
+    columns: &[TransferColumn],
+) -> Result<Vec<u8>> {
+    // The text columns are the same as in the CSV report.
+    let rows = all_rows(contexts, data)?;
+
+    let mut fields = vec![];
+    let mut arrays = vec![];
+    for &column in columns {
+        let array: ArrayRef = match column {
+            TransferColumn::BlockNumber => Arc::new(Int64Array::from(
+                data.iter()
+                    .map(|entry| Ok(i64::try_from(entry.data.block_num.as_u64())?))
+                    .collect::<Result<Vec<_>>>()?,
+            )),
+            TransferColumn::BlockTimestamp => Arc::new(
+                TimestampMillisecondArray::from(
+                    data.iter()
+                        .map(
+                            |entry| Ok(i64::try_from(entry.data.block_timestamp.as_secs())? * 1000),
+                        )
+                        .collect::<Result<Vec<_>>>()?,
+                )
+                .with_timezone("UTC"),
+            ),
+            TransferColumn::Amount | TransferColumn::Fee => Arc::new(
+                Decimal128Array::from(
+                    data.iter()
+                        .map(|entry| {
+                            let network = contexts.get(&entry.context_id)?.network;
+                            let amount = if column == TransferColumn::Amount {
+                                &entry.data.amount
+                            } else {
+                                &entry.data.fee
+                            };
+                            // Like `to_fee_amount`, a missing fee counts as zero.
+                            if column == TransferColumn::Fee && amount.is_empty() {
+                                return Ok(0);
+                            }
+                            to_decimal_amount(network, amount)
+                        })
+                        .collect::<Result<Vec<_>>>()?,
+                )
+                .with_precision_and_scale(DECIMAL_PRECISION, AMOUNT_SCALE as i8)?,
+            ),
+            TransferColumn::Success => Arc::new(BooleanArray::from(
+                data.iter()
+                    .map(|entry| entry.data.success)
+                    .collect::<Vec<_>>(),
+            )),
+            _ => Arc::new(StringArray::from(
+                rows.iter()
+                    .map(|row| row[column.index()].as_str())
+                    .collect::<Vec<_>>(),
+            )),
+        };
+
+        fields.push(Field::new(
+            COLUMNS[column.index()],
+            column.data_type(),
+            false,
+        ));
+        arrays.push(array);
+    }
+
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(Arc::clone(&schema), arrays)?;
+
+    let mut body = vec![];
+    let mut writer = ArrowWriter::try_new(&mut body, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+
+    Ok(body)
+}
+
+/// Keeps the given columns of the header and rows, in the given order.
+fn select_columns(
+    columns: &[TransferColumn],
+    rows: Vec<Vec<String>>,
+) -> (Vec<&'static str>, Vec<Vec<String>>) {
+    let header = columns
+        .iter()
+        .map(|column| COLUMNS[column.index()])
+        .collect();
+    let rows = rows
+        .into_iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|column| row[column.index()].clone())
+                .collect()
+        })
+        .collect();
+
+    (header, rows)
+}
+
+pub(super) const FEES_HEADER: [&str; 5] = ["Network", "Address", "Description", "Fees", "Symbol"];
+
 #[derive(Debug, Clone)]
-pub struct TransferReport(String);
+pub struct TransferReport {
+    period: ReportPeriod,
+    /// The part of the period, if split by `with_max_rows`. Starts at one.
+    part: Option<usize>,
+    format: ReportFormat,
+    body: Vec<u8>,
+    events: Vec<NotificationEvent>,
+}
+
+/// Creates a report row for each transfer, matching the columns of `HEADER`.
+pub(super) fn rows(
+    contexts: &ContextIndex,
+    data: &[ContextData<Transfer>],
+) -> Result<Vec<Vec<String>>> {
+    let mut rows = all_rows(contexts, data)?;
+    for row in &mut rows {
+        row.truncate(HEADER.len());
+    }
+
+    Ok(rows)
+}
+
+/// Like `rows`, but matching all columns of `COLUMNS`.
+fn all_rows(contexts: &ContextIndex, data: &[ContextData<Transfer>]) -> Result<Vec<Vec<String>>> {
+    let mut rows = vec![];
+
+    for entry in data {
+        let context = contexts.get(&entry.context_id)?;
+
+        let data = entry.data.as_ref();
+        rows.push(vec![
+            context.network.as_str().to_string(),
+            data.block_num.to_string(),
+            data.block_timestamp.to_string(),
+            data.from.clone(),
+            context.description.clone(),
+            data.to.clone(),
+            to_token_amount(context.network, &data.amount)?.to_string(),
+            context.network.token_symbol().to_string(),
+            to_fee_amount(context, data)?.to_string(),
+            data.extrinsic_index.to_string(),
+            data.success.to_string(),
+            account_display(
+                &data.from_account_display.display,
+                data.from_account_display.parent.as_ref(),
+            ),
+            account_display(
+                &data.to_account_display.display,
+                data.to_account_display.parent.as_ref(),
+            ),
+            format_labels(&entry.labels),
+        ]);
+    }
+
+    Ok(rows)
+}
+
+/// The labels as `key=value` pairs separated by `;`, ordered by key.
+fn format_labels(labels: &Labels) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// The on-chain identity of an account, e.g. `Alice`, or `Alice/stash` for a
+/// sub-account. Empty if the account has no identity.
+fn account_display(display: &str, parent: Option<&Parent>) -> String {
+    match parent {
+        Some(parent) if !parent.display.is_empty() => {
+            if parent.sub_symbol.is_empty() {
+                parent.display.clone()
+            } else {
+                format!("{}/{}", parent.display, parent.sub_symbol)
+            }
+        }
+        _ => display.to_string(),
+    }
+}
+
+/// The fee of the transfer in whole tokens. Subscan does not always return
+/// a fee, in which case it is treated as zero.
+fn to_fee_amount(context: &Context, data: &Transfer) -> Result<TokenAmount> {
+    if data.fee.is_empty() {
+        return Ok(TokenAmount::new(0, context.network.token_decimals()));
+    }
+
+    to_token_amount(context.network, &data.fee)
+}
+
+/// Sums up the fees paid by each account, matching the columns of
+/// `FEES_HEADER`. Only outgoing transfers are counted (see
+/// `Transfer::is_outgoing`), and transfers of the same batch call share a
+/// single fee.
+pub(super) fn fee_totals(
+    contexts: &ContextIndex,
+    data: &[ContextData<Transfer>],
+) -> Result<Vec<Vec<String>>> {
+    // Accounts are listed in order of their first transfer.
+    let mut totals: Vec<(&Context, TokenAmount)> = vec![];
+    let mut paid = HashSet::new();
+
+    for entry in data {
+        let context = contexts.get(&entry.context_id)?;
+
+        let data = entry.data.as_ref();
+        if !data.is_outgoing(&context.stash)
+            || !paid.insert((context.network, &context.stash, &data.extrinsic_index))
+        {
+            continue;
+        }
+
+        let fee = to_fee_amount(context, data)?;
+        match totals.iter_mut().find(|(c, _)| c.id() == context.id()) {
+            Some((_, total)) => {
+                *total = total
+                    .checked_add(fee)
+                    .ok_or_else(|| anyhow!("total fee of {} out of range", context.stash))?
+            }
+            None => totals.push((context, fee)),
+        }
+    }
+
+    Ok(totals
+        .into_iter()
+        .map(|(context, total)| {
+            vec![
+                context.network.as_str().to_string(),
+                context.stash.clone(),
+                context.description.clone(),
+                total.to_string(),
+                context.network.token_symbol().to_string(),
+            ]
+        })
+        .collect())
+}
+
+/// Creates a notification event for each transfer.
+fn events(
+    contexts: &ContextIndex,
+    data: &[ContextData<Transfer>],
+) -> Result<Vec<NotificationEvent>> {
+    let mut events = vec![];
+
+    for entry in data {
+        let context = contexts.get(&entry.context_id)?;
+
+        events.push(transfer_event(context, entry.data.as_ref())?);
+    }
+
+    Ok(events)
+}
+
+/// Creates the notification event of a single transfer of the account.
+pub(crate) fn transfer_event(context: &Context, data: &Transfer) -> Result<NotificationEvent> {
+    let amount = to_token_amount(context.network, &data.amount)?;
+
+    Ok(NotificationEvent {
+        id: format!(
+            "{}-{}-{}-{}-{}",
+            context.network.as_str(),
+            data.extrinsic_index,
+            data.from,
+            data.to,
+            data.amount
+        ),
+        account: context.description.clone(),
+        amount,
+        summary: format!(
+            "Transfer of {} {} from {} to {} ({}), block {}",
+            amount,
+            context.network.token_symbol(),
+            data.from,
+            data.to,
+            context.description,
+            data.block_num
+        ),
+    })
+}
 
 pub struct TransferReportGenerator<'a> {
     reader: DatabaseReader,
     contexts: Arc<RwLock<Vec<Context>>>,
+    sort: TransferSort,
+    counterparty: Option<String>,
+    extrinsics: Vec<ExtrinsicType>,
+    exclude_failed: bool,
+    labels: Labels,
+    buckets: Option<ReportBuckets>,
+    window: Option<ReportPeriod>,
+    columns: Option<Vec<TransferColumn>>,
+    max_rows: Option<usize>,
+    format: ReportFormat,
+    delimiter: u8,
+    metadata: bool,
     _p: PhantomData<&'a ()>,
 }
 
@@ -22,10 +433,180 @@ impl<'a> TransferReportGenerator<'a> {
     pub fn new(db: DatabaseReader, contexts: Arc<RwLock<Vec<Context>>>) -> Self {
         TransferReportGenerator {
             reader: db,
-            contexts: contexts,
+            contexts,
+            sort: Default::default(),
+            counterparty: None,
+            extrinsics: vec![],
+            exclude_failed: false,
+            labels: Labels::new(),
+            buckets: None,
+            window: None,
+            columns: None,
+            max_rows: None,
+            format: ReportFormat::default(),
+            delimiter: DEFAULT_CSV_DELIMITER,
+            metadata: false,
             _p: PhantomData,
         }
     }
+    /// Sets the order of the transfers in the report.
+    pub fn with_sort(mut self, sort: TransferSort) -> Self {
+        self.sort = sort;
+        self
+    }
+    /// Only reports transfers from or to the given address, if specified.
+    pub fn with_counterparty(mut self, counterparty: Option<String>) -> Self {
+        self.counterparty = counterparty;
+        self
+    }
+    /// Only reports transfers of the given extrinsic types, or all transfers
+    /// if empty.
+    pub fn with_extrinsics(mut self, extrinsics: Vec<ExtrinsicType>) -> Self {
+        self.extrinsics = extrinsics;
+        self
+    }
+    /// Leaves out the transfers of failed extrinsics, which moved no funds.
+    /// Included by default, see the `success` column.
+    pub fn with_exclude_failed(mut self, exclude: bool) -> Self {
+        self.exclude_failed = exclude;
+        self
+    }
+    /// Only reports on the accounts with all of the given labels.
+    pub fn with_labels(mut self, labels: Labels) -> Self {
+        self.labels = labels;
+        self
+    }
+    /// Generates one report per bucket of time instead of a single report
+    /// of all transfers.
+    pub fn with_buckets(mut self, buckets: Option<ReportBuckets>) -> Self {
+        self.buckets = buckets;
+        self
+    }
+    /// Generates a single report of the transfers from the start of `from`
+    /// until (excluding) `to`, named after the window. Takes precedence over
+    /// the buckets.
+    pub fn with_window(mut self, from: NaiveDate, to: NaiveDate) -> Self {
+        self.window = Some(ReportPeriod::Bucket { from, to });
+        self
+    }
+    /// Only includes the given columns in the report, in the given order,
+    /// instead of the ones of `HEADER`.
+    pub fn with_columns(mut self, columns: Option<Vec<TransferColumn>>) -> Self {
+        self.columns = columns;
+        self
+    }
+    /// Splits each report into parts of at most the given amount of rows,
+    /// e.g. for spreadsheet tools which can not handle large files. Reports
+    /// which fit are not split. Zero is treated as no limit.
+    pub fn with_max_rows(mut self, max_rows: Option<usize>) -> Self {
+        self.max_rows = max_rows.filter(|max_rows| *max_rows > 0);
+        self
+    }
+    /// Sets the file format of the report, CSV by default.
+    pub fn with_format(mut self, format: ReportFormat) -> Self {
+        self.format = format;
+        self
+    }
+    /// Sets the delimiter of the CSV report.
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+    /// Prepends a block of comment lines describing the report, see
+    /// `ReportMetadata`.
+    pub fn with_metadata(mut self, metadata: bool) -> Self {
+        self.metadata = metadata;
+        self
+    }
+    /// The transfers of the configured extrinsic types, without the failed
+    /// ones if excluded.
+    fn select_transfers<'d>(
+        &self,
+        data: &'d [ContextData<'a, Transfer>],
+    ) -> Cow<'d, [ContextData<'a, Transfer>]> {
+        if self.extrinsics.is_empty() && !self.exclude_failed {
+            return Cow::Borrowed(data);
+        }
+
+        Cow::Owned(
+            data.iter()
+                .filter(|entry| {
+                    self.extrinsics.is_empty()
+                        || self
+                            .extrinsics
+                            .iter()
+                            .any(|extrinsic| extrinsic.matches_module(&entry.data.module))
+                })
+                .filter(|entry| !self.exclude_failed || entry.data.success)
+                .cloned()
+                .collect(),
+        )
+    }
+    /// The periods to generate a report for.
+    fn periods(&self) -> Vec<ReportPeriod> {
+        if let Some(window) = self.window {
+            return vec![window];
+        }
+
+        match self.buckets {
+            Some(buckets) => buckets.periods(Utc::now().date_naive()),
+            None => vec![ReportPeriod::Full],
+        }
+    }
+    /// The reports of the given period, covering the given amount of
+    /// accounts. More than one if split into parts, see `with_max_rows`.
+    fn reports(
+        &self,
+        contexts: &ContextIndex,
+        accounts: usize,
+        period: ReportPeriod,
+        data: &[ContextData<Transfer>],
+    ) -> Result<Vec<TransferReport>> {
+        let chunks: Vec<&[ContextData<Transfer>]> = match self.max_rows {
+            Some(max_rows) if data.len() > max_rows => data.chunks(max_rows).collect(),
+            _ => vec![data],
+        };
+        let split = chunks.len() > 1;
+
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(idx, data)| {
+                let body = match self.format {
+                    ReportFormat::Csv => {
+                        let mut body = match &self.columns {
+                            Some(columns) => {
+                                let (header, rows) =
+                                    select_columns(columns, all_rows(contexts, data)?);
+                                to_csv(&header, &rows, self.delimiter)?
+                            }
+                            None => to_csv(&HEADER, &rows(contexts, data)?, self.delimiter)?,
+                        };
+                        if self.metadata {
+                            body =
+                                ReportMetadata::new(accounts, period.to_string()).prepend_to(body);
+                        }
+                        body.into_bytes()
+                    }
+                    ReportFormat::Parquet => {
+                        let columns = match &self.columns {
+                            Some(columns) => columns.as_slice(),
+                            None => &ALL_COLUMNS[..HEADER.len()],
+                        };
+                        to_parquet(contexts, data, columns)?
+                    }
+                };
+
+                Ok(TransferReport {
+                    period,
+                    part: if split { Some(idx + 1) } else { None },
+                    format: self.format,
+                    body,
+                    events: events(contexts, data)?,
+                })
+            })
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -43,29 +624,33 @@ where
     }
     async fn fetch_data(&self) -> Result<Option<Self::Data>> {
         let contexts = self.contexts.read().await;
+        let contexts = select_contexts(&contexts, &self.labels);
+
+        // The window covered by all periods, everything as of now unless
+        // split into buckets.
+        let periods = self.periods();
+        let (from, to) = match (periods.first(), periods.last()) {
+            (Some(first), Some(last)) => (first.time().0, last.time().1),
+            _ => return Ok(None),
+        };
         let data = self
             .reader
-            // Simply fetch everything as of now.
-            .fetch_transfers(
-                contexts.as_slice(),
-                Timestamp::from(0),
-                Timestamp::from(i64::MAX as u64),
-            )
+            .fetch_transfers_sorted(&contexts, from, to, self.sort, self.counterparty.as_deref())
             .await?;
-
         if data.is_empty() {
             return Ok(None);
-        } else {
-            debug!(
-                "{}: Fetched {} entries from database",
-                <Self as GenerateReport<T>>::name(),
-                data.len()
-            );
         }
 
+        debug!(
+            "{}: Fetched {} entries from database",
+            <Self as GenerateReport<T>>::name(),
+            data.len()
+        );
+
         Ok(Some(data))
     }
     async fn generate(&self, data: &Self::Data) -> Result<Vec<Self::Report>> {
+        let data = self.select_transfers(data);
         if data.is_empty() {
             return Ok(vec![]);
         }
@@ -77,34 +662,37 @@ where
         );
 
         let contexts = self.contexts.read().await;
+        let accounts = select_contexts(&contexts, &self.labels).len();
+        let contexts = index_contexts(contexts.as_slice());
 
-        // List all transfers.
-        let mut report =
-            String::from("Network,Block Number,Block Timestamp,From,Description,To,Amount,Extrinsic Index,Success\n");
+        if self.buckets.is_none() && self.window.is_none() {
+            // List all transfers.
+            return self.reports(&contexts, accounts, ReportPeriod::Full, &data);
+        }
 
-        for entry in data {
-            // TODO: Improve performance here.
-            let context = contexts
+        // Partitions the entries into the buckets in a single pass, borrowing
+        // rather than cloning them.
+        let periods = self.periods();
+        let times: Vec<_> = periods.iter().map(|period| period.time()).collect();
+        let mut buckets: Vec<Vec<ContextData<Transfer>>> = periods.iter().map(|_| vec![]).collect();
+        for entry in data.iter() {
+            let timestamp = entry.data.block_timestamp;
+            if let Some(idx) = times
                 .iter()
-                .find(|c| c.stash == entry.context_id.stash.clone().into_owned())
-                .ok_or(anyhow!("No context found while generating reports"))?;
-
-            let data = entry.data.as_ref();
-            report.push_str(&format!(
-                "{},{},{},{},{},{},{},{},{}\n",
-                context.network.as_str(),
-                data.block_num,
-                data.block_timestamp,
-                data.from,
-                context.description,
-                data.to,
-                data.amount,
-                data.extrinsic_index,
-                data.success,
-            ));
-        }
-
-        Ok(vec![TransferReport(report)])
+                .position(|(from, to)| timestamp >= *from && timestamp <= *to)
+            {
+                buckets[idx].push(entry.borrowed());
+            }
+        }
+
+        // One report per bucket, including empty ones, so there is a file for
+        // each bucket of the window.
+        let mut reports = vec![];
+        for (period, entries) in periods.into_iter().zip(buckets) {
+            reports.extend(self.reports(&contexts, accounts, period, &entries)?);
+        }
+
+        Ok(reports)
     }
     async fn publish(
         &self,
@@ -124,13 +712,841 @@ where
 
 impl From<TransferReport> for GoogleStoragePayload {
     fn from(val: TransferReport) -> Self {
-        let _date = chrono::offset::Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        let extension = val.format.extension();
+        let name = val.period.file_name("report_transfer", extension);
 
         GoogleStoragePayload {
-            name: format!("report_transfer.csv"),
-            mime_type: "application/vnd.google-apps.document".to_string(),
-            body: val.0.into_bytes(),
+            name: match val.part {
+                Some(part) => format!(
+                    "{}_part{}.{}",
+                    name.trim_end_matches(&format!(".{}", extension)),
+                    part,
+                    extension
+                ),
+                None => name,
+            },
+            mime_type: val.format.mime_type().to_string(),
+            body: val.body,
             is_public: false,
+            module: "transfers".to_string(),
         }
     }
 }
+
+impl From<TransferReport> for Notification {
+    fn from(val: TransferReport) -> Self {
+        Notification {
+            title: "Transfers".to_string(),
+            events: val.events,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_api::{Response, ToAccountDisplay, TransferDirection, TransfersPage};
+    use crate::database::{SortDirection, TransferSortKey};
+    use crate::publishing::GoogleStorage;
+    use crate::reporting::BucketSize;
+    use crate::tests::{db, entry, memory_db};
+    use crate::{BlockNumber, Network, Timestamp};
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Decimal128Type, Int64Type, TimestampMillisecondType};
+    use bytes::Bytes;
+    use chrono::NaiveDate;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn same_stash_on_both_networks() {
+        let polkadot = Context {
+            description: "Polkadot account".to_string(),
+            ..Context::alice()
+        };
+        let kusama = Context {
+            network: Network::Kusama,
+            description: "Kusama account".to_string(),
+            ..Context::alice()
+        };
+        let contexts = vec![polkadot.clone(), kusama.clone()];
+        let contexts = index_contexts(&contexts);
+
+        let transfer = Transfer {
+            amount: "1000000000000".into(),
+            ..Default::default()
+        };
+
+        let rows = rows(
+            &contexts,
+            &[entry(&kusama, transfer.clone()), entry(&polkadot, transfer)],
+        )
+        .unwrap();
+        let columns: Vec<(&str, &str, &str, &str)> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row[0].as_str(),
+                    row[4].as_str(),
+                    row[6].as_str(),
+                    row[7].as_str(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            columns,
+            vec![
+                ("kusama", "Kusama account", "1", "KSM"),
+                ("polkadot", "Polkadot account", "100", "DOT"),
+            ]
+        );
+    }
+
+    #[test]
+    fn sum_fees_per_account() {
+        let alice = Context::alice();
+        let bob = Context::bob();
+        let contexts = vec![alice.clone(), bob.clone()];
+        let contexts = index_contexts(&contexts);
+
+        fn transfer<'a>(
+            context: &'a Context,
+            from: &str,
+            fee: &str,
+            extrinsic_index: &str,
+        ) -> ContextData<'a, Transfer> {
+            entry(
+                context,
+                Transfer {
+                    amount: "0".into(),
+                    from: from.to_string(),
+                    fee: fee.into(),
+                    extrinsic_index: extrinsic_index.to_string().into(),
+                    ..Default::default()
+                },
+            )
+        }
+
+        let data = vec![
+            transfer(&alice, &alice.stash, "15000000000", "1-1"),
+            // Transfers of the same batch call share the fee.
+            transfer(&alice, &alice.stash, "2500000000", "2-1"),
+            transfer(&alice, &alice.stash, "2500000000", "2-1"),
+            // Fees of incoming transfers are paid by the sender.
+            transfer(&alice, &bob.stash, "10000000000", "3-1"),
+            transfer(&bob, &bob.stash, "1250000000", "4-1"),
+            transfer(&bob, &bob.stash, "", "5-1"),
+            // Sent by Bob via a proxy, as reported by Subscan.
+            {
+                let mut entry = transfer(&bob, &alice.stash, "5000000000", "6-1");
+                entry.data.to_mut().direction = Some(TransferDirection::Outgoing);
+                entry
+            },
+            // The direction reported by Subscan takes precedence over the sender.
+            {
+                let mut entry = transfer(&alice, &alice.stash, "5000000000", "7-1");
+                entry.data.to_mut().direction = Some(TransferDirection::Incoming);
+                entry
+            },
+        ];
+
+        let totals = fee_totals(&contexts, &data).unwrap();
+        assert_eq!(
+            totals,
+            vec![
+                vec![
+                    "polkadot".to_string(),
+                    alice.stash.clone(),
+                    alice.description.clone(),
+                    "1.75".to_string(),
+                    "DOT".to_string(),
+                ],
+                vec![
+                    "polkadot".to_string(),
+                    bob.stash.clone(),
+                    bob.description.clone(),
+                    "0.625".to_string(),
+                    "DOT".to_string(),
+                ],
+            ]
+        );
+
+        // The fee is listed per transfer as well.
+        let rows = rows(&contexts, &data).unwrap();
+        assert_eq!(rows[0][8], "1.5");
+        assert_eq!(rows[5][8], "0");
+
+        // Summed up exactly, without floating point rounding.
+        let data = vec![
+            transfer(&alice, &alice.stash, "1000000000", "1-1"),
+            transfer(&alice, &alice.stash, "2000000000", "2-1"),
+        ];
+        let totals = fee_totals(&contexts, &data).unwrap();
+        assert_eq!(totals[0][3], "0.3");
+    }
+
+    #[test]
+    fn upload_as_plain_csv() {
+        let report = TransferReport {
+            period: ReportPeriod::Full,
+            part: None,
+            format: ReportFormat::Csv,
+            body: b"Network\npolkadot\n".to_vec(),
+            events: vec![],
+        };
+
+        let payload = GoogleStoragePayload::from(report);
+        assert_eq!(payload.name, "report_transfer.csv");
+        assert_eq!(payload.mime_type, "text/csv");
+        assert_eq!(payload.body, b"Network\npolkadot\n");
+    }
+
+    #[tokio::test]
+    async fn generate_with_many_contexts() {
+        let contexts: Vec<Context> = (0..1_000)
+            .map(|idx| Context {
+                stash: format!("stash_{}", idx),
+                network: Network::Polkadot,
+                description: format!("account_{}", idx),
+                labels: Default::default(),
+            })
+            .collect();
+
+        // Entries are listed in reverse order of the contexts.
+        let data: Vec<ContextData<Transfer>> = contexts
+            .iter()
+            .rev()
+            .map(|context| {
+                entry(
+                    context,
+                    Transfer {
+                        from: context.stash.clone(),
+                        amount: "10000000000".into(),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+
+        let reader = memory_db().await.reader();
+        let generator =
+            TransferReportGenerator::new(reader, Arc::new(RwLock::new(contexts.clone())));
+
+        let reports =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+
+        assert_eq!(reports.len(), 1);
+
+        let rows: Vec<&str> = std::str::from_utf8(&reports[0].body)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .collect();
+        assert_eq!(rows.len(), contexts.len());
+
+        // Each row must contain the description of its own context.
+        for (row, context) in rows.iter().zip(contexts.iter().rev()) {
+            let columns: Vec<&str> = row.split(',').collect();
+            assert_eq!(columns[3], context.stash);
+            assert_eq!(columns[4], context.description);
+            assert_eq!(columns[6], "1");
+            assert_eq!(columns[7], "DOT");
+        }
+    }
+
+    #[tokio::test]
+    async fn generate_for_single_module() {
+        let alice = Context::alice();
+        let transfer = |module: &str, index: &str| {
+            entry(
+                &alice,
+                Transfer {
+                    from: alice.stash.clone(),
+                    amount: "10000000000".into(),
+                    module: module.to_string(),
+                    extrinsic_index: index.to_string().into(),
+                    ..Default::default()
+                },
+            )
+        };
+
+        let data = vec![
+            transfer("balances", "1-1"),
+            transfer("utility", "2-1"),
+            transfer("Balances", "3-1"),
+        ];
+
+        let reader = memory_db().await.reader();
+        let generator =
+            TransferReportGenerator::new(reader, Arc::new(RwLock::new(vec![alice.clone()])))
+                .with_extrinsics(vec![ExtrinsicType {
+                    module: "balances".to_string(),
+                    call: Some("transfer_keep_alive".to_string()),
+                }]);
+
+        let reports =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+
+        assert_eq!(reports.len(), 1);
+        let events: Vec<&str> = reports[0].events.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|id| !id.contains("2-1")));
+
+        // Nothing to report if no transfer matches.
+        let generator = generator.with_extrinsics(vec![ExtrinsicType {
+            module: "staking".to_string(),
+            call: None,
+        }]);
+        let reports =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+        assert!(reports.is_empty());
+    }
+
+    #[tokio::test]
+    async fn generate_without_failed_transfers() {
+        let alice = Context::alice();
+        let transfer = |index: &str, success: bool| {
+            entry(
+                &alice,
+                Transfer {
+                    from: alice.stash.clone(),
+                    amount: "10000000000".into(),
+                    extrinsic_index: index.to_string().into(),
+                    success,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let data = vec![
+            transfer("1-1", true),
+            transfer("2-1", false),
+            transfer("3-1", true),
+        ];
+
+        let reader = memory_db().await.reader();
+        let generator =
+            TransferReportGenerator::new(reader, Arc::new(RwLock::new(vec![alice.clone()])));
+
+        // Failed transfers are included by default.
+        let reports =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+        let rows: Vec<&str> = std::str::from_utf8(&reports[0].body)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows.iter().filter(|row| row.ends_with(",false")).count(), 1);
+
+        let generator = generator.with_exclude_failed(true);
+        let reports =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+        let rows: Vec<&str> = std::str::from_utf8(&reports[0].body)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| !row.ends_with(",false")));
+        let events: Vec<&str> = reports[0].events.iter().map(|e| e.id.as_str()).collect();
+        assert!(events.iter().all(|id| !id.contains("2-1")));
+
+        // Nothing to report if all transfers failed.
+        let failed = vec![transfer("2-1", false)];
+        let reports = <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(
+            &generator, &failed,
+        )
+        .await
+        .unwrap();
+        assert!(reports.is_empty());
+    }
+
+    #[tokio::test]
+    async fn generate_selected_columns() {
+        let alice = Context::alice();
+        let data = vec![entry(
+            &alice,
+            Transfer {
+                from: alice.stash.clone(),
+                amount: "10000000000".into(),
+                ..Default::default()
+            },
+        )];
+
+        let reader = memory_db().await.reader();
+        let columns: Vec<TransferColumn> = serde_yaml::from_str("[amount, symbol, from]").unwrap();
+        let generator =
+            TransferReportGenerator::new(reader, Arc::new(RwLock::new(vec![alice.clone()])))
+                .with_columns(Some(columns));
+
+        let reports =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+        let payload = GoogleStoragePayload::from(reports.into_iter().next().unwrap());
+
+        assert_eq!(
+            std::str::from_utf8(&payload.body).unwrap(),
+            format!("Amount,Symbol,From\n1,DOT,{}\n", alice.stash)
+        );
+
+        // Unknown columns are rejected when parsing the config.
+        assert!(serde_yaml::from_str::<Vec<TransferColumn>>("[amount, memo]").is_err());
+    }
+
+    #[tokio::test]
+    async fn generate_parquet() {
+        let alice = Context::alice();
+        let mut kusama = Context::bob();
+        kusama.network = Network::Kusama;
+
+        fn transfer<'a>(
+            context: &'a Context,
+            amount: &str,
+            fee: &str,
+            block: u64,
+            success: bool,
+        ) -> ContextData<'a, Transfer> {
+            entry(
+                context,
+                Transfer {
+                    from: context.stash.clone(),
+                    amount: amount.into(),
+                    fee: fee.into(),
+                    block_num: BlockNumber::from(block),
+                    block_timestamp: Timestamp::from(1_700_000_000 + block),
+                    extrinsic_index: format!("{}-1", block).into(),
+                    success,
+                    ..Default::default()
+                },
+            )
+        }
+
+        let data = vec![
+            transfer(&alice, "25000000000", "", 10, true),
+            transfer(&kusama, "1234567890123", "1000000000", 11, false),
+        ];
+
+        let reader = memory_db().await.reader();
+        let generator = TransferReportGenerator::new(
+            reader,
+            Arc::new(RwLock::new(vec![alice.clone(), kusama.clone()])),
+        )
+        .with_format(ReportFormat::Parquet);
+
+        let reports =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+        let payload = GoogleStoragePayload::from(reports.into_iter().next().unwrap());
+        assert_eq!(payload.name, "report_transfer.parquet");
+        assert_eq!(payload.mime_type, "application/vnd.apache.parquet");
+
+        let batches: Vec<RecordBatch> =
+            ParquetRecordBatchReaderBuilder::try_new(Bytes::from(payload.body))
+                .unwrap()
+                .build()
+                .unwrap()
+                .collect::<std::result::Result<_, _>>()
+                .unwrap();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+
+        // The columns of the CSV report, typed.
+        let schema = batch.schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, HEADER);
+        for (field, column) in schema.fields().iter().zip(ALL_COLUMNS.iter()) {
+            assert_eq!(field.data_type(), &column.data_type());
+        }
+
+        let column = |name: &str| batch.column_by_name(name).unwrap();
+        assert_eq!(
+            column("Block Number").as_primitive::<Int64Type>().values(),
+            &[10, 11]
+        );
+        assert_eq!(
+            column("Block Timestamp")
+                .as_primitive::<TimestampMillisecondType>()
+                .values(),
+            &[1_700_000_010_000, 1_700_000_011_000]
+        );
+        // In whole tokens, with 12 decimals.
+        let amounts = column("Amount").as_primitive::<Decimal128Type>();
+        assert_eq!(amounts.value_as_string(0), "2.500000000000");
+        assert_eq!(amounts.value_as_string(1), "1.234567890123");
+        let fees = column("Fee").as_primitive::<Decimal128Type>();
+        assert_eq!(fees.value_as_string(0), "0.000000000000");
+        assert_eq!(fees.value_as_string(1), "0.001000000000");
+        assert_eq!(
+            column("Success").as_boolean().iter().collect::<Vec<_>>(),
+            vec![Some(true), Some(false)]
+        );
+        assert_eq!(
+            column("Symbol")
+                .as_string::<i32>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Some("DOT"), Some("KSM")]
+        );
+    }
+
+    #[tokio::test]
+    async fn generate_display_columns() {
+        let alice = Context::alice();
+        let bob = Context::bob();
+        let transfer = |to: &Context, to_account_display| {
+            entry(
+                &alice,
+                Transfer {
+                    from: alice.stash.clone(),
+                    to: to.stash.clone(),
+                    amount: "10000000000".into(),
+                    to_account_display,
+                    ..Default::default()
+                },
+            )
+        };
+
+        // A named counterparty, a sub-account of a named parent and an
+        // account without an identity.
+        let data = vec![
+            transfer(
+                &bob,
+                ToAccountDisplay {
+                    display: "Bob".to_string(),
+                    identity: true,
+                    ..Default::default()
+                },
+            ),
+            transfer(
+                &bob,
+                ToAccountDisplay {
+                    display: "stash".to_string(),
+                    parent: Some(Parent {
+                        display: "Bob".to_string(),
+                        sub_symbol: "stash".to_string(),
+                        identity: true,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ),
+            transfer(&Context::eve(), Default::default()),
+        ];
+
+        let reader = memory_db().await.reader();
+        let contexts = Arc::new(RwLock::new(vec![alice.clone()]));
+
+        // Only included if selected.
+        let generator = TransferReportGenerator::new(reader.clone(), Arc::clone(&contexts));
+        let reports =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+        let payload = GoogleStoragePayload::from(reports.into_iter().next().unwrap());
+        assert!(!std::str::from_utf8(&payload.body)
+            .unwrap()
+            .contains("Display"));
+
+        let columns: Vec<TransferColumn> =
+            serde_yaml::from_str("[to, to_display, from_display]").unwrap();
+        let generator = TransferReportGenerator::new(reader, contexts).with_columns(Some(columns));
+        let reports =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+        let payload = GoogleStoragePayload::from(reports.into_iter().next().unwrap());
+
+        assert_eq!(
+            std::str::from_utf8(&payload.body).unwrap(),
+            format!(
+                "To,To Display,From Display\n{},Bob,\n{},Bob/stash,\n{},,\n",
+                bob.stash,
+                bob.stash,
+                Context::eve().stash
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn generate_labels_column() {
+        let mut alice = Context::alice();
+        alice
+            .labels
+            .insert("team".to_string(), "treasury".to_string());
+        alice
+            .labels
+            .insert("category".to_string(), "validator".to_string());
+
+        let transfer = Transfer {
+            amount: "10000000000".into(),
+            ..Default::default()
+        };
+        let labeled = entry(&alice, transfer.clone());
+        // Stored before the account was labeled.
+        let mut unlabeled = entry(&alice, transfer);
+        unlabeled.labels.clear();
+
+        let columns: Vec<TransferColumn> = serde_yaml::from_str("[labels]").unwrap();
+        let generator = TransferReportGenerator::new(
+            memory_db().await.reader(),
+            Arc::new(RwLock::new(vec![alice.clone()])),
+        )
+        .with_columns(Some(columns));
+        let reports = <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(
+            &generator,
+            &vec![labeled, unlabeled],
+        )
+        .await
+        .unwrap();
+        let payload = GoogleStoragePayload::from(reports.into_iter().next().unwrap());
+
+        assert_eq!(
+            std::str::from_utf8(&payload.body).unwrap(),
+            "Labels\ncategory=validator;team=treasury\n\"\"\n"
+        );
+    }
+
+    #[test]
+    fn column_names_match_header() {
+        let columns: Vec<TransferColumn> = serde_yaml::from_str(
+            "[network, block_number, block_timestamp, from, description, to, amount, symbol, \
+             fee, extrinsic_index, success, from_display, to_display, labels]",
+        )
+        .unwrap();
+
+        let (header, _) = select_columns(&columns, vec![]);
+        assert_eq!(header, COLUMNS);
+        assert_eq!(HEADER[..], COLUMNS[..HEADER.len()]);
+    }
+
+    #[tokio::test]
+    async fn generate_monthly_buckets() {
+        let alice = Context::alice();
+        let transfer = |block_timestamp: u64| {
+            entry(
+                &alice,
+                Transfer {
+                    from: alice.stash.clone(),
+                    amount: "10000000000".into(),
+                    block_timestamp: Timestamp::from(block_timestamp),
+                    ..Default::default()
+                },
+            )
+        };
+
+        // The last second of January, the first one of February and mid
+        // March 2024 (UTC).
+        let data = vec![
+            transfer(1706745599),
+            transfer(1706745600),
+            transfer(1710460800),
+        ];
+
+        let reader = memory_db().await.reader();
+        let generator =
+            TransferReportGenerator::new(reader, Arc::new(RwLock::new(vec![alice.clone()])))
+                .with_buckets(Some(ReportBuckets {
+                    start: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                    end: Some(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()),
+                    size: BucketSize::Monthly,
+                }));
+
+        let reports =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+
+        let payloads: Vec<GoogleStoragePayload> = reports.into_iter().map(Into::into).collect();
+        let files: Vec<(&str, usize)> = payloads
+            .iter()
+            .map(|payload| {
+                let rows = std::str::from_utf8(&payload.body).unwrap().lines().count() - 1;
+                (payload.name.as_str(), rows)
+            })
+            .collect();
+
+        // Months without transfers result in a file too.
+        assert_eq!(
+            files,
+            vec![
+                ("report_transfer_2024-01-01_2024-01-31.csv", 1),
+                ("report_transfer_2024-02-01_2024-02-29.csv", 1),
+                ("report_transfer_2024-03-01_2024-03-31.csv", 1),
+                ("report_transfer_2024-04-01_2024-04-30.csv", 0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn split_into_parts() {
+        let alice = Context::alice();
+        let data: Vec<ContextData<Transfer>> = (0..5)
+            .map(|idx| {
+                entry(
+                    &alice,
+                    Transfer {
+                        amount: "10000000000".into(),
+                        extrinsic_index: format!("{}-1", idx).into(),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+
+        let reader = memory_db().await.reader();
+        let contexts = Arc::new(RwLock::new(vec![alice.clone()]));
+
+        let files = |max_rows| {
+            let generator = TransferReportGenerator::new(reader.clone(), Arc::clone(&contexts))
+                .with_max_rows(Some(max_rows));
+            let data = &data;
+
+            async move {
+                let reports = <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(
+                    &generator, data,
+                )
+                .await
+                .unwrap();
+
+                reports
+                    .into_iter()
+                    .map(|report| {
+                        let payload = GoogleStoragePayload::from(report);
+                        let rows = std::str::from_utf8(&payload.body).unwrap().lines().count() - 1;
+                        (payload.name, rows)
+                    })
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(
+            files(2).await,
+            vec![
+                ("report_transfer_part1.csv".to_string(), 2),
+                ("report_transfer_part2.csv".to_string(), 2),
+                ("report_transfer_part3.csv".to_string(), 1),
+            ]
+        );
+        // Reports which fit are not split.
+        assert_eq!(files(5).await, vec![("report_transfer.csv".to_string(), 5)]);
+        assert_eq!(files(0).await, vec![("report_transfer.csv".to_string(), 5)]);
+    }
+
+    #[tokio::test]
+    async fn generate_for_window() {
+        let alice = Context::alice();
+        let transfer = |block_timestamp: u64| {
+            entry(
+                &alice,
+                Transfer {
+                    from: alice.stash.clone(),
+                    amount: "10000000000".into(),
+                    block_timestamp: Timestamp::from(block_timestamp),
+                    ..Default::default()
+                },
+            )
+        };
+
+        // The last second of January, the first one of February and mid
+        // March 2024 (UTC).
+        let data = vec![
+            transfer(1706745599),
+            transfer(1706745600),
+            transfer(1710460800),
+        ];
+
+        let reader = memory_db().await.reader();
+        let generator =
+            TransferReportGenerator::new(reader, Arc::new(RwLock::new(vec![alice.clone()])))
+                .with_buckets(Some(ReportBuckets {
+                    start: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                    end: None,
+                    size: BucketSize::Monthly,
+                }))
+                .with_window(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+                );
+
+        let reports =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+
+        // A single report of the window, regardless of the buckets.
+        let payloads: Vec<GoogleStoragePayload> = reports.into_iter().map(Into::into).collect();
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(
+            payloads[0].name,
+            "report_transfer_2024-02-01_2024-02-29.csv"
+        );
+
+        let body = std::str::from_utf8(&payloads[0].body).unwrap();
+        assert_eq!(body.lines().count() - 1, 1);
+    }
+
+    #[tokio::test]
+    async fn generate_sorted_by_amount() {
+        let db = db().await;
+        let alice = Context::alice();
+
+        // Amounts are not in order of the blocks, and would be in a different
+        // order if compared as strings.
+        let amounts = ["20000000000", "100000000000", "5000000000", "30000000000"];
+
+        let mut resp: Response<TransfersPage> = Default::default();
+        resp.data.transfers = Some(
+            amounts
+                .iter()
+                .enumerate()
+                .map(|(idx, amount)| Transfer {
+                    amount: (*amount).into(),
+                    block_num: BlockNumber::from(idx as u64),
+                    extrinsic_index: idx.to_string().into(),
+                    ..Default::default()
+                })
+                .collect(),
+        );
+
+        db.store_transfer_event(&alice, &resp).await.unwrap();
+
+        let generator =
+            TransferReportGenerator::new(db.reader(), Arc::new(RwLock::new(vec![alice])))
+                .with_sort(TransferSort {
+                    key: TransferSortKey::Amount,
+                    direction: SortDirection::Descending,
+                });
+
+        let data =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::fetch_data(&generator)
+                .await
+                .unwrap()
+                .unwrap();
+        let reports =
+            <TransferReportGenerator as GenerateReport<GoogleStorage>>::generate(&generator, &data)
+                .await
+                .unwrap();
+
+        let amounts: Vec<&str> = std::str::from_utf8(&reports[0].body)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|row| row.split(',').nth(6).unwrap())
+            .collect();
+
+        assert_eq!(amounts, vec!["10", "3", "2", "0.5"]);
+    }
+}
@@ -0,0 +1,485 @@
+use crate::chain_api::{
+    ChainBackend, Nomination, NominationsPage, Response, RewardsSlashesPage, StakingActionsPage,
+    StashAccountDisplay, TransfersPage,
+};
+use crate::{Context, Network, Result};
+use blake2::{Blake2b512, Digest};
+use futures::{SinkExt, StreamExt};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::hash::Hasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use twox_hash::XxHash64;
+
+const SS58_CHECKSUM_PREFIX: &[u8] = b"SS58PRE";
+const SS58_CHECKSUM_LEN: usize = 2;
+const ACCOUNT_ID_LEN: usize = 32;
+
+/// How long a request over a websocket may take. A stalled connection would
+/// otherwise block all further requests to the node.
+const WEBSOCKET_TIMEOUT: u64 = 30;
+
+type WebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Queries account data directly from the JSON-RPC endpoint of a node, either
+/// via websocket (`ws://`, `wss://`) or HTTP, without relying on Subscan.
+/// Nodes only expose the current chain state, so historic data such as
+/// transfers, rewards/slashes or staking actions cannot be retrieved this way
+/// and requires an indexer.
+pub struct RpcBackend {
+    client: Client,
+    endpoints: HashMap<Network, String>,
+    /// The connection to each websocket endpoint. Opened on first use and
+    /// re-opened after a failed request.
+    sockets: HashMap<Network, Mutex<Option<WebSocket>>>,
+    request_id: AtomicU64,
+}
+
+impl RpcBackend {
+    pub fn new(endpoints: HashMap<Network, String>) -> Self {
+        RpcBackend {
+            client: Client::new(),
+            sockets: endpoints
+                .iter()
+                .filter(|(_, endpoint)| is_websocket(endpoint))
+                .map(|(network, _)| (*network, Mutex::new(None)))
+                .collect(),
+            endpoints,
+            request_id: AtomicU64::new(0),
+        }
+    }
+    async fn request<R: DeserializeOwned>(
+        &self,
+        network: Network,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<R> {
+        let endpoint = self
+            .endpoints
+            .get(&network)
+            .ok_or_else(|| anyhow!("no RPC endpoint configured for {}", network.as_str()))?;
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": self.request_id.fetch_add(1, Ordering::Relaxed),
+            "method": method,
+            "params": params,
+        });
+
+        let resp = if is_websocket(endpoint) {
+            timeout(
+                Duration::from_secs(WEBSOCKET_TIMEOUT),
+                self.request_websocket(network, endpoint, &request),
+            )
+            .await
+            .map_err(|_| anyhow!("RPC request '{}' timed out", method))??
+        } else {
+            self.client
+                .post(endpoint)
+                .json(&request)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?
+        };
+
+        if let Some(err) = resp.error {
+            return Err(anyhow!(
+                "RPC request '{}' failed: {} ({})",
+                method,
+                err.message,
+                err.code
+            ));
+        }
+
+        Ok(serde_json::from_value(
+            resp.result.unwrap_or(serde_json::Value::Null),
+        )?)
+    }
+    async fn request_websocket(
+        &self,
+        network: Network,
+        endpoint: &str,
+        request: &serde_json::Value,
+    ) -> Result<RpcResponse> {
+        let mut socket = self
+            .sockets
+            .get(&network)
+            .ok_or_else(|| anyhow!("no websocket configured for {}", network.as_str()))?
+            .lock()
+            .await;
+
+        // The connection is only kept if the request succeeds, otherwise its
+        // state is unknown.
+        let mut ws = match socket.take() {
+            Some(ws) => ws,
+            None => connect_async(endpoint).await?.0,
+        };
+
+        ws.send(Message::text(request.to_string())).await?;
+        // Other messages, e.g. of subscriptions, are skipped. Pings are
+        // answered by the client itself.
+        let resp = loop {
+            let text = match ws.next().await {
+                Some(Ok(Message::Text(text))) => text,
+                Some(Ok(Message::Close(_))) | None => {
+                    return Err(anyhow!("websocket connection closed by {}", endpoint))
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Err(err.into()),
+            };
+
+            let resp: RpcResponse = serde_json::from_str(&text)?;
+            if resp.id == request["id"] {
+                break resp;
+            }
+        };
+
+        *socket = Some(ws);
+
+        Ok(resp)
+    }
+    fn unsupported<T>(context: &Context, data: &str) -> Result<T> {
+        Err(anyhow!(
+            "{} of {:?} cannot be fetched from RPC nodes, an indexer such as Subscan is required",
+            data,
+            context
+        ))
+    }
+}
+
+#[async_trait]
+impl ChainBackend for RpcBackend {
+    async fn request_transfer(
+        &self,
+        context: &Context,
+        _row: usize,
+        _page: usize,
+    ) -> Result<Response<TransfersPage>> {
+        Self::unsupported(context, "transfers")
+    }
+    async fn request_reward_slash(
+        &self,
+        context: &Context,
+        _row: usize,
+        _page: usize,
+    ) -> Result<Response<RewardsSlashesPage>> {
+        Self::unsupported(context, "rewards/slashes")
+    }
+    async fn request_staking_actions(
+        &self,
+        context: &Context,
+        _row: usize,
+        _page: usize,
+    ) -> Result<Response<StakingActionsPage>> {
+        Self::unsupported(context, "staking actions")
+    }
+    async fn request_nominations(&self, context: &Context) -> Result<Response<NominationsPage>> {
+        let account = decode_ss58(&context.stash)?;
+
+        let storage: Option<String> = self
+            .request(
+                context.network,
+                "state_getStorage",
+                serde_json::json!([nominators_storage_key(&account)]),
+            )
+            .await?;
+
+        // The account does not nominate anyone if there is no storage entry.
+        let targets = match storage {
+            Some(value) => {
+                decode_nomination_targets(&hex::decode(value.trim_start_matches("0x"))?)?
+            }
+            None => vec![],
+        };
+
+        let list = if targets.is_empty() {
+            None
+        } else {
+            Some(
+                targets
+                    .iter()
+                    .map(|target| Nomination {
+                        stash_account_display: StashAccountDisplay {
+                            address: encode_ss58(context.network.ss58_prefix(), target),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .collect(),
+            )
+        };
+
+        Ok(Response {
+            code: Some(0),
+            data: NominationsPage { list },
+            message: "Success".to_string(),
+            ttl: None,
+        })
+    }
+}
+
+/// Whether the endpoint is a websocket (`ws://`, `wss://`) instead of HTTP.
+pub(crate) fn is_websocket(endpoint: &str) -> bool {
+    endpoint.starts_with("ws://") || endpoint.starts_with("wss://")
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    #[serde(default)]
+    id: serde_json::Value,
+    result: Option<serde_json::Value>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+fn twox_64(data: &[u8], seed: u64) -> [u8; 8] {
+    let mut hasher = XxHash64::with_seed(seed);
+    hasher.write(data);
+    hasher.finish().to_le_bytes()
+}
+
+fn twox_128(data: &[u8]) -> Vec<u8> {
+    [twox_64(data, 0), twox_64(data, 1)].concat()
+}
+
+/// The storage key of the `Staking::Nominators` map entry of the account,
+/// which uses the `Twox64Concat` hasher.
+fn nominators_storage_key(account: &[u8; ACCOUNT_ID_LEN]) -> String {
+    let key = [
+        twox_128(b"Staking"),
+        twox_128(b"Nominators"),
+        twox_64(account, 0).to_vec(),
+        account.to_vec(),
+    ]
+    .concat();
+
+    format!("0x{}", hex::encode(key))
+}
+
+/// Decodes the SCALE encoded targets of a `Nominations` entry. The remaining
+/// fields (`submitted_in`, `suppressed`) are not relevant for monitoring.
+fn decode_nomination_targets(data: &[u8]) -> Result<Vec<[u8; ACCOUNT_ID_LEN]>> {
+    let (len, offset) = decode_compact_len(data)?;
+    let end = offset + len * ACCOUNT_ID_LEN;
+
+    data.get(offset..end)
+        .ok_or_else(|| anyhow!("invalid nominations storage entry"))?
+        .chunks(ACCOUNT_ID_LEN)
+        .map(|chunk| Ok(chunk.try_into()?))
+        .collect()
+}
+
+/// Decodes a SCALE compact encoded length, returning the length and the
+/// amount of bytes it occupied.
+fn decode_compact_len(data: &[u8]) -> Result<(usize, usize)> {
+    let invalid = || anyhow!("invalid SCALE compact length");
+    let first = *data.first().ok_or_else(invalid)?;
+
+    match first & 0b11 {
+        0 => Ok(((first >> 2) as usize, 1)),
+        1 => {
+            let bytes = data.get(..2).ok_or_else(invalid)?;
+            Ok(((u16::from_le_bytes(bytes.try_into()?) >> 2) as usize, 2))
+        }
+        2 => {
+            let bytes = data.get(..4).ok_or_else(invalid)?;
+            Ok(((u32::from_le_bytes(bytes.try_into()?) >> 2) as usize, 4))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn ss58_checksum(data: &[u8]) -> Vec<u8> {
+    let mut hasher = Blake2b512::new();
+    hasher.update(SS58_CHECKSUM_PREFIX);
+    hasher.update(data);
+    hasher.finalize()[..SS58_CHECKSUM_LEN].to_vec()
+}
+
+/// Decodes an SS58 address with a single byte network prefix into the raw
+/// account ID.
+pub(crate) fn decode_ss58(address: &str) -> Result<[u8; ACCOUNT_ID_LEN]> {
+    let data = bs58::decode(address).into_vec()?;
+    if data.len() != 1 + ACCOUNT_ID_LEN + SS58_CHECKSUM_LEN || data[0] >= 64 {
+        return Err(anyhow!("unsupported SS58 address: {}", address));
+    }
+
+    let (payload, checksum) = data.split_at(1 + ACCOUNT_ID_LEN);
+    if ss58_checksum(payload) != checksum {
+        return Err(anyhow!("invalid SS58 checksum of address: {}", address));
+    }
+
+    Ok(payload[1..].try_into()?)
+}
+
+pub(crate) fn encode_ss58(prefix: u8, account: &[u8; ACCOUNT_ID_LEN]) -> String {
+    let mut data = vec![prefix];
+    data.extend_from_slice(account);
+    let checksum = ss58_checksum(&data);
+    data.extend(checksum);
+
+    bs58::encode(data).into_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const ADDRESS: &str = "11uMPbeaEDJhUxzU4ZfWW9VQEsryP9XqFcNRfPdYda6aFWJ";
+
+    /// Starts a websocket server which accepts a single connection and sends
+    /// the messages returned by `respond` for each received message.
+    async fn mock_node<F>(respond: F) -> String
+    where
+        F: Fn(String) -> Vec<String> + Send + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+            while let Some(Ok(message)) = ws.next().await {
+                if let Message::Text(text) = message {
+                    for resp in respond(text.to_string()) {
+                        ws.send(Message::text(resp)).await.unwrap();
+                    }
+                }
+            }
+        });
+
+        url
+    }
+
+    #[test]
+    fn ss58_roundtrip() {
+        let account = decode_ss58(ADDRESS).unwrap();
+        assert_eq!(encode_ss58(0, &account), ADDRESS);
+
+        // Invalid checksum
+        let mut invalid = ADDRESS.to_string();
+        invalid.pop();
+        invalid.push('X');
+        assert!(decode_ss58(&invalid).is_err());
+    }
+
+    #[test]
+    fn nominators_storage_key_prefix() {
+        let key = nominators_storage_key(&[0; ACCOUNT_ID_LEN]);
+        assert!(
+            key.starts_with("0x5f3e4907f716ac89b6347d15ececedca9c6a637f62ae2af1c7e31eed7e96be04")
+        );
+        // Prefix, hashed account and the account itself.
+        assert_eq!(key.len(), 2 + (32 + 8 + ACCOUNT_ID_LEN) * 2);
+    }
+
+    #[test]
+    fn decode_nominations() {
+        // Two targets, `submitted_in` and `suppressed`.
+        let mut data = vec![2 << 2];
+        data.extend_from_slice(&[1; ACCOUNT_ID_LEN]);
+        data.extend_from_slice(&[2; ACCOUNT_ID_LEN]);
+        data.extend_from_slice(&100u32.to_le_bytes());
+        data.push(0);
+
+        assert_eq!(
+            decode_nomination_targets(&data).unwrap(),
+            vec![[1; ACCOUNT_ID_LEN], [2; ACCOUNT_ID_LEN]]
+        );
+
+        // Truncated entry
+        assert!(decode_nomination_targets(&data[..40]).is_err());
+    }
+
+    #[tokio::test]
+    async fn request_nominations_from_mock_node() {
+        let server = MockServer::start().await;
+        let account = decode_ss58(ADDRESS).unwrap();
+
+        let mut storage = vec![1 << 2];
+        storage.extend_from_slice(&account);
+        storage.extend_from_slice(&100u32.to_le_bytes());
+        storage.push(0);
+
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "method": "state_getStorage",
+                "params": [nominators_storage_key(&account)],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": format!("0x{}", hex::encode(storage)),
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let backend = RpcBackend::new(
+            vec![(Network::Polkadot, server.uri())]
+                .into_iter()
+                .collect(),
+        );
+        let context = Context::from(ADDRESS);
+        let resp = backend.request_nominations(&context).await.unwrap();
+
+        let list = resp.data.list.unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].stash_account_display.address, ADDRESS);
+
+        // Historic data is not available.
+        assert!(backend.request_transfer(&context, 10, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn request_nominations_over_websocket() {
+        let url = mock_node(|message| {
+            let request: serde_json::Value = serde_json::from_str(&message).unwrap();
+            assert_eq!(request["method"], "state_getStorage");
+
+            // A notification of a subscription before the actual response.
+            vec![
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "chain_newHead",
+                    "params": {},
+                })
+                .to_string(),
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": null,
+                })
+                .to_string(),
+            ]
+        })
+        .await;
+
+        let backend = RpcBackend::new(vec![(Network::Polkadot, url)].into_iter().collect());
+        let context = Context::from(ADDRESS);
+
+        // The connection is reused for further requests, the mock node only
+        // accepts a single one.
+        for _ in 0..2 {
+            let resp = backend.request_nominations(&context).await.unwrap();
+            assert!(resp.data.list.is_none());
+        }
+    }
+}
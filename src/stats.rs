@@ -0,0 +1,104 @@
+use crate::Timestamp;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Counters of the fetchers and report generators, to check on the monitor
+/// without a metrics backend. The counters are updated without locks, so a
+/// single instance can be shared by all fetchers and report generators.
+#[derive(Debug, Default)]
+pub struct Stats {
+    requests: AtomicU64,
+    inserted: AtomicU64,
+    errors: AtomicU64,
+    reports: AtomicU64,
+    /// In seconds since the UNIX epoch, zero if no cycle completed yet.
+    last_cycle_at: AtomicU64,
+    last_cycle_millis: AtomicU64,
+}
+
+/// The values of `Stats` at the time of `Stats::snapshot`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StatsSnapshot {
+    /// The requests made to the chain backend.
+    pub requests: u64,
+    /// The entries newly inserted into the database.
+    pub inserted: u64,
+    /// The failed fetcher cycles and report runs, and the accounts which
+    /// failed to be fetched within a cycle.
+    pub errors: u64,
+    /// The published reports.
+    pub reports: u64,
+    /// When the last fetcher cycle over all accounts completed.
+    pub last_cycle_at: Option<Timestamp>,
+    /// How long the last completed fetcher cycle took.
+    pub last_cycle_duration: Option<Duration>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub(crate) fn add_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+    pub(crate) fn add_inserted(&self, inserted: usize) {
+        self.inserted.fetch_add(inserted as u64, Ordering::Relaxed);
+    }
+    pub(crate) fn add_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+    pub(crate) fn add_report(&self) {
+        self.reports.fetch_add(1, Ordering::Relaxed);
+    }
+    pub(crate) fn cycle_completed(&self, duration: Duration) {
+        self.last_cycle_millis
+            .store(duration.as_millis() as u64, Ordering::Relaxed);
+        self.last_cycle_at
+            .store(Timestamp::now().as_secs(), Ordering::Relaxed);
+    }
+    /// Reads the current values. Each counter is read individually, so the
+    /// values of concurrently running fetchers might be slightly apart.
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let last_cycle_at = self.last_cycle_at.load(Ordering::Relaxed);
+        let completed = last_cycle_at != 0;
+
+        StatsSnapshot {
+            requests: self.requests.load(Ordering::Relaxed),
+            inserted: self.inserted.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            reports: self.reports.load(Ordering::Relaxed),
+            last_cycle_at: completed.then(|| Timestamp::from(last_cycle_at)),
+            last_cycle_duration: completed
+                .then(|| Duration::from_millis(self.last_cycle_millis.load(Ordering::Relaxed))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_of_counters() {
+        let stats = Stats::new();
+        assert_eq!(stats.snapshot(), StatsSnapshot::default());
+
+        stats.add_request();
+        stats.add_request();
+        stats.add_inserted(10);
+        stats.add_error();
+        stats.add_report();
+        stats.cycle_completed(Duration::from_millis(1500));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.requests, 2);
+        assert_eq!(snapshot.inserted, 10);
+        assert_eq!(snapshot.errors, 1);
+        assert_eq!(snapshot.reports, 1);
+        assert!(snapshot.last_cycle_at.is_some());
+        assert_eq!(
+            snapshot.last_cycle_duration,
+            Some(Duration::from_millis(1500))
+        );
+    }
+}